@@ -0,0 +1,55 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use crlox::{Backend, Interpreter};
+
+/*
+Benchmarks
+
+Runs each script in bench/ on the tree-walker and the VM, RUNS times each in a fresh interpreter, and
+writes out the fastest and median times. What the scripts print is thrown away. Run with cargo bench,
+or cargo bench -- calls to only run the scripts whose names hold "calls".
+*/
+
+const RUNS: usize = 5;
+
+fn main() {
+    let filters: Vec<String> = std::env::args().skip(1).filter(|a| !a.starts_with("--")).collect();
+    let directory: PathBuf = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("bench");
+    let mut scripts: Vec<PathBuf> = fs::read_dir(&directory).expect("Could not read bench/")
+        .filter_map(|a| a.ok().map(|b| b.path()))
+        .filter(|a| a.extension().is_some_and(|b| b == "lox"))
+        .collect();
+    scripts.sort();
+
+    println!("{:<16} {:<12} {:>10} {:>10}", "script", "backend", "fastest", "median");
+    for script in scripts {
+        let name: String = script.file_stem().map(|a| a.to_string_lossy().to_string()).unwrap_or_default();
+        if !filters.is_empty() && !filters.iter().any(|a| name.contains(a.as_str())) {
+            continue;
+        }
+
+        let source: String = fs::read_to_string(&script).expect("Could not read a benchmark");
+        for (label, backend) in [("tree-walker", Backend::TreeWalker), ("vm", Backend::Vm)] {
+            let mut times: Vec<Duration> = (0..RUNS).map(|_| run(&source, backend)).collect();
+            times.sort();
+            println!("{:<16} {:<12} {:>8.1}ms {:>8.1}ms", name, label, millis(times[0]), millis(times[RUNS / 2]));
+        }
+    }
+}
+
+fn run(source: &str, backend: Backend) -> Duration {
+    let mut interpreter: Interpreter = Interpreter::default();
+    interpreter.set_backend(backend);
+    interpreter.set_output(std::io::sink());
+
+    let start: Instant = Instant::now();
+    if let Err(a) = interpreter.run(source) {
+        panic!("A benchmark failed: {}", a[0]);
+    }
+    start.elapsed()
+}
+
+fn millis(time: Duration) -> f64 {
+    time.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,197 @@
+use std::rc::Rc;
+use crate::expression::{Expr, ExprVisitor, Slot};
+use crate::interpreter::Interpreter;
+use crate::stmt::{Stmt, StmtVisitor};
+use crate::token::{Literal, Token};
+
+/*
+AST printer
+
+Renders statements as Lisp style S-expressions, to see what the parser made of some source: how it
+grouped operators, and what it turned for loops into. Expressions are written on one line, with the
+operator first, eg. (+ 1 (* 2 3)). Statements holding other statements put each of those on a line
+of its own, indented under them, with the closing parentheses gathered at the end of the last line.
+*/
+pub struct AstPrinter {
+    output: String,
+    indent: usize
+}
+
+impl AstPrinter {
+    pub fn ast_printer_builder() -> AstPrinter {
+        return AstPrinter {
+            output: String::new(),
+            indent: 0
+        }
+    }
+
+    pub fn print(&mut self, statements: &[Stmt]) -> String {
+        for statement in statements {
+            self.execute(statement);
+        }
+        self.output.push('\n');
+        return std::mem::take(&mut self.output);
+    }
+
+    fn expression(&mut self, expr: &Expr) -> String {
+        return self.visit(expr).unwrap_or_default();
+    }
+
+    // Start a line at the current indent
+    fn line(&mut self, text: &str) {
+        if !self.output.is_empty() {
+            self.output.push('\n');
+        }
+        self.output.push_str(&"  ".repeat(self.indent));
+        self.output.push_str(text);
+    }
+
+    // A statement holding others, which "children" writes out one level further in
+    fn nested<F: FnOnce(&mut Self)>(&mut self, head: &str, children: F) {
+        self.line(&format!("({}", head));
+        self.indent += 1;
+        children(self);
+        self.indent -= 1;
+        self.output.push(')');
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.execute(statement);
+        }
+    }
+
+    // An expression with its operands, eg. (+ 1 2)
+    fn parenthesize(&mut self, name: &str, exprs: &[&Expr]) -> Option<String> {
+        let mut text: String = format!("({}", name);
+        for expr in exprs {
+            text.push(' ');
+            text.push_str(&self.expression(expr));
+        }
+        text.push(')');
+        return Some(text);
+    }
+}
+
+impl StmtVisitor<> for AstPrinter {
+    fn visit_expression(&mut self, expression: &Expr) {
+        let text: String = self.expression(expression);
+        self.line(&format!("(expr {})", text));
+    }
+
+    fn visit_print(&mut self, expression: &Expr) {
+        let text: String = self.expression(expression);
+        self.line(&format!("(print {})", text));
+    }
+
+    fn visit_var(&mut self, name: &Token, right: Option<&Expr>) {
+        match right {
+            Some(a) => {
+                let text: String = self.expression(a);
+                self.line(&format!("(var {} {})", name.lexeme, text));
+            }
+            None => self.line(&format!("(var {})", name.lexeme))
+        }
+    }
+
+    fn visit_block(&mut self, statements: &[Stmt]) {
+        self.nested("block", |a| a.statements(statements));
+    }
+
+    fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>) {
+        let head: String = format!("if {}", self.expression(condition));
+        self.nested(&head, |a| {
+            a.execute(then_branch);
+            if let Some(b) = else_branch {
+                a.execute(b);
+            }
+        });
+    }
+
+    fn visit_while(&mut self, _keyword: &Token, condition: &Expr, body: &Stmt) {
+        let head: String = format!("while {}", self.expression(condition));
+        self.nested(&head, |a| a.execute(body));
+    }
+
+    fn visit_try(&mut self, try_branch: &[Stmt], name: Option<&Token>, catch_branch: Option<&[Stmt]>, finally_branch: Option<&[Stmt]>) {
+        self.nested("try", |a| {
+            a.statements(try_branch);
+            if let Some(statements) = catch_branch {
+                let head: String = match name {
+                    Some(b) => format!("catch {}", b.lexeme),
+                    None => "catch".to_string()
+                };
+                a.nested(&head, |b| b.statements(statements));
+            }
+            if let Some(statements) = finally_branch {
+                a.nested("finally", |b| b.statements(statements));
+            }
+        });
+    }
+
+    fn visit_throw(&mut self, _keyword: &Token, value: &Expr) {
+        let text: String = self.expression(value);
+        self.line(&format!("(throw {})", text));
+    }
+
+    fn visit_function(&mut self, name: &Token, params: &[Token], body: &Rc<Vec<Stmt>>) {
+        let params: Vec<String> = params.iter().map(|a| a.lexeme.to_string()).collect();
+        let head: String = format!("fun {} ({})", name.lexeme, params.join(" "));
+        self.nested(&head, |a| a.statements(body));
+    }
+
+    fn visit_return(&mut self, _keyword: &Token, value: Option<&Expr>) {
+        match value {
+            Some(a) => {
+                let text: String = self.expression(a);
+                self.line(&format!("(return {})", text));
+            }
+            None => self.line("(return)")
+        }
+    }
+}
+
+impl ExprVisitor<String> for AstPrinter {
+    fn visit_binary(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Option<String> {
+        return self.parenthesize(&operator.lexeme.text(), &[left, right]);
+    }
+
+    fn visit_grouping(&mut self, expression: &Expr) -> Option<String> {
+        return self.parenthesize("group", &[expression]);
+    }
+
+    fn visit_literal(&mut self, value: &Literal) -> Option<String> {
+        match value {
+            Literal::Str(a) => return Some(format!("{:?}", a)),
+            Literal::Nill => return Some("nil".to_string()),
+            _ => return Some(Interpreter::stringify(value.clone()))
+        }
+    }
+
+    fn visit_unary(&mut self, operator: &Token, right: &Expr) -> Option<String> {
+        return self.parenthesize(&operator.lexeme.text(), &[right]);
+    }
+
+    fn visit_variable(&mut self, token: &Token, _slot: Option<Slot>) -> Option<String> {
+        return Some(token.lexeme.to_string());
+    }
+
+    fn visit_assignment(&mut self, name: &Token, value: &Expr, _slot: Option<Slot>) -> Option<String> {
+        return self.parenthesize(&format!("= {}", name.lexeme), &[value]);
+    }
+
+    fn visit_logical(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Option<String> {
+        return self.parenthesize(&operator.lexeme.text(), &[left, right]);
+    }
+
+    fn visit_call(&mut self, callee: &Expr, _paren: &Token, arguments: &[Expr]) -> Option<String> {
+        let mut exprs: Vec<&Expr> = vec![callee];
+        exprs.extend(arguments.iter());
+        return self.parenthesize("call", &exprs);
+    }
+
+    fn visit_get(&mut self, object: &Expr, name: &Token) -> Option<String> {
+        let text: String = self.expression(object);
+        return Some(format!("(. {} {})", text, name.lexeme));
+    }
+}
@@ -0,0 +1,238 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use crate::chunk::Prototype;
+use crate::environment::SharedEnvironment;
+use crate::error::RuntimeError;
+use crate::gc::Tracer;
+use crate::interpreter::Interpreter;
+use crate::stmt::Stmt;
+use crate::token::{Literal, Token};
+
+// Signature every native function implemented in Rust must follow.
+pub type NativeFn = fn(&mut Interpreter, Vec<Literal>) -> Result<Literal, String>;
+
+// Signature of a built-in method, which also receives the value it was accessed on.
+pub type MethodFn = fn(&mut Interpreter, &Literal, Vec<Literal>) -> Result<Literal, String>;
+
+// Signature of a function registered by the host embedding the interpreter, any closure will do.
+pub type HostFn = dyn Fn(&mut Interpreter, &[Literal]) -> Result<Literal, RuntimeError>;
+
+// Anything that can be called from Lox with the "(" arguments ")" syntax.
+pub trait LoxCallable: fmt::Debug {
+    fn arity(&self) -> usize;
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, RuntimeError>;
+    fn name(&self) -> String;
+
+    // Visit each value and scope the callable holds on to, for the garbage collector and measuring memory.
+    // Gives back false if they couldn't be looked at as they are in use.
+    fn trace(&self, _tracer: &mut dyn Tracer) -> bool {
+        return true;
+    }
+
+    // Let go of every value the callable holds on to, once the garbage collector finds it unreachable
+    fn clear(&self) {}
+
+    // The callable as a function compiled for the virtual machine, which it calls without going through call
+    fn closure(&self) -> Option<&Closure> {
+        return None;
+    }
+}
+
+// A function implemented in Rust and exposed to Lox through the global environment.
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    pub function: NativeFn
+}
+
+impl LoxCallable for NativeFunction {
+    fn arity(&self) -> usize {
+        return self.arity;
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, RuntimeError> {
+        return interpreter.call_native(arguments, |a, b| (self.function)(a, b).map_err(RuntimeError::native));
+    }
+
+    fn name(&self) -> String {
+        return self.name.clone();
+    }
+}
+
+// Display a NativeFunction
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+// A closure registered by the host, see Interpreter::register_fn.
+pub struct HostFunction {
+    pub name: String,
+    pub arity: usize,
+    pub function: Rc<HostFn>
+}
+
+impl LoxCallable for HostFunction {
+    fn arity(&self) -> usize {
+        return self.arity;
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, RuntimeError> {
+        let value: Literal = interpreter.call_native(arguments, |a, b| (self.function)(a, &b))?;
+        interpreter.track(&value);
+        return Ok(value);
+    }
+
+    fn name(&self) -> String {
+        return self.name.clone();
+    }
+}
+
+// Display a HostFunction
+impl fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+// A built-in method looked up on a value (eg. "abc".upper), bound to that value.
+pub struct BoundMethod {
+    pub receiver: RefCell<Literal>,
+    pub name: String,
+    pub arity: usize,
+    pub method: MethodFn
+}
+
+impl LoxCallable for BoundMethod {
+    fn arity(&self) -> usize {
+        return self.arity;
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, RuntimeError> {
+        let receiver = self.receiver.borrow();
+        return interpreter.call_native(arguments, |a, b| (self.method)(a, &receiver, b).map_err(RuntimeError::native));
+    }
+
+    fn name(&self) -> String {
+        return self.name.clone();
+    }
+
+    fn trace(&self, tracer: &mut dyn Tracer) -> bool {
+        match self.receiver.try_borrow() {
+            Ok(a) => tracer.value(&a),
+            Err(_) => return false
+        }
+
+        return true;
+    }
+
+    fn clear(&self) {
+        *self.receiver.borrow_mut() = Literal::Nill;
+    }
+}
+
+// Display a BoundMethod
+impl fmt::Debug for BoundMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native method {}>", self.name)
+    }
+}
+
+// A function declared in Lox, along with the local scopes it was declared in.
+pub struct LoxFunction {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Rc<Vec<Stmt>>,
+    pub closure: RefCell<Option<SharedEnvironment>>
+}
+
+impl LoxCallable for LoxFunction {
+    fn arity(&self) -> usize {
+        return self.params.len();
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, RuntimeError> {
+        return Ok(interpreter.call_function(self, arguments));
+    }
+
+    fn name(&self) -> String {
+        return self.name.lexeme.to_string();
+    }
+
+    fn trace(&self, tracer: &mut dyn Tracer) -> bool {
+        match self.closure.try_borrow() {
+            Ok(a) => {
+                if let Some(b) = &*a {
+                    tracer.environment(b);
+                }
+            }
+            Err(_) => return false
+        }
+
+        return true;
+    }
+
+    fn clear(&self) {
+        *self.closure.borrow_mut() = None;
+    }
+}
+
+// Display a LoxFunction
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.name.lexeme)
+    }
+}
+
+// A variable a Closure uses from a function around it. It is open while the variable is still on
+// the virtual machine's stack, pointing at its slot there, and closed once the variable has left
+// the stack, holding the variable itself from then on.
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Literal)
+}
+
+// An upvalue shared between every closure using that variable
+pub type SharedUpvalue = Rc<RefCell<Upvalue>>;
+
+// A function compiled for the virtual machine, along with the variables it uses from around it.
+pub struct Closure {
+    pub function: Rc<Prototype>,
+    pub upvalues: Rc<[SharedUpvalue]>
+}
+
+impl LoxCallable for Closure {
+    fn arity(&self) -> usize {
+        return self.function.arity;
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, RuntimeError> {
+        return interpreter.call_closure(self, arguments);
+    }
+
+    fn name(&self) -> String {
+        return self.function.name.to_string();
+    }
+
+    fn trace(&self, tracer: &mut dyn Tracer) -> bool {
+        for upvalue in self.upvalues.iter() {
+            tracer.upvalue(upvalue);
+        }
+
+        return true;
+    }
+
+    fn closure(&self) -> Option<&Closure> {
+        return Some(self);
+    }
+}
+
+// Display a Closure the same as a LoxFunction
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.function.name)
+    }
+}
@@ -0,0 +1,290 @@
+use std::fmt;
+use std::rc::Rc;
+use crate::interpreter::Interpreter;
+use crate::intern::Symbol;
+use crate::token::Literal;
+
+/*
+Bytecode
+
+The compiler turns each function, and the top level of a script, into a Prototype holding a Chunk
+of bytecode for the virtual machine. A chunk is the code itself, one byte per opcode followed by
+its operands, the constants the code refers to by index, and a table giving the line each
+instruction came from.
+
+Operands are a byte (slots, argument counts) or two bytes, high byte first (constants, names and
+jump offsets), as given next to each opcode below.
+*/
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum OpCode {
+    // Push constant u16
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    // Push or set local variable u8, counted from the start of the call's frame
+    GetLocal,
+    SetLocal,
+    // Push, define or set the global named by constant u16
+    GetGlobal,
+    DefineGlobal,
+    SetGlobal,
+    // Push or set the variable closed over by upvalue u8 of the running function
+    GetUpvalue,
+    SetUpvalue,
+    // Replace the value on top with its property named by constant u16
+    GetProperty,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    // Jump forward u16 bytes
+    Jump,
+    // Jump forward u16 bytes if the value on top is false, leaving it there
+    JumpIfFalse,
+    // Jump back u16 bytes, the loop's keyword is constant u16 for errors raised when out of budget
+    Loop,
+    // Call the value below u8 arguments
+    Call,
+    // Push a new function closing over the variables prototype constant u16 asks for
+    Closure,
+    // Move the local on top off of the stack into the upvalues pointing at it, then pop it
+    CloseUpvalue,
+    Return,
+    // Run the code u16 bytes ahead if an error is raised before the matching PopCatch
+    PushCatch,
+    // Run the code u16 bytes ahead if an error is raised or a return made before the matching
+    // PopFinally, which carries on into that same code
+    PushFinally,
+    PopCatch,
+    PopFinally,
+    // The end of a finally block, carry on with whatever it interrupted
+    EndFinally,
+    Throw
+}
+
+impl OpCode {
+    const ALL: [OpCode; 39] = [
+        OpCode::Constant, OpCode::Nil, OpCode::True, OpCode::False, OpCode::Pop,
+        OpCode::GetLocal, OpCode::SetLocal, OpCode::GetGlobal, OpCode::DefineGlobal, OpCode::SetGlobal,
+        OpCode::GetUpvalue, OpCode::SetUpvalue, OpCode::GetProperty,
+        OpCode::Equal, OpCode::NotEqual, OpCode::Greater, OpCode::GreaterEqual, OpCode::Less, OpCode::LessEqual,
+        OpCode::Add, OpCode::Subtract, OpCode::Multiply, OpCode::Divide, OpCode::Not, OpCode::Negate,
+        OpCode::Print, OpCode::Jump, OpCode::JumpIfFalse, OpCode::Loop, OpCode::Call,
+        OpCode::Closure, OpCode::CloseUpvalue, OpCode::Return,
+        OpCode::PushCatch, OpCode::PushFinally, OpCode::PopCatch, OpCode::PopFinally, OpCode::EndFinally,
+        OpCode::Throw
+    ];
+
+    // The opcode a byte stands for, None if it isn't one
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        return Self::ALL.get(byte as usize).copied();
+    }
+
+    // How many bytes of operands follow the opcode
+    pub fn operand_size(self) -> usize {
+        match self {
+            OpCode::GetLocal | OpCode::SetLocal | OpCode::GetUpvalue | OpCode::SetUpvalue | OpCode::Call => return 1,
+            OpCode::Constant | OpCode::GetGlobal | OpCode::DefineGlobal | OpCode::SetGlobal | OpCode::GetProperty
+                | OpCode::Jump | OpCode::JumpIfFalse | OpCode::Closure | OpCode::PushCatch | OpCode::PushFinally => return 2,
+            OpCode::Loop => return 4,
+            _ => return 0
+        }
+    }
+
+    // How many values the instruction needs on the stack, and how many it leaves in their place,
+    // "count" being the number of arguments when it is a call. Setters and JumpIfFalse only look
+    // at the value on top, so they need one and leave it.
+    pub fn stack_effect(self, count: usize) -> (usize, usize) {
+        match self {
+            OpCode::Constant | OpCode::Nil | OpCode::True | OpCode::False | OpCode::GetLocal | OpCode::GetGlobal
+                | OpCode::GetUpvalue | OpCode::Closure => return (0, 1),
+            OpCode::Pop | OpCode::DefineGlobal | OpCode::Print | OpCode::CloseUpvalue | OpCode::Return | OpCode::Throw => return (1, 0),
+            OpCode::SetLocal | OpCode::SetGlobal | OpCode::SetUpvalue | OpCode::GetProperty | OpCode::Not | OpCode::Negate
+                | OpCode::JumpIfFalse => return (1, 1),
+            OpCode::Equal | OpCode::NotEqual | OpCode::Greater | OpCode::GreaterEqual | OpCode::Less | OpCode::LessEqual
+                | OpCode::Add | OpCode::Subtract | OpCode::Multiply | OpCode::Divide => return (2, 1),
+            OpCode::Call => return (count + 1, 1),
+            OpCode::Jump | OpCode::Loop | OpCode::PushCatch | OpCode::PushFinally | OpCode::PopCatch | OpCode::PopFinally
+                | OpCode::EndFinally => return (0, 0)
+        }
+    }
+}
+
+// A value known when compiling, referred to from the code by its index
+#[derive(Clone, Debug)]
+pub enum Constant {
+    Num(f64),
+    Str(Rc<str>),
+    // An identifier, such as the name of a global or a property
+    Name(Symbol),
+    Function(Rc<Prototype>)
+}
+
+// Display a constant the way the disassembler lists it
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constant::Num(a) => write!(f, "{}", Interpreter::stringify(Literal::Num(*a))),
+            Constant::Str(a) => write!(f, "{:?}", a),
+            Constant::Name(a) => write!(f, "{}", a),
+            Constant::Function(a) => write!(f, "<fn {}>", a.name)
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Constant>,
+    // The line of each run of code from the same line, as the offset the run starts at and the line
+    pub lines: Vec<(usize, u32)>
+}
+
+impl Chunk {
+    pub fn write(&mut self, byte: u8, line: u32) {
+        match self.lines.last() {
+            Some((_, a)) if *a == line => {}
+            _ => self.lines.push((self.code.len(), line))
+        }
+        self.code.push(byte);
+    }
+
+    // Add a constant, giving back its index
+    pub fn add_constant(&mut self, constant: Constant) -> usize {
+        self.constants.push(constant);
+        return self.constants.len() - 1;
+    }
+
+    // The two byte operand at "offset"
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        return u16::from_be_bytes([self.code[offset], self.code[offset + 1]]);
+    }
+
+    // The line the code at "offset" came from
+    pub fn line(&self, offset: usize) -> u32 {
+        let run: usize = self.lines.partition_point(|a| a.0 <= offset);
+        return match run {
+            0 => 0,
+            _ => self.lines[run - 1].1
+        };
+    }
+
+    // A listing of every instruction, headed by the name of the function the chunk belongs to
+    pub fn disassemble(&self, name: &str) -> String {
+        let mut listing: String = format!("== {} ==\n", name);
+        let mut offset: usize = 0;
+        while offset < self.code.len() {
+            let (instruction, next) = self.disassemble_instruction(offset);
+            listing.push_str(&instruction);
+            listing.push('\n');
+            offset = next;
+        }
+
+        return listing;
+    }
+
+    // The instruction at "offset" as a line of the listing: its offset, source line ("|" when it is
+    // the same as the instruction before), opcode and operands. Also gives back where the next one starts.
+    pub fn disassemble_instruction(&self, offset: usize) -> (String, usize) {
+        let line: String = match offset > 0 && self.line(offset) == self.line(offset - 1) {
+            true => "   |".to_string(),
+            false => format!("{:4}", self.line(offset))
+        };
+        let prefix: String = format!("{:04} {} ", offset, line);
+
+        let op: OpCode = match OpCode::from_byte(self.code[offset]) {
+            Some(a) => a,
+            None => return (format!("{}Unknown opcode {}", prefix, self.code[offset]), offset + 1)
+        };
+        let next: usize = offset + 1 + op.operand_size();
+        if next > self.code.len() {
+            return (format!("{}{:?} (missing operands)", prefix, op), self.code.len());
+        }
+
+        let name: String = format!("{:?}", op);
+        let operands: String = match op {
+            OpCode::GetLocal | OpCode::SetLocal | OpCode::GetUpvalue | OpCode::SetUpvalue | OpCode::Call => {
+                format!("{:<16} {:4}", name, self.code[offset + 1])
+            }
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::PushCatch | OpCode::PushFinally => {
+                let distance: u16 = self.read_u16(offset + 1);
+                format!("{:<16} {:4} -> {:04}", name, distance, next + distance as usize)
+            }
+            OpCode::Loop => {
+                let distance: u16 = self.read_u16(offset + 1);
+                let keyword: String = self.constant(self.read_u16(offset + 3));
+                format!("{:<16} {:4} -> {:04} {}", name, distance, next.saturating_sub(distance as usize), keyword)
+            }
+            OpCode::Closure => {
+                let index: u16 = self.read_u16(offset + 1);
+                let mut captures: Vec<String> = Vec::new();
+                if let Some(Constant::Function(a)) = self.constants.get(index as usize) {
+                    for upvalue in &a.upvalues {
+                        captures.push(format!("{} {}", if upvalue.local { "local" } else { "upvalue" }, upvalue.index));
+                    }
+                }
+                format!("{:<16} {:4} {} [{}]", name, index, self.constant(index), captures.join(", "))
+            }
+            _ if op.operand_size() == 2 => {
+                let index: u16 = self.read_u16(offset + 1);
+                format!("{:<16} {:4} {}", name, index, self.constant(index))
+            }
+            _ => name
+        };
+
+        return (format!("{}{}", prefix, operands), next);
+    }
+
+    // A constant as the listing shows it
+    fn constant(&self, index: u16) -> String {
+        match self.constants.get(index as usize) {
+            Some(a) => return a.to_string(),
+            None => return "<no such constant>".to_string()
+        }
+    }
+}
+
+// Where a function finds a variable it closes over when it is made: in a local slot of the
+// function making it, or in one of that function's own upvalues
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UpvalueRef {
+    pub local: bool,
+    pub index: u8
+}
+
+// A compiled function, or the top level of a script
+#[derive(Debug)]
+pub struct Prototype {
+    pub name: Symbol,
+    pub arity: usize,
+    pub upvalues: Vec<UpvalueRef>,
+    pub chunk: Chunk
+}
+
+impl Prototype {
+    // A listing of the function's chunk, then of every function declared in it
+    pub fn disassemble(&self) -> String {
+        let mut listing: String = self.chunk.disassemble(&self.name.text());
+        for constant in &self.chunk.constants {
+            if let Constant::Function(a) = constant {
+                listing.push('\n');
+                listing.push_str(&a.disassemble());
+            }
+        }
+
+        return listing;
+    }
+}
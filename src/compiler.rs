@@ -0,0 +1,537 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::Lox;
+use crate::chunk::{Chunk, Constant, OpCode, Prototype, UpvalueRef};
+use crate::expression::{Expr, ExprVisitor, Slot};
+use crate::intern::Symbol;
+use crate::stmt::{Stmt, StmtVisitor};
+use crate::token::{Literal, Token, TokenType};
+
+/*
+Compiler
+
+Turns the statements of a script into bytecode for the virtual machine, one Prototype per function
+plus one for the top level. Like the tree-walking interpreter, anything declared at the top level
+is a global, looked up by name when it runs, and everything else is a local.
+
+Locals live on the virtual machine's stack, in the call's frame. The compiler keeps track of which
+slot each one is in as it goes, so it works the same out as the resolver does for the tree-walker.
+A function using a local of a function around it gets an upvalue for it, which points at the
+variable's slot while it is still on the stack, and holds the variable itself once it has gone.
+*/
+
+// A local variable of the function being compiled, kept in the slot matching its position
+struct Local {
+    name: Symbol,
+    depth: usize,
+    // Whether a function declared inside the scope uses the variable, so it needs moving off of the stack when the scope ends
+    captured: bool
+}
+
+// The function being compiled, the compiler keeps one for each function it is inside of
+struct FunctionState {
+    name: Symbol,
+    arity: usize,
+    chunk: Chunk,
+    upvalues: Vec<UpvalueRef>,
+    locals: Vec<Local>,
+    scope_depth: usize,
+    // Constants already made for names, so each is only added once
+    names: HashMap<Symbol, u16>
+}
+
+impl FunctionState {
+    fn new(name: Symbol, arity: usize) -> FunctionState {
+        // The first slot holds the function being called
+        let callee: Local = Local {name: Symbol::detached(""), depth: 0, captured: false};
+        return FunctionState {
+            name: name,
+            arity: arity,
+            chunk: Chunk::default(),
+            upvalues: Vec::new(),
+            locals: vec![callee],
+            scope_depth: 0,
+            names: HashMap::new()
+        }
+    }
+}
+
+pub struct Compiler {
+    functions: Vec<FunctionState>,
+    // The token being compiled, errors are reported at it and code is given its line
+    token: Token,
+    pub instance: Lox
+}
+
+impl Compiler {
+    pub fn compiler_builder(instance: Lox) -> Compiler {
+        return Compiler {
+            functions: Vec::new(),
+            token: Token {token_type: TokenType::Eof, lexeme: Symbol::detached(""), literal: None, line: 1, column: 0},
+            instance: instance
+        }
+    }
+
+    // Compile a script, giving back its top level as a function taking no arguments. Running it
+    // gives back the value of the last statement if that is an expression statement, like
+    // Interpreter::run does.
+    pub fn compile(&mut self, statements: &[Stmt]) -> Option<Rc<Prototype>> {
+        self.functions.push(FunctionState::new(Symbol::detached("script"), 0));
+
+        for (i, statement) in statements.iter().enumerate() {
+            match statement {
+                Stmt::Expression {expression} if i == statements.len() - 1 => {
+                    self.visit(expression);
+                    self.emit_op(OpCode::Return);
+                }
+                _ => self.execute(statement)
+            }
+        }
+        self.emit_op(OpCode::Nil);
+        self.emit_op(OpCode::Return);
+
+        let script: Prototype = self.end_function();
+        if self.instance.had_error {
+            return None;
+        }
+        return Some(Rc::new(script));
+    }
+
+    fn current(&mut self) -> &mut FunctionState {
+        return self.functions.last_mut().expect("No function being compiled");
+    }
+
+    fn error(&mut self, message: &str) {
+        let token: Token = self.token.clone();
+        self.instance.parser_error(token, message);
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        let line: u32 = self.token.line;
+        self.current().chunk.write(byte, line);
+    }
+
+    fn emit_op(&mut self, op: OpCode) {
+        self.emit_byte(op as u8);
+    }
+
+    fn emit_u16(&mut self, value: u16) {
+        for byte in value.to_be_bytes() {
+            self.emit_byte(byte);
+        }
+    }
+
+    // Add a constant to the chunk being compiled, giving back its index
+    fn make_constant(&mut self, constant: Constant) -> u16 {
+        let index: usize = self.current().chunk.add_constant(constant);
+        if index > u16::MAX as usize {
+            self.error("Too many constants in one chunk.");
+            return 0;
+        }
+        return index as u16;
+    }
+
+    // The constant holding a name, made the first time the name is used
+    fn name_constant(&mut self, name: Symbol) -> u16 {
+        if let Some(a) = self.current().names.get(&name) {
+            return *a;
+        }
+
+        let index: u16 = self.make_constant(Constant::Name(name.clone()));
+        self.current().names.insert(name, index);
+        return index;
+    }
+
+    fn emit_constant(&mut self, op: OpCode, constant: Constant) {
+        let index: u16 = self.make_constant(constant);
+        self.emit_op(op);
+        self.emit_u16(index);
+    }
+
+    fn emit_name(&mut self, op: OpCode, name: Symbol) {
+        let index: u16 = self.name_constant(name);
+        self.emit_op(op);
+        self.emit_u16(index);
+    }
+
+    // Emit a jump whose offset is filled in by patch_jump, giving back where the offset goes
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit_op(op);
+        self.emit_u16(u16::MAX);
+        return self.current().chunk.code.len() - 2;
+    }
+
+    // Point a jump at the next instruction to be emitted
+    fn patch_jump(&mut self, offset: usize) {
+        let distance: usize = self.current().chunk.code.len() - offset - 2;
+        if distance > u16::MAX as usize {
+            self.error("Too much code to jump over.");
+            return;
+        }
+
+        let code: &mut Vec<u8> = &mut self.current().chunk.code;
+        code[offset..offset + 2].copy_from_slice(&(distance as u16).to_be_bytes());
+    }
+
+    // Jump back to "start", checking the budget on the way as the tree-walker does at each loop
+    fn emit_loop(&mut self, start: usize, keyword: Symbol) {
+        let keyword: u16 = self.name_constant(keyword);
+        self.emit_op(OpCode::Loop);
+        let distance: usize = self.current().chunk.code.len() - start + 4;
+        if distance > u16::MAX as usize {
+            self.error("Loop body too large.");
+        }
+        self.emit_u16(distance as u16);
+        self.emit_u16(keyword);
+    }
+
+    fn begin_scope(&mut self) {
+        self.current().scope_depth += 1;
+    }
+
+    // Leave a scope, its locals come off of the stack
+    fn end_scope(&mut self) {
+        self.current().scope_depth -= 1;
+        loop {
+            let state: &mut FunctionState = self.current();
+            let captured: bool = match state.locals.last() {
+                Some(a) if a.depth > state.scope_depth => a.captured,
+                _ => break
+            };
+            state.locals.pop();
+            self.emit_op(if captured { OpCode::CloseUpvalue } else { OpCode::Pop });
+        }
+    }
+
+    // Whether declarations are globals, which they are at the top level of the script
+    fn is_global(&mut self) -> bool {
+        return self.functions.len() == 1 && self.current().scope_depth == 0;
+    }
+
+    // Declare a local in the next slot, the value on top of the stack once it is in scope
+    fn add_local(&mut self, name: Symbol) {
+        if self.current().locals.len() > u8::MAX as usize {
+            self.error("Too many local variables in function.");
+            return;
+        }
+
+        let depth: usize = self.current().scope_depth;
+        self.current().locals.push(Local {name: name, depth: depth, captured: false});
+    }
+
+    // The slot of the innermost local with this name in "function"
+    fn resolve_local(&self, function: usize, name: Symbol) -> Option<u8> {
+        let locals: &Vec<Local> = &self.functions[function].locals;
+        return locals.iter().rposition(|a| a.name == name).map(|a| a as u8);
+    }
+
+    // The upvalue "function" reaches a local of a function around it through, adding it and one
+    // to each function in between if they don't have one already
+    fn resolve_upvalue(&mut self, function: usize, name: Symbol) -> Option<u8> {
+        if function == 0 {
+            return None;
+        }
+
+        if let Some(a) = self.resolve_local(function - 1, name.clone()) {
+            self.functions[function - 1].locals[a as usize].captured = true;
+            return Some(self.add_upvalue(function, UpvalueRef {local: true, index: a}));
+        }
+        if let Some(a) = self.resolve_upvalue(function - 1, name) {
+            return Some(self.add_upvalue(function, UpvalueRef {local: false, index: a}));
+        }
+
+        return None;
+    }
+
+    fn add_upvalue(&mut self, function: usize, upvalue: UpvalueRef) -> u8 {
+        let upvalues: &Vec<UpvalueRef> = &self.functions[function].upvalues;
+        if let Some(a) = upvalues.iter().position(|a| *a == upvalue) {
+            return a as u8;
+        }
+        if upvalues.len() > u8::MAX as usize {
+            self.error("Too many closure variables in function.");
+            return 0;
+        }
+
+        self.functions[function].upvalues.push(upvalue);
+        return (self.functions[function].upvalues.len() - 1) as u8;
+    }
+
+    // Emit the get or set of a variable, wherever it turned out to be
+    fn emit_variable(&mut self, name: Symbol, get: bool) {
+        let function: usize = self.functions.len() - 1;
+        if let Some(a) = self.resolve_local(function, name.clone()) {
+            self.emit_op(if get { OpCode::GetLocal } else { OpCode::SetLocal });
+            self.emit_byte(a);
+        } else if let Some(a) = self.resolve_upvalue(function, name.clone()) {
+            self.emit_op(if get { OpCode::GetUpvalue } else { OpCode::SetUpvalue });
+            self.emit_byte(a);
+        } else {
+            self.emit_name(if get { OpCode::GetGlobal } else { OpCode::SetGlobal }, name);
+        }
+    }
+
+    // Finish the function being compiled
+    fn end_function(&mut self) -> Prototype {
+        let state: FunctionState = self.functions.pop().expect("No function being compiled");
+        return Prototype {
+            name: state.name,
+            arity: state.arity,
+            upvalues: state.upvalues,
+            chunk: state.chunk
+        }
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.execute(statement);
+        }
+    }
+
+    // Compile statements in a scope of their own
+    fn block(&mut self, statements: &[Stmt]) {
+        self.begin_scope();
+        self.statements(statements);
+        self.end_scope();
+    }
+}
+
+impl StmtVisitor<> for Compiler {
+    fn visit_expression(&mut self, expression: &Expr) {
+        self.visit(expression);
+        self.emit_op(OpCode::Pop);
+    }
+
+    fn visit_print(&mut self, expression: &Expr) {
+        self.visit(expression);
+        self.emit_op(OpCode::Print);
+    }
+
+    fn visit_var(&mut self, name: &Token, right: Option<&Expr>) {
+        // The initializer runs before the variable exists, so it sees whatever the name meant before
+        match right {
+            Some(a) => {
+                self.visit(a);
+            }
+            None => self.emit_op(OpCode::Nil)
+        }
+
+        self.token = name.clone();
+        if self.is_global() {
+            self.emit_name(OpCode::DefineGlobal, name.lexeme.clone());
+        } else {
+            self.add_local(name.lexeme.clone());
+        }
+    }
+
+    fn visit_block(&mut self, statements: &[Stmt]) {
+        self.block(statements);
+    }
+
+    fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>) {
+        self.visit(condition);
+        let then_jump: usize = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+        self.execute(then_branch);
+
+        let else_jump: usize = self.emit_jump(OpCode::Jump);
+        self.patch_jump(then_jump);
+        self.emit_op(OpCode::Pop);
+        if let Some(a) = else_branch {
+            self.execute(a);
+        }
+        self.patch_jump(else_jump);
+    }
+
+    fn visit_while(&mut self, keyword: &Token, condition: &Expr, body: &Stmt) {
+        let start: usize = self.current().chunk.code.len();
+        self.visit(condition);
+        let exit: usize = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+        self.execute(body);
+
+        self.token = keyword.clone();
+        self.emit_loop(start, keyword.lexeme.clone());
+        self.patch_jump(exit);
+        self.emit_op(OpCode::Pop);
+    }
+
+    // A try with both a catch and a finally runs the same as a try and catch inside of a try and finally
+    fn visit_try(&mut self, try_branch: &[Stmt], name: Option<&Token>, catch_branch: Option<&[Stmt]>, finally_branch: Option<&[Stmt]>) {
+        let finally: Option<usize> = finally_branch.map(|_| self.emit_jump(OpCode::PushFinally));
+        let catch: Option<usize> = catch_branch.map(|_| self.emit_jump(OpCode::PushCatch));
+        self.block(try_branch);
+
+        if let (Some(a), Some(statements)) = (catch, catch_branch) {
+            self.emit_op(OpCode::PopCatch);
+            let end: usize = self.emit_jump(OpCode::Jump);
+
+            // The error is pushed onto the stack, where it becomes the catch variable
+            self.patch_jump(a);
+            self.begin_scope();
+            self.add_local(name.map(|b| b.lexeme.clone()).unwrap_or_else(|| Symbol::detached("")));
+            self.statements(statements);
+            self.end_scope();
+            self.patch_jump(end);
+        }
+
+        if let (Some(a), Some(statements)) = (finally, finally_branch) {
+            self.emit_op(OpCode::PopFinally);
+            self.patch_jump(a);
+            self.block(statements);
+            self.emit_op(OpCode::EndFinally);
+        }
+    }
+
+    fn visit_throw(&mut self, keyword: &Token, value: &Expr) {
+        self.visit(value);
+        self.token = keyword.clone();
+        self.emit_op(OpCode::Throw);
+    }
+
+    fn visit_function(&mut self, name: &Token, params: &[Token], body: &Rc<Vec<Stmt>>) {
+        // Declared before the body so the function can call itself
+        self.token = name.clone();
+        let global: bool = self.is_global();
+        if !global {
+            self.add_local(name.lexeme.clone());
+        }
+
+        self.functions.push(FunctionState::new(name.lexeme.clone(), params.len()));
+        self.begin_scope();
+        for param in params {
+            self.add_local(param.lexeme.clone());
+        }
+        self.statements(body);
+        self.emit_op(OpCode::Nil);
+        self.emit_op(OpCode::Return);
+        let function: Prototype = self.end_function();
+
+        self.token = name.clone();
+        self.emit_constant(OpCode::Closure, Constant::Function(Rc::new(function)));
+        if global {
+            self.emit_name(OpCode::DefineGlobal, name.lexeme.clone());
+        }
+    }
+
+    fn visit_return(&mut self, keyword: &Token, value: Option<&Expr>) {
+        match value {
+            Some(a) => {
+                self.visit(a);
+            }
+            None => self.emit_op(OpCode::Nil)
+        }
+        self.token = keyword.clone();
+        self.emit_op(OpCode::Return);
+    }
+}
+
+impl ExprVisitor<()> for Compiler {
+    fn visit_binary(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Option<()> {
+        self.visit(left);
+        self.visit(right);
+
+        self.token = operator.clone();
+        match operator.token_type {
+            TokenType::Plus => self.emit_op(OpCode::Add),
+            TokenType::Minus => self.emit_op(OpCode::Subtract),
+            TokenType::Star => self.emit_op(OpCode::Multiply),
+            TokenType::Slash => self.emit_op(OpCode::Divide),
+            TokenType::Greater => self.emit_op(OpCode::Greater),
+            TokenType::GreaterEqual => self.emit_op(OpCode::GreaterEqual),
+            TokenType::Less => self.emit_op(OpCode::Less),
+            TokenType::LessEqual => self.emit_op(OpCode::LessEqual),
+            TokenType::EqualEqual => self.emit_op(OpCode::Equal),
+            TokenType::BangEqual => self.emit_op(OpCode::NotEqual),
+            _ => {
+                self.emit_op(OpCode::Pop);
+                self.emit_op(OpCode::Pop);
+                self.emit_op(OpCode::Nil);
+            }
+        }
+        return Some(());
+    }
+
+    fn visit_grouping(&mut self, expression: &Expr) -> Option<()> {
+        return self.visit(expression);
+    }
+
+    fn visit_literal(&mut self, value: &Literal) -> Option<()> {
+        match value {
+            Literal::Num(a) => self.emit_constant(OpCode::Constant, Constant::Num(*a)),
+            Literal::Str(a) => self.emit_constant(OpCode::Constant, Constant::Str(a.clone())),
+            Literal::True => self.emit_op(OpCode::True),
+            Literal::False => self.emit_op(OpCode::False),
+            _ => self.emit_op(OpCode::Nil)
+        }
+        return Some(());
+    }
+
+    fn visit_unary(&mut self, operator: &Token, right: &Expr) -> Option<()> {
+        self.visit(right);
+
+        self.token = operator.clone();
+        match operator.token_type {
+            TokenType::Minus => self.emit_op(OpCode::Negate),
+            TokenType::Bang => self.emit_op(OpCode::Not),
+            _ => {
+                self.emit_op(OpCode::Pop);
+                self.emit_op(OpCode::Nil);
+            }
+        }
+        return Some(());
+    }
+
+    fn visit_variable(&mut self, token: &Token, _slot: Option<Slot>) -> Option<()> {
+        self.token = token.clone();
+        self.emit_variable(token.lexeme.clone(), true);
+        return Some(());
+    }
+
+    fn visit_assignment(&mut self, name: &Token, value: &Expr, _slot: Option<Slot>) -> Option<()> {
+        self.visit(value);
+        self.token = name.clone();
+        self.emit_variable(name.lexeme.clone(), false);
+        return Some(());
+    }
+
+    fn visit_logical(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Option<()> {
+        self.visit(left);
+
+        self.token = operator.clone();
+        if matches!(operator.token_type, TokenType::Or) {
+            let else_jump: usize = self.emit_jump(OpCode::JumpIfFalse);
+            let end: usize = self.emit_jump(OpCode::Jump);
+            self.patch_jump(else_jump);
+            self.emit_op(OpCode::Pop);
+            self.visit(right);
+            self.patch_jump(end);
+        } else {
+            let end: usize = self.emit_jump(OpCode::JumpIfFalse);
+            self.emit_op(OpCode::Pop);
+            self.visit(right);
+            self.patch_jump(end);
+        }
+        return Some(());
+    }
+
+    fn visit_call(&mut self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> Option<()> {
+        self.visit(callee);
+        for argument in arguments {
+            self.visit(argument);
+        }
+
+        // The parser allows no more than 255 arguments
+        self.token = paren.clone();
+        self.emit_op(OpCode::Call);
+        self.emit_byte(arguments.len() as u8);
+        return Some(());
+    }
+
+    fn visit_get(&mut self, object: &Expr, name: &Token) -> Option<()> {
+        self.visit(object);
+        self.token = name.clone();
+        self.emit_name(OpCode::GetProperty, name.lexeme.clone());
+        return Some(());
+    }
+}
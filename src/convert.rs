@@ -0,0 +1,145 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::Value;
+use crate::error::RuntimeError;
+use crate::interpreter::Interpreter;
+use crate::token::Literal;
+
+// Conversions between Lox values and Rust types, for hosts passing values in and out of scripts.
+// Lists and maps are copied across, so changes made on one side aren't seen by the other.
+
+// A Rust value that can be handed to Lox
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+// A Rust value that can be taken out of a Lox value, failing if the Lox value is the wrong type
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> Result<Self, RuntimeError>;
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        return self;
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        return Ok(value);
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        return Literal::Num(self);
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Literal::Num(a) => return Ok(a),
+            a => return Err(mismatch("number", a))
+        }
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        return Interpreter::from_bool(self);
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Literal::True => return Ok(true),
+            Literal::False => return Ok(false),
+            a => return Err(mismatch("boolean", a))
+        }
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        return Literal::Str(self.into());
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        return Literal::Str(self.into());
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Literal::Str(a) => return Ok(a.to_string()),
+            a => return Err(mismatch("string", a))
+        }
+    }
+}
+
+// Nothing comes across as nil
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        return Literal::Nill;
+    }
+}
+
+// There is no interpreter to track lists and maps with here, that happens as they are handed to one
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        return Literal::List(Rc::new(RefCell::new(self.into_iter().map(T::into_value).collect())));
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Literal::List(a) => return a.borrow().iter().map(|b| T::from_value(b.clone())).collect(),
+            a => return Err(mismatch("list", a))
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for HashMap<String, T> {
+    fn into_value(self) -> Value {
+        return Literal::Map(Rc::new(RefCell::new(self.into_iter().map(|(a, b)| (a, b.into_value())).collect())));
+    }
+}
+
+impl<T: FromValue> FromValue for HashMap<String, T> {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Literal::Map(a) => return a.borrow().iter().map(|(b, c)| Ok((b.clone(), T::from_value(c.clone())?))).collect(),
+            a => return Err(mismatch("map", a))
+        }
+    }
+}
+
+// None is nil, and nil is None
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        match self {
+            Some(a) => return a.into_value(),
+            None => return Literal::Nill
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Literal::Nill => return Ok(None),
+            a => return Ok(Some(T::from_value(a)?))
+        }
+    }
+}
+
+fn mismatch(expected: &str, value: Value) -> RuntimeError {
+    return RuntimeError::native(format!("Expected a {} but got {}.", expected, value));
+}
@@ -0,0 +1,73 @@
+use std::fmt;
+
+// Which stage of running some source an error came from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiagnosticKind {
+    // A file couldn't be read, written, or loaded as compiled bytecode
+    Io,
+    Scan,
+    Parse,
+    Runtime,
+    // The script ran out of budget or was cancelled, see Interpreter::set_max_steps
+    Aborted,
+    // The script called exit() with this status, which isn't an error to report
+    Exit(i32)
+}
+
+// An error found while scanning, parsing or running some source, in a form the host can inspect
+// rather than as text on stderr.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub line: u32,
+    // Where on the line the error is, eg. " at 'foo'" or " at end", empty when it isn't known
+    pub location: String,
+    pub message: String,
+    // Python style traceback for uncaught run time errors, most recent call last
+    pub traceback: Vec<String>
+}
+
+impl Diagnostic {
+    // A file couldn't be read, written or loaded
+    pub fn io(message: String) -> Diagnostic {
+        return Diagnostic {
+            kind: DiagnosticKind::Io,
+            line: 0,
+            location: String::new(),
+            message: message,
+            traceback: Vec::new()
+        }
+    }
+
+    // The status the script asked to exit with, if that is what stopped it
+    pub fn exit_status(&self) -> Option<i32> {
+        match self.kind {
+            DiagnosticKind::Exit(a) => return Some(a),
+            _ => return None
+        }
+    }
+
+    // Syntax errors mean nothing ran at all
+    pub fn is_syntax_error(&self) -> bool {
+        return matches!(self.kind, DiagnosticKind::Scan | DiagnosticKind::Parse);
+    }
+}
+
+// Display a Diagnostic the way the command line reports it
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if matches!(self.kind, DiagnosticKind::Io | DiagnosticKind::Exit(_)) {
+            return write!(f, "{}", self.message);
+        }
+
+        write!(f, "[line {}] Error{}: {}", self.line, self.location, self.message)?;
+        if !self.traceback.is_empty() {
+            write!(f, "\nTraceback (most recent call last):")?;
+            for line in &self.traceback {
+                write!(f, "\n  {}", line)?;
+            }
+        }
+
+        return Ok(());
+    }
+}
@@ -1,50 +1,110 @@
-use crate::token::{Literal, Token};
-use std::{collections::HashMap};
+use crate::expression::Slot;
+use crate::token::Literal;
+use crate::intern::Symbol;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
 
-#[derive(Clone, Default, Debug)]
+// A scope shared between the code running in it and every function declared in it
+pub type SharedEnvironment = Rc<RefCell<Environment>>;
+
+// A local scope. Its variables are kept in the order they were declared in, which is the order
+// the resolver numbered them in, so they are found by index rather than by name.
+#[derive(Default, Debug)]
 pub struct Environment {
-    instance: crate::Lox,
-    values: HashMap<String, Literal>,
-    pub enclosing: Option<Box<Environment>>
+    slots: Vec<Literal>,
+    pub enclosing: Option<SharedEnvironment>,
+    // Whether the garbage collector knows about this scope, which it only needs to once a function closes over it
+    pub tracked: bool
 }
 
 impl Environment {
-    pub fn build_environment(instance: crate::Lox, enclosing: Box<Self>) -> Self {
+    pub fn build_environment(enclosing: Option<SharedEnvironment>) -> Self {
         Self {
-            instance: instance,
-            values: HashMap::new(),
-            enclosing: Some(enclosing)
+            slots: Vec::new(),
+            enclosing: enclosing,
+            tracked: false
+        }
+    }
+
+    // Wrap a scope up to be shared
+    pub fn share(self) -> SharedEnvironment {
+        return Rc::new(RefCell::new(self));
+    }
+
+    // The value in a slot, None if that variable hasn't been declared yet
+    pub fn get(&self, slot: Slot) -> Option<Literal> {
+        if slot.depth == 0 {
+            return self.slots.get(slot.index).cloned();
+        }
+
+        match &self.enclosing {
+            Some(a) => return a.borrow().get(Slot {depth: slot.depth - 1, index: slot.index}),
+            None => return None
+        }
+    }
+
+    // The variables declared in this scope alone
+    pub fn values(&self) -> impl Iterator<Item = &Literal> {
+        return self.slots.iter();
+    }
+
+    // Declare the next variable in this scope
+    pub fn define(&mut self, value: Literal) {
+        self.slots.push(value);
+    }
+
+    // Set the value in a slot, false if that variable hasn't been declared yet
+    pub fn assign(&mut self, slot: Slot, value: Literal) -> bool {
+        if slot.depth == 0 {
+            match self.slots.get_mut(slot.index) {
+                Some(a) => *a = value,
+                None => return false
+            }
+            return true;
+        }
+
+        match &self.enclosing {
+            Some(a) => return a.borrow_mut().assign(Slot {depth: slot.depth - 1, index: slot.index}, value),
+            None => return false
         }
     }
 
-    // If there is an error send it here to report to the Lox instance
-    fn error(&mut self, token: Token, message: String) {
-        self.instance.interpreter_error(token, &*message);
+    // Forget every variable, once the garbage collector finds the scope unreachable
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.enclosing = None;
     }
+}
 
-    pub fn get(&self, name: Token) -> Result<Literal, String> {
-        if self.values.contains_key(&name.lexeme) {
-            return Ok(self.values.get(&name.lexeme).unwrap().clone())
-        } else if self.enclosing.is_some() {
-            return self.enclosing.as_ref().unwrap().get(name)
-        } else {
-            return Err(format!("Undefined variable {}.", name.lexeme))
+// The global variables. Code can use a global declared after it, so they are looked up by name.
+#[derive(Default, Debug)]
+pub struct Globals {
+    values: HashMap<Symbol, Literal>
+}
+
+impl Globals {
+    pub fn get(&self, name: Symbol) -> Result<Literal, String> {
+        match self.values.get(&name) {
+            Some(a) => return Ok(a.clone()),
+            None => return Err(format!("Undefined variable {}.", name))
         }
     }
 
-    pub fn define(&mut self, name: String, value: Literal) {
+    pub fn values(&self) -> impl Iterator<Item = &Literal> {
+        return self.values.values();
+    }
+
+    pub fn define(&mut self, name: Symbol, value: Literal) {
         self.values.insert(name, value);
     }
 
-    pub fn assign(&mut self, name: Token, value: Literal) {
-        if self.values.contains_key(&name.lexeme) {
-            self.values.insert(name.lexeme, value);
-            return
-        } else if self.clone().enclosing.is_some() { 
-            self.enclosing.as_mut().unwrap().assign(name, value);
-            return
+    pub fn assign(&mut self, name: Symbol, value: Literal) -> Result<(), String> {
+        match self.values.get_mut(&name) {
+            Some(a) => *a = value,
+            None => return Err(format!("Undefined variable {}.", name))
         }
-        
-        self.error(name.clone(), format!("Undefined variable {}.", name.lexeme));
+
+        return Ok(());
     }
-}
\ No newline at end of file
+}
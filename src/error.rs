@@ -0,0 +1,190 @@
+use std::fmt;
+use std::rc::Rc;
+use crate::{Diagnostic, DiagnosticKind};
+use crate::interpreter::Interpreter;
+use crate::intern::Symbol;
+use crate::token::{Literal, Token, TokenType};
+
+// The most lines a traceback prints, the middle of anything longer is left out.
+const MAX_TRACEBACK_LINES: usize = 24;
+
+// One call in progress, the name of what was called and the line it was called from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallFrame {
+    pub function: String,
+    pub line: u32
+}
+
+// An error raised while running, either by the interpreter itself or by a throw statement. It
+// unwinds through the statements being executed until a try statement catches it, at which point
+// it becomes a regular value bound to the catch variable.
+#[derive(Clone, Debug)]
+pub struct RuntimeError {
+    // Where it was raised, boxed to keep the error small as it is handed back through every call on the way out
+    pub token: Box<Token>,
+    pub message: String,
+    pub value: Literal,
+    // Raised because the script ran out of budget, was cancelled or called exit(), try statements don't catch these
+    pub aborted: bool,
+    // The status exit() was called with, for the host to end the process with if it wants to
+    pub exit: Option<i32>,
+    // The calls that were in progress when the error was raised, outermost first
+    pub trace: Vec<CallFrame>
+}
+
+impl RuntimeError {
+    // An error raised by the interpreter, such as dividing by 0
+    pub fn new(token: Token, message: String, trace: Vec<CallFrame>) -> RuntimeError {
+        RuntimeError {
+            token: Box::new(token),
+            message: message,
+            value: Literal::Nill,
+            aborted: false,
+            exit: None,
+            trace: trace
+        }
+    }
+
+    // An error raised by "throw value;", the thrown value is kept around for the catch block
+    pub fn thrown(token: Token, value: Literal, trace: Vec<CallFrame>) -> RuntimeError {
+        RuntimeError {
+            token: Box::new(token),
+            message: Interpreter::stringify(value.clone()),
+            value: value,
+            aborted: false,
+            exit: None,
+            trace: trace
+        }
+    }
+
+    // An error raised by a native function. It has no location of its own yet, the interpreter
+    // places it at the call once it reaches back there.
+    pub fn native(message: String) -> RuntimeError {
+        let token: Token = Token {token_type: TokenType::Eof, lexeme: Symbol::detached(""), literal: None, line: 0, column: 0};
+        return Self::new(token, message, Vec::new());
+    }
+
+    // An error stopping the script for good, raised when it runs out of budget or is cancelled
+    pub fn aborted(message: String) -> RuntimeError {
+        let mut error: RuntimeError = Self::native(message);
+        error.aborted = true;
+        return error;
+    }
+
+    // Raised by exit(), which stops the script and leaves it to the host to end the process
+    pub fn exit(code: i32) -> RuntimeError {
+        let mut error: RuntimeError = Self::aborted(format!("Exited with status {}.", code));
+        error.exit = Some(code);
+        return error;
+    }
+
+    // Whether the error still needs placing at a call, line 0 never appears in a script
+    pub fn is_unplaced(&self) -> bool {
+        return self.token.line == 0;
+    }
+
+    // Resolve "name" on a caught error
+    pub fn get_property(error: Rc<RuntimeError>, name: &str) -> Result<Literal, String> {
+        match name {
+            "message" => return Ok(Literal::Str(error.message.as_str().into())),
+            "line" => return Ok(Literal::Num(error.token.line as f64)),
+            "value" => return Ok(error.value.clone()),
+            _ => return Err(format!("Undefined property '{}' on error.", name))
+        }
+    }
+
+    // Report an error that nothing caught
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let location: String = match self.token.token_type {
+            _ if self.is_unplaced() => String::new(),
+            TokenType::Eof => " at end".to_string(),
+            _ => format!(" at '{}'", self.token.lexeme)
+        };
+
+        Diagnostic {
+            kind: match (self.exit, self.aborted) {
+                (Some(a), _) => DiagnosticKind::Exit(a),
+                (None, true) => DiagnosticKind::Aborted,
+                (None, false) => DiagnosticKind::Runtime
+            },
+            line: self.token.line,
+            location: location,
+            message: self.message.clone(),
+            traceback: self.traceback()
+        }
+    }
+
+    // Lines of a Python style traceback, most recent call last. Each frame is shown with the line
+    // it had reached, runs of identical frames (deep recursion) are collapsed and very long
+    // tracebacks lose their middle. Errors raised outside of any call show just the line of the script.
+    pub fn traceback(&self) -> Vec<String> {
+        if self.is_unplaced() {
+            return Vec::new();
+        }
+
+        // The script was at the first call, each function at the call after it, the last at the error
+        let mut frames: Vec<String> = Vec::new();
+        for (i, frame) in self.trace.iter().enumerate() {
+            let name: String = match i {
+                0 => "script".to_string(),
+                _ => format!("{}()", self.trace[i - 1].function)
+            };
+            frames.push(format!("[line {}] in {}", frame.line, name));
+        }
+        let name: String = match self.trace.last() {
+            Some(a) => format!("{}()", a.function),
+            None => "script".to_string()
+        };
+        frames.push(format!("[line {}] in {}", self.token.line, name));
+
+        let mut lines: Vec<String> = Vec::new();
+        let mut repeated: usize = 0;
+        for (i, frame) in frames.iter().enumerate() {
+            if i > 0 && *frame == frames[i - 1] {
+                repeated += 1;
+                continue;
+            }
+            if repeated > 0 {
+                lines.push(format!("[Previous line repeated {} more times]", repeated));
+                repeated = 0;
+            }
+            lines.push(frame.clone());
+        }
+        if repeated > 0 {
+            lines.push(format!("[Previous line repeated {} more times]", repeated));
+        }
+
+        if lines.len() > MAX_TRACEBACK_LINES {
+            let half: usize = MAX_TRACEBACK_LINES / 2;
+            let omitted: usize = lines.len() - 2 * half;
+            let mut capped: Vec<String> = lines[..half].to_vec();
+            capped.push(format!("[... {} more lines ...]", omitted));
+            capped.extend_from_slice(&lines[lines.len() - half..]);
+            return capped;
+        }
+
+        return lines;
+    }
+}
+
+// Lets natives written by a host use "?" on their own string errors
+impl From<String> for RuntimeError {
+    fn from(message: String) -> RuntimeError {
+        return Self::native(message);
+    }
+}
+
+impl From<&str> for RuntimeError {
+    fn from(message: &str) -> RuntimeError {
+        return Self::native(message.to_string());
+    }
+}
+
+// Display a RuntimeError as its message
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for RuntimeError {}
@@ -1,5 +1,14 @@
+use std::cell::Cell;
 use crate::token::{Token, Literal};
 
+// Where a local variable lives, as worked out by the resolver: how many scopes out from the
+// innermost one it was declared in, and its index among that scope's variables
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Slot {
+    pub depth: usize,
+    pub index: usize
+}
+
 #[derive(Clone, Debug)]
 pub enum Expr {
     Binary {
@@ -17,12 +26,15 @@ pub enum Expr {
         operator: Token,
         right: Box<Expr>
     },
+    // "slot" is None for globals, and until the resolver has been over the expression
     Variable {
-        token: Token
+        token: Token,
+        slot: Cell<Option<Slot>>
     },
     Assign {
         name: Token,
-        value: Box<Expr>
+        value: Box<Expr>,
+        slot: Cell<Option<Slot>>
     },
     Logical {
         left: Box<Expr>,
@@ -33,29 +45,35 @@ pub enum Expr {
         callee: Box<Expr>,
         paren: Token,
         arguments: Vec<Expr> 
+    },
+    Get {
+        object: Box<Expr>,
+        name: Token
     }
 }
 
 pub trait ExprVisitor<T> {
-    fn visit(&mut self, expr: Expr) -> Option<T> {
+    fn visit(&mut self, expr: &Expr) -> Option<T> {
         match expr {
             Expr::Binary {left, operator, right } => self.visit_binary(left, operator, right),
             Expr::Grouping {expression} => self.visit_grouping(expression),
             Expr::Literal {value} => self.visit_literal(value),
             Expr::Unary {operator, right} => self.visit_unary(operator, right),
-            Expr::Variable {token} => self.visit_variable(token),
-            Expr::Assign {name, value} => self.visit_assignment(name, value),
+            Expr::Variable {token, slot} => self.visit_variable(token, slot.get()),
+            Expr::Assign {name, value, slot} => self.visit_assignment(name, value, slot.get()),
             Expr::Logical {left, operator, right} => self.visit_logical(left, operator, right),
-            Expr::Call {callee, paren, arguments} => self.visit_call(callee, paren, arguments)
+            Expr::Call {callee, paren, arguments} => self.visit_call(callee, paren, arguments),
+            Expr::Get {object, name} => self.visit_get(object, name)
         }
     }
 
-    fn visit_binary(&mut self, left: Box<Expr>, operator: Token, right: Box<Expr>) -> Option<T>;
-    fn visit_grouping(&mut self, expression: Box<Expr>) -> Option<T>;
-    fn visit_literal(&mut self, value: Literal) -> Option<T>;
-    fn visit_unary(&mut self, operator: Token, right: Box<Expr>) -> Option<T>;
-    fn visit_variable(&mut self, token: Token) -> Option<T>;
-    fn visit_assignment(&mut self, name: Token, value: Box<Expr>) -> Option<T>;
-    fn visit_logical(&mut self, left: Box<Expr>, operator: Token, right: Box<Expr>) -> Option<T>;
-    fn visit_call(&mut self, callee: Box<Expr>, paren: Token, arguments: Vec<Expr>) -> Option<T>;
-}
\ No newline at end of file
+    fn visit_binary(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Option<T>;
+    fn visit_grouping(&mut self, expression: &Expr) -> Option<T>;
+    fn visit_literal(&mut self, value: &Literal) -> Option<T>;
+    fn visit_unary(&mut self, operator: &Token, right: &Expr) -> Option<T>;
+    fn visit_variable(&mut self, token: &Token, slot: Option<Slot>) -> Option<T>;
+    fn visit_assignment(&mut self, name: &Token, value: &Expr, slot: Option<Slot>) -> Option<T>;
+    fn visit_logical(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Option<T>;
+    fn visit_call(&mut self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> Option<T>;
+    fn visit_get(&mut self, object: &Expr, name: &Token) -> Option<T>;
+}
@@ -0,0 +1,291 @@
+use crate::token::{Token, TokenType};
+
+/*
+Formatter
+
+Reprints source in one canonical layout (crlox fmt). It works from the tokens, comments included,
+rather than the statements, as those have lost what the formatter must keep: for loops are whiles by
+then, and numbers and comments are gone. The source is parsed before it gets here, so the tokens are
+known to make up whole statements.
+
+The layout:
+  - each statement on a line of its own, indented four spaces for each block it is in
+  - an opening brace at the end of the line before it, a closing one on a line of its own, followed
+    by an else, catch or finally, and an empty block as {}
+  - a space either side of binary operators and after commas and keywords, none inside parentheses,
+    around dots, after unary operators or before the parentheses of a call
+  - a line longer than MAX_WIDTH is broken after the last comma or binary operator that fits, the
+    rest indented one more level
+  - a single blank line kept wherever there were blank lines between statements, and none at the
+    start or end of a block
+  - comments kept where they were, on the end of a line or on a line of their own
+*/
+
+const MAX_WIDTH: usize = 100;
+const INDENT: &str = "    ";
+
+// Part of the line being built, a token or a comment
+struct Piece {
+    text: String,
+    // Whether a space goes before it, unless it starts the line
+    space: bool,
+    // The line may be broken after it
+    break_after: bool,
+    // A comment never moves to the next line to make room
+    comment: bool,
+    unary: bool
+}
+
+pub struct Formatter {
+    tokens: Vec<Token>,
+    current: usize,
+    lines: Vec<String>,
+    pieces: Vec<Piece>,
+    indent: usize,
+    // Parentheses open on the line being built, a semicolon inside them (in a for) doesn't end it
+    parens: usize,
+    // The line being built carries on a statement broken by a comment
+    continued: bool,
+    // The last line written opened a block
+    opened: bool,
+    // The last token that isn't a comment, to tell unary operators from binary ones
+    previous: Option<TokenType>,
+    // The line the last token ended on, to find the blank lines
+    last_line: u32
+}
+
+impl Formatter {
+    pub fn formatter_builder(tokens: Vec<Token>) -> Formatter {
+        return Formatter {
+            tokens: tokens,
+            current: 0,
+            lines: Vec::new(),
+            pieces: Vec::new(),
+            indent: 0,
+            parens: 0,
+            continued: false,
+            opened: false,
+            previous: None,
+            last_line: 0
+        }
+    }
+
+    pub fn format(&mut self) -> String {
+        while self.current < self.tokens.len() {
+            let token: Token = self.tokens[self.current].clone();
+            self.current += 1;
+            if token.token_type == TokenType::Eof {
+                break;
+            }
+
+            self.token(&token);
+
+            // A token may have taken the one after it along (an empty block's closing brace)
+            let last: &Token = &self.tokens[self.current - 1];
+            self.last_line = Self::end_line(last);
+            if last.token_type != TokenType::Comment {
+                self.previous = Some(last.token_type.clone());
+            }
+        }
+        self.end_line_of_code();
+
+        if self.lines.is_empty() {
+            return String::new();
+        }
+        return format!("{}\n", self.lines.join("\n"));
+    }
+
+    fn token(&mut self, token: &Token) {
+        match token.token_type {
+            TokenType::Comment => self.comment(token),
+            TokenType::LBrace => {
+                self.blank_line(token);
+                // A brace a comment pushed onto a line of its own lines up with its statement
+                if self.pieces.is_empty() {
+                    self.continued = false;
+                }
+                if self.next_type() == Some(TokenType::RBrace) {
+                    self.current += 1;
+                    self.push("{}", true, false);
+                    self.after_block();
+                    return;
+                }
+
+                self.push("{", true, false);
+                self.end_line_of_code();
+                self.indent += 1;
+                self.opened = true;
+            }
+            TokenType::RBrace => {
+                self.end_line_of_code();
+                self.indent = self.indent.saturating_sub(1);
+                self.push("}", false, false);
+                self.after_block();
+            }
+            TokenType::Semicolon => {
+                self.push(";", false, false);
+                if self.parens == 0 {
+                    self.end_line_of_code();
+                }
+            }
+            _ => {
+                self.blank_line(token);
+                let previous_unary: bool = self.pieces.last().map(|a| a.unary).unwrap_or(false);
+                let unary: bool = self.is_unary(&token.token_type);
+                let space: bool = !previous_unary && self.spaced(&token.token_type);
+                let break_after: bool = token.token_type == TokenType::Comma || (!unary && Self::is_operator(&token.token_type));
+                self.pieces.push(Piece {text: token.lexeme.to_string(), space: space, break_after: break_after, comment: false, unary: unary});
+
+                match token.token_type {
+                    TokenType::LParen => self.parens += 1,
+                    TokenType::RParen => self.parens = self.parens.saturating_sub(1),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // A comment stays on the end of the line it was on, or on a line of its own
+    fn comment(&mut self, token: &Token) {
+        let text: String = token.lexeme.text().trim_end().to_string();
+        let trailing: bool = self.current > 1 && token.line == self.last_line;
+        let statement: bool = !self.pieces.is_empty() || self.continued;
+
+        // On the end of a line already written, as nothing else follows on it
+        if trailing && self.pieces.is_empty() && !self.continued {
+            if let Some(a) = self.lines.last_mut() {
+                a.push(' ');
+                a.push_str(&text);
+                return;
+            }
+        }
+
+        // On a line of its own in the middle of a statement, which carries on after it
+        if !trailing && !self.pieces.is_empty() {
+            self.flush();
+            self.continued = true;
+        }
+        self.blank_line(token);
+        self.pieces.push(Piece {text: text.clone(), space: true, break_after: false, comment: true, unary: false});
+
+        // Code only follows on the same line if it did before
+        let next_line: Option<u32> = self.tokens.get(self.current).map(|a| a.line);
+        if text.starts_with("//") || next_line.is_some_and(|a| a > Self::end_line(token)) {
+            self.flush();
+            self.continued = statement;
+        }
+    }
+
+    // After a closing brace, an else, catch or finally carries on the same line
+    fn after_block(&mut self) {
+        match self.next_type() {
+            Some(TokenType::Else) | Some(TokenType::Catch) | Some(TokenType::Finally) => {}
+            _ => self.end_line_of_code()
+        }
+    }
+
+    // Keep one blank line where the source had any between statements
+    fn blank_line(&mut self, token: &Token) {
+        if self.pieces.is_empty() && !self.continued && !self.opened && !self.lines.is_empty()
+            && token.token_type != TokenType::RBrace && token.line > self.last_line + 1 {
+            self.lines.push(String::new());
+        }
+    }
+
+    fn push(&mut self, text: &str, space: bool, break_after: bool) {
+        self.pieces.push(Piece {text: text.to_string(), space: space, break_after: break_after, comment: false, unary: false});
+    }
+
+    // Finish the line being built along with the statement it holds
+    fn end_line_of_code(&mut self) {
+        self.flush();
+        self.continued = false;
+    }
+
+    // Write out the line being built, broken up where it runs past MAX_WIDTH
+    fn flush(&mut self) {
+        if self.pieces.is_empty() {
+            return;
+        }
+
+        let continuation: String = INDENT.repeat(self.indent + 1);
+        let mut line: String = match self.continued {
+            true => continuation.clone(),
+            false => INDENT.repeat(self.indent)
+        };
+        let mut last_break: Option<usize> = None;
+        for (i, piece) in std::mem::take(&mut self.pieces).into_iter().enumerate() {
+            let text: String = match i > 0 && piece.space {
+                true => format!(" {}", piece.text),
+                false => piece.text
+            };
+
+            if !piece.comment && Self::width(&line) + text.chars().count() > MAX_WIDTH {
+                if let Some(a) = last_break.take() {
+                    let rest: String = line.split_off(a);
+                    self.lines.push(line.trim_end().to_string());
+                    line = format!("{}{}", continuation, rest.trim_start());
+                }
+            }
+
+            // Nothing but the indent yet when the break came right before this piece
+            match line.trim().is_empty() {
+                true => line.push_str(text.trim_start()),
+                false => line.push_str(&text)
+            }
+            if piece.break_after {
+                last_break = Some(line.len());
+            }
+        }
+
+        self.lines.push(line);
+        self.opened = false;
+    }
+
+    // Width of the last line in some text, a string can hold several
+    fn width(text: &str) -> usize {
+        return text.rsplit('\n').next().unwrap_or("").chars().count();
+    }
+
+    fn end_line(token: &Token) -> u32 {
+        return token.line + token.lexeme.text().matches('\n').count() as u32;
+    }
+
+    fn next_type(&self) -> Option<TokenType> {
+        return self.tokens.get(self.current).map(|a| a.token_type.clone());
+    }
+
+    // Whether a token is spaced from the one before it on the line
+    fn spaced(&self, token_type: &TokenType) -> bool {
+        match token_type {
+            TokenType::RParen | TokenType::Comma | TokenType::Semicolon | TokenType::Dot => return false,
+            // Calls, not the parentheses after a keyword or operator
+            TokenType::LParen if matches!(self.previous, Some(TokenType::Id) | Some(TokenType::RParen) | Some(TokenType::This) | Some(TokenType::Super)) => return false,
+            _ => {}
+        }
+
+        match self.previous {
+            Some(TokenType::LParen) | Some(TokenType::Dot) => return false,
+            _ => return true
+        }
+    }
+
+    // A minus is only unary where an operand can't have just ended
+    fn is_unary(&self, token_type: &TokenType) -> bool {
+        match token_type {
+            TokenType::Bang => return true,
+            TokenType::Minus => {
+                return !matches!(self.previous, Some(TokenType::Id) | Some(TokenType::Num) | Some(TokenType::String)
+                    | Some(TokenType::True) | Some(TokenType::False) | Some(TokenType::Nil) | Some(TokenType::This)
+                    | Some(TokenType::Super) | Some(TokenType::RParen));
+            }
+            _ => return false
+        }
+    }
+
+    fn is_operator(token_type: &TokenType) -> bool {
+        return matches!(token_type, TokenType::Minus | TokenType::Plus | TokenType::Slash | TokenType::Star
+            | TokenType::BangEqual | TokenType::Equal | TokenType::EqualEqual | TokenType::Greater | TokenType::GreaterEqual
+            | TokenType::Less | TokenType::LessEqual | TokenType::And | TokenType::Or | TokenType::Question | TokenType::Colon);
+    }
+}
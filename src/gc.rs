@@ -0,0 +1,333 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::rc::{Rc, Weak};
+use crate::callable::{LoxCallable, SharedUpvalue, Upvalue};
+use crate::environment::{Environment, SharedEnvironment};
+use crate::error::RuntimeError;
+use crate::token::Literal;
+
+// Collections made after this many new objects have been tracked, unless there are more live
+// objects than that, in which case it waits for as many new ones as there are live ones.
+const DEFAULT_THRESHOLD: usize = 10000;
+
+/*
+Garbage collector
+
+Values are reference counted, which frees everything except cycles, such as a list holding itself
+or a function declared in a scope, whose closure holds that same scope. So every object that can
+hold other values (lists, maps, functions, the scopes functions close over and the variables
+compiled functions close over) is tracked in its interpreter's Heap, and a cycle collection every
+so often finds the cycles nothing can reach any more and breaks them up, after which reference
+counting frees them.
+
+It is a cycle collector using trial deletion, not a tracing collector that owns the objects. Values
+are held directly all over the Rust side (the tree-walker's temporaries, natives, a host's own
+variables), so there is no list of roots to start tracing from. Instead the references between
+tracked objects are taken away from each object's reference count, and anything left over must
+come from outside, making that object a root. Everything reachable from the roots is marked, and
+whatever isn't is emptied out.
+
+Caught errors are tracked too, as the value one was thrown with can hold the error again. An error
+can't be emptied, but it can't be changed to hold anything new either, so a cycle through one always
+passes through a list, map or scope as well, and emptying that frees the error. Strings hold no other values and are freed as soon as nothing uses them. Lists and maps a host
+builds in Rust are tracked once they reach a script, those nested inside them aren't.
+*/
+
+// Something that holds on to other values, and so can be part of a cycle
+#[derive(Clone)]
+pub enum Object {
+    List(Rc<RefCell<Vec<Literal>>>),
+    Map(Rc<RefCell<HashMap<String, Literal>>>),
+    Callable(Rc<dyn LoxCallable>),
+    Environment(SharedEnvironment),
+    Upvalue(SharedUpvalue),
+    Error(Rc<RuntimeError>)
+}
+
+// Visits what an object holds on to, see Object::children
+pub trait Tracer {
+    fn value(&mut self, value: &Literal);
+    fn environment(&mut self, environment: &SharedEnvironment);
+    fn upvalue(&mut self, upvalue: &SharedUpvalue);
+}
+
+// A tracked object, weak so that tracking it doesn't keep it alive
+enum Tracked {
+    List(Weak<RefCell<Vec<Literal>>>),
+    Map(Weak<RefCell<HashMap<String, Literal>>>),
+    Callable(Weak<dyn LoxCallable>),
+    Environment(Weak<RefCell<Environment>>),
+    Upvalue(Weak<RefCell<Upvalue>>),
+    Error(Weak<RuntimeError>)
+}
+
+// Every object an interpreter has tracked
+pub struct Heap {
+    objects: Vec<Tracked>,
+    // Objects tracked since the last collection
+    allocated: usize,
+    // Objects still live after the last collection
+    live: usize,
+    threshold: usize,
+    // Collect every time an object is tracked, to shake out objects the collector misses
+    stress: bool
+}
+
+impl Object {
+    // The object behind a value, if it is one
+    pub fn from_value(value: &Literal) -> Option<Object> {
+        match value {
+            Literal::List(a) => return Some(Object::List(a.clone())),
+            Literal::Map(a) => return Some(Object::Map(a.clone())),
+            Literal::Callable(a) => return Some(Object::Callable(a.clone())),
+            Literal::Error(a) => return Some(Object::Error(a.clone())),
+            _ => return None
+        }
+    }
+
+    // Where the object lives, which identifies it
+    pub fn address(&self) -> usize {
+        match self {
+            Object::List(a) => return Rc::as_ptr(a) as *const () as usize,
+            Object::Map(a) => return Rc::as_ptr(a) as *const () as usize,
+            Object::Callable(a) => return Rc::as_ptr(a) as *const () as usize,
+            Object::Environment(a) => return Rc::as_ptr(a) as *const () as usize,
+            Object::Upvalue(a) => return Rc::as_ptr(a) as *const () as usize,
+            Object::Error(a) => return Rc::as_ptr(a) as *const () as usize
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Object::List(a) => return Rc::strong_count(a),
+            Object::Map(a) => return Rc::strong_count(a),
+            Object::Callable(a) => return Rc::strong_count(a),
+            Object::Environment(a) => return Rc::strong_count(a),
+            Object::Upvalue(a) => return Rc::strong_count(a),
+            Object::Error(a) => return Rc::strong_count(a)
+        }
+    }
+
+    fn downgrade(&self) -> Tracked {
+        match self {
+            Object::List(a) => return Tracked::List(Rc::downgrade(a)),
+            Object::Map(a) => return Tracked::Map(Rc::downgrade(a)),
+            Object::Callable(a) => return Tracked::Callable(Rc::downgrade(a)),
+            Object::Environment(a) => return Tracked::Environment(Rc::downgrade(a)),
+            Object::Upvalue(a) => return Tracked::Upvalue(Rc::downgrade(a)),
+            Object::Error(a) => return Tracked::Error(Rc::downgrade(a))
+        }
+    }
+
+    // Visit everything the object holds directly, false if it is in use and couldn't be looked into
+    pub fn children(&self, tracer: &mut dyn Tracer) -> bool {
+        match self {
+            Object::List(a) => match a.try_borrow() {
+                Ok(b) => b.iter().for_each(|c| tracer.value(c)),
+                Err(_) => return false
+            },
+            Object::Map(a) => match a.try_borrow() {
+                Ok(b) => b.values().for_each(|c| tracer.value(c)),
+                Err(_) => return false
+            },
+            Object::Callable(a) => return a.trace(tracer),
+            Object::Environment(a) => match a.try_borrow() {
+                Ok(b) => {
+                    b.values().for_each(|c| tracer.value(c));
+                    if let Some(c) = &b.enclosing {
+                        tracer.environment(c);
+                    }
+                }
+                Err(_) => return false
+            },
+            // An open upvalue points at the stack, which isn't garbage
+            Object::Upvalue(a) => match a.try_borrow() {
+                Ok(b) => {
+                    if let Upvalue::Closed(c) = &*b {
+                        tracer.value(c);
+                    }
+                }
+                Err(_) => return false
+            },
+            Object::Error(a) => tracer.value(&a.value)
+        }
+
+        return true;
+    }
+
+    // Empty out a garbage object, dropping its references to other objects
+    fn clear(&self) {
+        match self {
+            Object::List(a) => a.borrow_mut().clear(),
+            Object::Map(a) => a.borrow_mut().clear(),
+            Object::Callable(a) => a.clear(),
+            Object::Environment(a) => a.borrow_mut().clear(),
+            Object::Upvalue(a) => *a.borrow_mut() = Upvalue::Closed(Literal::Nill),
+            // Freed along with the rest of its cycle, see above
+            Object::Error(_) => {}
+        }
+    }
+}
+
+impl Tracked {
+    fn upgrade(&self) -> Option<Object> {
+        match self {
+            Tracked::List(a) => return a.upgrade().map(Object::List),
+            Tracked::Map(a) => return a.upgrade().map(Object::Map),
+            Tracked::Callable(a) => return a.upgrade().map(Object::Callable),
+            Tracked::Environment(a) => return a.upgrade().map(Object::Environment),
+            Tracked::Upvalue(a) => return a.upgrade().map(Object::Upvalue),
+            Tracked::Error(a) => return a.upgrade().map(Object::Error)
+        }
+    }
+}
+
+// Hands the address of each object visited to a callback
+struct Edges<'a> {
+    visit: &'a mut dyn FnMut(usize)
+}
+
+impl Tracer for Edges<'_> {
+    fn value(&mut self, value: &Literal) {
+        if let Some(a) = Object::from_value(value) {
+            (self.visit)(a.address());
+        }
+    }
+
+    fn environment(&mut self, environment: &SharedEnvironment) {
+        (self.visit)(Rc::as_ptr(environment) as *const () as usize);
+    }
+
+    fn upvalue(&mut self, upvalue: &SharedUpvalue) {
+        (self.visit)(Rc::as_ptr(upvalue) as *const () as usize);
+    }
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Heap {
+            objects: Vec::new(),
+            allocated: 0,
+            live: 0,
+            threshold: DEFAULT_THRESHOLD,
+            stress: false
+        }
+    }
+}
+
+impl Heap {
+    // Track a newly made list, map or function, possibly collecting first
+    pub fn track(&mut self, value: &Literal) {
+        if let Some(a) = Object::from_value(value) {
+            self.track_object(a.downgrade());
+        }
+    }
+
+    // Track the scope a function closes over, along with the scopes around it. A scope no function has
+    // closed over can only be reached from the interpreter, so it can't be part of a cycle and isn't tracked.
+    pub fn track_environment(&mut self, environment: &SharedEnvironment) {
+        let mut scope: Option<SharedEnvironment> = Some(environment.clone());
+        while let Some(a) = scope {
+            // The scopes around a tracked one are already tracked
+            if a.borrow().tracked {
+                return;
+            }
+            a.borrow_mut().tracked = true;
+            self.track_object(Tracked::Environment(Rc::downgrade(&a)));
+            scope = a.borrow().enclosing.clone();
+        }
+    }
+
+    // Track a variable a compiled function closes over, which can hold that same function
+    pub fn track_upvalue(&mut self, upvalue: &SharedUpvalue) {
+        self.track_object(Tracked::Upvalue(Rc::downgrade(upvalue)));
+    }
+
+    fn track_object(&mut self, tracked: Tracked) {
+        self.objects.push(tracked);
+        self.allocated += 1;
+        if self.stress || self.allocated >= self.threshold.max(self.live) {
+            self.collect();
+        }
+    }
+
+    // Set how many objects are tracked between collections, at the least
+    pub fn set_threshold(&mut self, threshold: usize) {
+        self.threshold = threshold.max(1);
+    }
+
+    // Turn collecting on every allocation on or off
+    pub fn set_stress(&mut self, stress: bool) {
+        self.stress = stress;
+    }
+
+    // How many tracked objects are alive, as of the last collection
+    pub fn live_objects(&self) -> usize {
+        return self.live;
+    }
+
+    // Collect now, giving back how many objects were freed
+    pub fn collect(&mut self) -> usize {
+        // Hold on to everything still alive, each object once
+        let mut objects: Vec<Object> = Vec::new();
+        let mut index: HashMap<usize, usize> = HashMap::new();
+        for entry in std::mem::take(&mut self.objects) {
+            if let Some(a) = entry.upgrade() {
+                if let Entry::Vacant(b) = index.entry(a.address()) {
+                    b.insert(objects.len());
+                    objects.push(a);
+                }
+            }
+        }
+
+        // References from outside of the tracked objects, less the one just taken above
+        let mut external: Vec<usize> = objects.iter().map(|a| a.strong_count() - 1).collect();
+        let mut traced: Vec<bool> = vec![true; objects.len()];
+        for (i, object) in objects.iter().enumerate() {
+            let mut visit = |a: usize| {
+                if let Some(b) = index.get(&a) {
+                    external[*b] -= 1;
+                }
+            };
+            traced[i] = object.children(&mut Edges {visit: &mut visit});
+        }
+
+        // Mark everything reachable from the roots. An object in use elsewhere couldn't be looked into,
+        // so it is a root too, as are all of its contents as their references from it were never taken away.
+        let mut marked: Vec<bool> = vec![false; objects.len()];
+        let mut pending: Vec<usize> = Vec::new();
+        for i in 0..objects.len() {
+            if external[i] > 0 || !traced[i] {
+                marked[i] = true;
+                pending.push(i);
+            }
+        }
+        while let Some(i) = pending.pop() {
+            let mut visit = |a: usize| {
+                if let Some(b) = index.get(&a) {
+                    if !marked[*b] {
+                        marked[*b] = true;
+                        pending.push(*b);
+                    }
+                }
+            };
+            objects[i].children(&mut Edges {visit: &mut visit});
+        }
+
+        // Sweep by emptying out the garbage, breaking its cycles so it is freed once dropped below
+        let mut freed: usize = 0;
+        for (i, object) in objects.iter().enumerate() {
+            if marked[i] {
+                self.objects.push(object.downgrade());
+            } else {
+                object.clear();
+                freed += 1;
+            }
+        }
+
+        self.allocated = 0;
+        self.live = self.objects.len();
+        return freed;
+    }
+}
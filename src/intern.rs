@@ -0,0 +1,125 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+// An interned string, standing in for its text. Symbols from the same interner are equal exactly
+// when their text is, so identifiers are compared and hashed by address rather than as strings.
+#[derive(Clone)]
+pub struct Symbol(Rc<str>);
+
+// The names one interpreter has seen, each stored once. Only identifiers, keywords and operators
+// are interned, never string values, so it grows with the names in the source and no further.
+#[derive(Default)]
+pub struct Interner {
+    strings: HashSet<Rc<str>>
+}
+
+impl Interner {
+    // The symbol for some text, interning it if this is the first time it has been seen
+    pub fn intern(&mut self, text: &str) -> Symbol {
+        if let Some(a) = self.strings.get(text) {
+            return Symbol(a.clone());
+        }
+
+        let text: Rc<str> = Rc::from(text);
+        self.strings.insert(text.clone());
+        return Symbol(text);
+    }
+
+    // The symbol for some text, None if it was never interned and so can't name anything
+    pub fn lookup(&self, text: &str) -> Option<Symbol> {
+        return self.strings.get(text).map(|a| Symbol(a.clone()));
+    }
+}
+
+impl Symbol {
+    // A symbol for text that is only ever shown, such as the lexeme of a string or the operator
+    // an error is reported at. It is left out of the interner, and equal to no other symbol.
+    pub fn detached(text: &str) -> Symbol {
+        return Symbol(Rc::from(text));
+    }
+
+    // The text a symbol stands for, shared with every other use of it
+    pub fn text(&self) -> Rc<str> {
+        return self.0.clone();
+    }
+
+    fn address(&self) -> usize {
+        return Rc::as_ptr(&self.0) as *const u8 as usize;
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Symbol) -> bool {
+        return Rc::ptr_eq(&self.0, &other.0);
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.address().hash(state);
+    }
+}
+
+// Display a Symbol as its text
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// Debug a Symbol as its quoted text
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Limits, Lox};
+
+    #[test]
+    fn same_text_same_symbol() {
+        let mut interner: Interner = Interner::default();
+        let first: Symbol = interner.intern("count");
+        let second: Symbol = interner.intern(&String::from("count"));
+        assert_eq!(first, second);
+        assert_ne!(first, interner.intern("counts"));
+        assert_eq!(interner.strings.len(), 2);
+    }
+
+    // Symbols from another interner, or detached ones, are other names even with the same text
+    #[test]
+    fn equality_is_by_interner() {
+        let mut interner: Interner = Interner::default();
+        let symbol: Symbol = interner.intern("a");
+        assert_ne!(symbol, Symbol::detached("a"));
+        assert_ne!(symbol, Interner::default().intern("a"));
+        assert_eq!(symbol.to_string(), Symbol::detached("a").to_string());
+    }
+
+    // Looking a name up, as get_global does, never adds it
+    #[test]
+    fn lookup_does_not_intern() {
+        let mut interner: Interner = Interner::default();
+        assert!(interner.lookup("missing").is_none());
+        assert!(interner.strings.is_empty());
+        let symbol: Symbol = interner.intern("present");
+        assert_eq!(interner.lookup("present"), Some(symbol));
+    }
+
+    #[test]
+    fn names_are_interned_and_strings_are_not() {
+        let mut interner: Interner = Interner::default();
+        Lox::parse("var name = \"some text\"; fun f(name) { return name; }", Limits::default(), &mut interner).expect("parses");
+        assert!(interner.lookup("name").is_some());
+        assert!(interner.lookup("f").is_some());
+        assert!(interner.lookup("some text").is_none());
+        assert!(interner.lookup("\"some text\"").is_none());
+    }
+}
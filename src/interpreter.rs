@@ -1,358 +1,1139 @@
-use crate::expression::{Expr, ExprVisitor};
+use std::fs;
+use std::io::Write;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use crate::{Backend, Capabilities, Diagnostic, DumpFormat, Limits, Lox, Value};
+use crate::expression::{Expr, ExprVisitor, Slot};
 use crate::token::{Literal, Token, TokenType};
 use crate::stmt::{Stmt, StmtVisitor};
-use crate::environment::Environment;
+use crate::environment::{Environment, Globals, SharedEnvironment};
+use crate::error::{CallFrame, RuntimeError};
+use crate::callable::{HostFunction, LoxCallable, LoxFunction};
+use crate::chunk::Prototype;
+use crate::ast_printer::AstPrinter;
+use crate::json::{self, JsonWriter};
+use crate::formatter::Formatter;
+use crate::loxc;
+use crate::natives::{io, list, map, string, system};
+use crate::natives::math::{self, Rng};
+use crate::output::{CallbackWriter, Sink};
+use crate::gc::{Heap, Tracer};
+use crate::memory::Meter;
+use crate::intern::{Interner, Symbol};
 
+mod vm;
+
+// Why statements are being skipped over on the way out of a block
+enum Unwind {
+    Error(RuntimeError),
+    Return(Literal)
+}
+
+// Measuring memory walks everything live, so after each measurement at least 1/MEMORY_SLACK of the
+// limit more is allocated before the next one
+const MEMORY_SLACK: usize = 16;
+
+// How much longer a script may run for, checked at loop back-edges and calls
+struct Budget {
+    // Steps (calls and passes round a loop) taken since the step limit was set
+    steps: u64,
+    max_steps: Option<u64>,
+    deadline: Option<Instant>,
+    // Set from anywhere, including other threads, to stop the script
+    cancelled: Arc<AtomicBool>,
+    // Bytes allocated since memory was last measured, plus what was live then
+    allocated: usize,
+    // What "allocated" can reach before memory is measured again
+    measure_at: usize,
+    max_memory: Option<usize>,
+    // Set when a native runs out of memory, so that the error it gives back stops the script
+    out_of_memory: bool
+}
+
+// A Lox interpreter, walking the tree or running bytecode. Globals, including the natives, persist
+// from one run to the next so it can be fed a script a piece at a time, as the prompt does.
 pub struct Interpreter {
-    instance: crate::Lox,
-    environment: Environment
+    limits: Limits,
+    backend: Backend,
+    // Whether the virtual machine traces the instructions it runs
+    trace_execution: bool,
+    globals: Globals,
+    // The local scopes, None when running top level code
+    environment: Option<SharedEnvironment>,
+    // The scopes of the calls in progress, put aside while the function they called runs
+    suspended: Vec<Option<SharedEnvironment>>,
+    // What is currently unwinding through the statements being executed, if anything
+    unwinding: Option<Unwind>,
+    // The calls in progress, outermost first
+    call_stack: Vec<CallFrame>,
+    // How many expressions and statements are being evaluated inside of each other, across calls
+    depth: usize,
+    budget: Budget,
+    // The stack and frames of the virtual machine
+    vm: vm::Vm,
+    // The objects that can make up cycles, for the garbage collector
+    heap: Heap,
+    // The names in the source this interpreter has run, see intern.rs
+    interner: Interner,
+    // Where print writes to
+    output: Sink,
+    // Where diagnostics and eprint write to
+    error_output: Sink,
+    pub rng: Rng
 }
 
 impl Interpreter {
-    // Build an interpreter
-    pub fn build_interpreter(instance: crate::Lox) -> Interpreter {
-        Interpreter {
-            instance: instance,
-            environment: Environment::build_environment(instance, Box::new(Environment::default()))
+    // Build an interpreter, with the natives the capabilities allow and the script's arguments defined as globals
+    pub fn build_interpreter(limits: Limits, capabilities: Capabilities, arguments: &[String]) -> Interpreter {
+        let mut interpreter: Interpreter = Interpreter {
+            limits: limits,
+            backend: Backend::default(),
+            trace_execution: false,
+            globals: Globals::default(),
+            environment: None,
+            suspended: Vec::new(),
+            unwinding: None,
+            call_stack: Vec::new(),
+            depth: 0,
+            budget: Budget {
+                steps: 0,
+                max_steps: None,
+                deadline: None,
+                cancelled: Arc::new(AtomicBool::new(false)),
+                allocated: 0,
+                measure_at: 0,
+                max_memory: None,
+                out_of_memory: false
+            },
+            vm: vm::Vm::default(),
+            heap: Heap::default(),
+            interner: Interner::default(),
+            output: Box::new(std::io::stdout()),
+            error_output: Box::new(std::io::stderr()),
+            rng: Rng::from_time()
+        };
+        math::define_math(&mut interpreter);
+        io::define_io(&mut interpreter, capabilities);
+        system::define_system(&mut interpreter, capabilities);
+        system::define_arguments(&mut interpreter, arguments);
+        return interpreter;
+    }
+
+    // Scan, parse and run some source. Gives back the value of the last top level expression
+    // statement (nil if there wasn't one), or what went wrong.
+    pub fn run(&mut self, source: &str) -> Result<Value, Vec<Diagnostic>> {
+        let result: Result<Value, Vec<Diagnostic>> = crate::with_stack(|| {
+            match self.backend {
+                Backend::TreeWalker => {
+                    let statements: Vec<Stmt> = Lox::parse(source, self.limits, &mut self.interner)?;
+                    return self.interpret(&statements);
+                }
+                Backend::Vm => {
+                    let script: Rc<Prototype> = Lox::compile(source, self.limits, &mut self.interner)?;
+                    return self.run_script(script);
+                }
+            }
+        });
+        self.flush();
+        return result;
+    }
+
+    // Choose how scripts run from now on
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    // Write the virtual machine's stack and each instruction to the error output as it runs them
+    pub fn set_trace_execution(&mut self, trace_execution: bool) {
+        self.trace_execution = trace_execution;
+    }
+
+    // Read a script in and run it. A script compiled ahead of time (a .loxc file) runs on the virtual
+    // machine whichever backend is chosen.
+    pub fn run_file(&mut self, path: &str) -> Result<Value, Vec<Diagnostic>> {
+        if Self::is_compiled(path) {
+            let script: Rc<Prototype> = Self::load_compiled(path, self.limits, &mut self.interner)?;
+            let result: Result<Value, Vec<Diagnostic>> = crate::with_stack(|| self.run_script(script));
+            self.flush();
+            return result;
         }
+
+        let source: String = Self::read_source(path)?;
+        return self.run(&source);
     }
 
-    // Interpret an expression
-    pub fn interpret(&mut self, statements: Vec<Stmt>) {
-        for statement in statements {
-            self.execute(statement)
+    // Compile some source to the bytes of a .loxc file. Its names are interned afresh, as they are
+    // again whenever it is loaded.
+    pub fn compile(&self, source: &str) -> Result<Vec<u8>, Vec<Diagnostic>> {
+        let script: Rc<Prototype> = crate::with_stack(|| Lox::compile(source, self.limits, &mut Interner::default()))?;
+        return Ok(loxc::write(&script));
+    }
+
+    // Compile a script and save it to "output", to run later with run_file
+    pub fn compile_file(&self, path: &str, output: &str) -> Result<(), Vec<Diagnostic>> {
+        let source: String = Self::read_source(path)?;
+        let bytes: Vec<u8> = self.compile(&source)?;
+        match fs::write(output, bytes) {
+            Ok(_) => return Ok(()),
+            Err(a) => return Err(vec![Diagnostic::io(format!("Could not write file '{}': {}.", output, a))])
         }
     }
 
-    // If there is an error send it here to report to the Lox instance
-    fn error(&mut self, token: Token, message: String) {
-        self.instance.interpreter_error(token, &*message);
+    // The bytecode some source compiles to, listed one function after another
+    pub fn disassemble(&self, source: &str) -> Result<String, Vec<Diagnostic>> {
+        let script: Rc<Prototype> = crate::with_stack(|| Lox::compile(source, self.limits, &mut Interner::default()))?;
+        return Ok(script.disassemble());
     }
 
-    fn binary_error(&mut self, left: Literal, operator: Token, right: Literal) {
-        let message: String = format!("{:?} and {:?} must both be numbers", left, right);
-        self.error(operator, message);
+    // Read a script in and disassemble it, it can be a .loxc file as well as source
+    pub fn disassemble_file(&self, path: &str) -> Result<String, Vec<Diagnostic>> {
+        if Self::is_compiled(path) {
+            return Ok(Self::load_compiled(path, self.limits, &mut Interner::default())?.disassemble());
+        }
+
+        let source: String = Self::read_source(path)?;
+        return self.disassemble(&source);
     }
 
-    // Return trues (Nill and False are false, anything else true)
-    fn is_truthy(literal: Literal) -> bool {
-        match literal {
-            Literal::Nill => return false,
-            Literal::False => return false,
-            Literal::True => return true,
-            _ => return true
+    // The tokens some source scans to, one a line with where it starts, or as JSON. The listing is
+    // for reading, tools should ask for JSON.
+    pub fn dump_tokens(&self, source: &str, format: DumpFormat) -> Result<String, Vec<Diagnostic>> {
+        let tokens: Vec<Token> = Lox::scan(source, self.limits, false, &mut Interner::default())?;
+        if format == DumpFormat::Json {
+            return Ok(format!("{}\n", json::tokens(&tokens)));
         }
+
+        let mut listing: String = String::new();
+        for token in tokens {
+            // A string can run over several lines, which are kept to one here. Its value is written
+            // as a JSON string, so nothing in it can be mistaken for the end of the line.
+            let lexeme: String = token.lexeme.text().replace('\r', "\\r").replace('\n', "\\n").replace('\t', "\\t");
+            let literal: String = match token.literal {
+                Some(Literal::Str(a)) => json::string(&a),
+                Some(a) => a.to_string(),
+                None => String::new()
+            };
+            let line: String = format!("{:>4}:{:<4} {:<13} {:<16} {}", token.line, token.column, token.token_type.to_string(), lexeme, literal);
+            listing.push_str(line.trim_end());
+            listing.push('\n');
+        }
+        return Ok(listing);
     }
-    
-    // Check equality
-    fn is_equal(left: Literal, right: Literal) -> bool {
-        match (left, right) {
-            (Literal::Nill, Literal::Nill) => true,
-            (Literal::True, Literal::True) => true,
-            (Literal::False, Literal::False) => true,
-            (Literal::Str(a), Literal::Str(b)) => a == b,
-            (Literal::Num(a), Literal::Num(b)) => a == b,
-            _ => false
+
+    // The statements some source parses to, before they are optimized, as S-expressions or as JSON
+    pub fn dump_ast(&self, source: &str, format: DumpFormat) -> Result<String, Vec<Diagnostic>> {
+        return crate::with_stack(|| {
+            let statements: Vec<Stmt> = Lox::parse_tree(source, self.limits, &mut Interner::default())?;
+            match format {
+                DumpFormat::Text => return Ok(AstPrinter::ast_printer_builder().print(&statements)),
+                DumpFormat::Json => return Ok(format!("{}\n", JsonWriter::json_writer_builder().write(&statements)))
+            }
+        });
+    }
+
+    // Read a script in and dump its tokens, see dump_tokens
+    pub fn dump_tokens_file(&self, path: &str, format: DumpFormat) -> Result<String, Vec<Diagnostic>> {
+        let source: String = Self::read_source_only(path, "tokens")?;
+        return self.dump_tokens(&source, format);
+    }
+
+    // Read a script in and dump its statements, see dump_ast
+    pub fn dump_ast_file(&self, path: &str, format: DumpFormat) -> Result<String, Vec<Diagnostic>> {
+        let source: String = Self::read_source_only(path, "a syntax tree")?;
+        return self.dump_ast(&source, format);
+    }
+
+    // Some source in the canonical layout, see formatter.rs. Source that doesn't parse is refused.
+    pub fn format(&self, source: &str) -> Result<String, Vec<Diagnostic>> {
+        let mut interner: Interner = Interner::default();
+        crate::with_stack(|| Lox::parse_tree(source, self.limits, &mut interner))?;
+        let tokens: Vec<Token> = Lox::scan(source, self.limits, true, &mut interner)?;
+        let formatted: String = Formatter::formatter_builder(tokens.clone()).format();
+
+        // Only the layout may change, never the tokens or the comments
+        let before: Vec<(TokenType, String)> = Self::layout_free(tokens);
+        let after: Vec<(TokenType, String)> = Self::layout_free(Lox::scan(&formatted, self.limits, true, &mut interner)?);
+        if before != after {
+            return Err(vec![Diagnostic::io("Could not format the source without changing what it says.".to_string())]);
         }
+        return Ok(formatted);
     }
 
-    // Convert literal to proper strings for display
-    fn stringify(literal: Literal) -> String {
-        match literal {
-            Literal::Nill => return String::from("nill"),
-            Literal::Num(a) => {
-                let a = a.to_string();
-                if a.ends_with(".0") {
-                    return a[..a.len() - 2].to_string()
-                } else {
-                    return a
-                }
+    // Format a script in place, or with "check" only see whether it needs it. Gives back whether
+    // it was already formatted.
+    pub fn format_file(&self, path: &str, check: bool) -> Result<bool, Vec<Diagnostic>> {
+        let source: String = Self::read_source_only(path, "code to format")?;
+        let formatted: String = self.format(&source)?;
+        if formatted == source {
+            return Ok(true);
+        }
+
+        if !check {
+            if let Err(a) = fs::write(path, formatted) {
+                return Err(vec![Diagnostic::io(format!("Could not write file '{}': {}.", path, a))]);
             }
-            _ => {
-                return literal.to_string()
+        }
+        return Ok(false);
+    }
+
+    // Tokens without where they are, comments without the space on the end of them
+    fn layout_free(tokens: Vec<Token>) -> Vec<(TokenType, String)> {
+        return tokens.into_iter().map(|a| (a.token_type, a.lexeme.text().trim_end().to_string())).collect();
+    }
+
+    // Read a script that has to be source, as a .loxc file has no "wanted" (tokens, a syntax tree) left
+    fn read_source_only(path: &str, wanted: &str) -> Result<String, Vec<Diagnostic>> {
+        if Self::is_compiled(path) {
+            return Err(vec![Diagnostic::io(format!("Could not read '{}': it is compiled, only its source has {}.", path, wanted))]);
+        }
+
+        return Self::read_source(path);
+    }
+
+    fn is_compiled(path: &str) -> bool {
+        return path.ends_with(".loxc");
+    }
+
+    fn read_source(path: &str) -> Result<String, Vec<Diagnostic>> {
+        match fs::read_to_string(path) {
+            Ok(a) => return Ok(a),
+            Err(a) => return Err(vec![Diagnostic::io(format!("Could not read file '{}': {}.", path, a))])
+        }
+    }
+
+    fn load_compiled(path: &str, limits: Limits, interner: &mut Interner) -> Result<Rc<Prototype>, Vec<Diagnostic>> {
+        let bytes: Vec<u8> = match fs::read(path) {
+            Ok(a) => a,
+            Err(a) => return Err(vec![Diagnostic::io(format!("Could not read file '{}': {}.", path, a))])
+        };
+
+        match crate::with_stack(|| loxc::read(&bytes, limits.max_nesting_depth, interner)) {
+            Ok(a) => return Ok(a),
+            Err(a) => return Err(vec![Diagnostic::io(format!("Could not load '{}': {}.", path, a))])
+        }
+    }
+
+    // The value of a global variable, natives included
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        // A name that was never interned isn't the name of anything
+        let name: Symbol = self.interner.lookup(name)?;
+        return self.globals.get(name).ok();
+    }
+
+    // Define (or redefine) a global variable
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.heap.track(&value);
+        self.define_global(name, value);
+    }
+
+    // Define a global the garbage collector needn't keep an eye on, such as a native
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        let name: Symbol = self.interner.intern(name);
+        self.globals.define(name, value);
+    }
+
+    // Stop scripts once they have taken this many more steps, None for no limit. A step is a call
+    // or a pass round a loop, which both backends count alike, so a script is stopped at the same
+    // point whichever it runs on.
+    pub fn set_max_steps(&mut self, max_steps: Option<u64>) {
+        self.budget.steps = 0;
+        self.budget.max_steps = max_steps;
+    }
+
+    // Stop scripts still running at the deadline, None for no deadline
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.budget.deadline = deadline;
+    }
+
+    // Stop scripts still running once the timeout has passed, counting from now
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.set_deadline(Some(Instant::now() + timeout));
+    }
+
+    // A flag that stops the running script when set, it stays set (stopping every script) until it is cleared
+    pub fn cancel_flag(&self) -> Arc<AtomicBool> {
+        return self.budget.cancelled.clone();
+    }
+
+    // Stop scripts whose values take up more than this many bytes, None for no limit
+    pub fn set_max_memory(&mut self, max_memory: Option<usize>) {
+        self.budget.allocated = 0;
+        self.budget.measure_at = max_memory.unwrap_or(0);
+        self.budget.max_memory = max_memory;
+    }
+
+    // Account for bytes about to be allocated. Values are freed as soon as nothing holds them, so
+    // once the count passes the limit it is replaced with what is actually still live, and only if
+    // that is over the limit too has the script run out of memory. With little room left under the
+    // limit, measuring again waits for MEMORY_SLACK's share of it to be allocated, so a script can
+    // go over by that much before it is stopped, rather than measuring on every allocation.
+    pub fn allocate(&mut self, bytes: usize) -> Result<(), RuntimeError> {
+        let max_memory: usize = match self.budget.max_memory {
+            Some(a) => a,
+            None => return Ok(())
+        };
+
+        self.budget.allocated += bytes;
+        if self.budget.allocated <= self.budget.measure_at {
+            return Ok(());
+        }
+
+        self.budget.allocated = self.live_bytes() + bytes;
+        if self.budget.allocated > max_memory {
+            return Err(RuntimeError::aborted("Out of memory.".to_string()));
+        }
+
+        self.budget.measure_at = max_memory.max(self.budget.allocated + max_memory / MEMORY_SLACK);
+        return Ok(());
+    }
+
+    // For natives, account for a result about to be built before building it, so that a huge one
+    // runs out of memory rather than being made first. The error only carries the message, the
+    // script is stopped once the native passes it on.
+    pub fn reserve(&mut self, bytes: usize) -> Result<(), String> {
+        match self.allocate(bytes) {
+            Ok(_) => return Ok(()),
+            Err(a) => {
+                self.budget.out_of_memory = true;
+                return Err(a.message);
             }
         }
     }
 
-    fn execute_block(&mut self, statements: Vec<Stmt>, environment: Environment) {
-        self.environment = environment;
+    // Bytes taken by the given values, 0 without a memory limit as nobody is counting
+    fn size_of(&self, values: &[Literal]) -> usize {
+        if self.budget.max_memory.is_none() {
+            return 0;
+        }
 
-        for statement in statements {
-            self.execute(statement);
+        let mut meter: Meter = Meter::default();
+        for value in values {
+            meter.visit(value);
         }
+        return meter.finish();
+    }
 
-        self.environment = *self.environment.clone().enclosing.unwrap();
+    // Bytes taken by everything the script can still reach: the globals, the scopes of the calls in
+    // progress, the virtual machine's stack and whatever their functions closed over
+    fn live_bytes(&self) -> usize {
+        let mut meter: Meter = Meter::default();
+        for value in self.globals.values() {
+            meter.visit(value);
+        }
+        for environment in self.suspended.iter().chain(std::iter::once(&self.environment)).flatten() {
+            meter.environment(environment);
+        }
+        for value in self.vm.values() {
+            meter.visit(value);
+        }
+        return meter.finish();
     }
-}
 
-impl StmtVisitor<> for Interpreter {
-    fn visit_expression(&mut self, expression: Expr) {
-        self.visit(expression);
+    // Have the garbage collector keep an eye on a value that might be part of a cycle
+    pub fn track(&mut self, value: &Literal) {
+        self.heap.track(value);
     }
 
-    fn visit_print(&mut self, expression: Expr) {
-        let value: Option<Literal> = self.visit(expression);
-        match value {
-            Some(a) => {
-                println!("{}", Self::stringify(a))
+    // Set how many lists, maps and functions are made between garbage collections, at the least
+    pub fn set_gc_threshold(&mut self, threshold: usize) {
+        self.heap.set_threshold(threshold);
+    }
+
+    // Collect garbage every time a list, map or function is made, to find any the collector misses
+    pub fn set_gc_stress(&mut self, stress: bool) {
+        self.heap.set_stress(stress);
+    }
+
+    // Collect garbage now, giving back how many objects were freed
+    pub fn collect_garbage(&mut self) -> usize {
+        return self.heap.collect();
+    }
+
+    // How many lists, maps, functions and scopes were still alive after the last garbage collection
+    pub fn live_objects(&self) -> usize {
+        return self.heap.live_objects();
+    }
+
+    // Run a native, charging what it was given and what it gave back against the memory limit, as
+    // it may have kept hold of the former (eg. list.push) and just made the latter
+    pub fn call_native<F>(&mut self, arguments: Vec<Literal>, native: F) -> Result<Literal, RuntimeError>
+    where F: FnOnce(&mut Interpreter, Vec<Literal>) -> Result<Literal, RuntimeError> {
+        let given: usize = self.size_of(&arguments);
+        let value: Literal = match native(self, arguments) {
+            Ok(a) => a,
+            Err(_) if self.budget.out_of_memory => {
+                self.budget.out_of_memory = false;
+                return Err(RuntimeError::aborted("Out of memory.".to_string()));
             }
-            None => {
-                return
+            Err(a) => return Err(a)
+        };
+        let made: usize = self.size_of(std::slice::from_ref(&value));
+        self.allocate(given + made)?;
+        return Ok(value);
+    }
+
+    // Why the script has to stop now, if it does
+    fn exhausted(&self) -> Option<String> {
+        if self.budget.cancelled.load(Ordering::Relaxed) {
+            return Some("Execution cancelled.".to_string());
+        }
+        if let Some(a) = self.budget.max_steps {
+            if self.budget.steps > a {
+                return Some(format!("Execution step limit of {} exceeded.", a));
             }
         }
+        if let Some(a) = self.budget.deadline {
+            if Instant::now() >= a {
+                return Some("Execution timed out.".to_string());
+            }
+        }
+
+        return None;
     }
 
-    fn visit_var(&mut self, name: Token, right: Option<Expr>) {
-        let value: Option<Literal>;
-        match right {
-            Some(a) => {
-                value = self.visit(a);
-                match value {
-                    Some(b) => self.environment.define(name.lexeme, b),
-                    _ => {}
+    // Send print output to a writer instead of stdout
+    pub fn set_output<W: Write + 'static>(&mut self, output: W) {
+        self.output = Box::new(output);
+    }
+
+    // Send diagnostics and eprint output to a writer instead of stderr
+    pub fn set_error_output<W: Write + 'static>(&mut self, error_output: W) {
+        self.error_output = Box::new(error_output);
+    }
+
+    // Hand print output to a callback instead of stdout
+    pub fn set_output_callback<F: FnMut(&str) + 'static>(&mut self, callback: F) {
+        self.set_output(CallbackWriter::new(callback));
+    }
+
+    // Hand diagnostics and eprint output to a callback instead of stderr
+    pub fn set_error_output_callback<F: FnMut(&str) + 'static>(&mut self, callback: F) {
+        self.set_error_output(CallbackWriter::new(callback));
+    }
+
+    // Write text to the error output as is
+    pub fn write_error(&mut self, text: &str) -> std::io::Result<()> {
+        self.error_output.write_all(text.as_bytes())?;
+        return self.error_output.flush();
+    }
+
+    // Write diagnostics to the error output, one after another
+    pub fn report(&mut self, diagnostics: &[Diagnostic]) {
+        for diagnostic in diagnostics.iter().filter(|a| a.exit_status().is_none()) {
+            let _ = self.write_error(&format!("{}\n", diagnostic));
+        }
+    }
+
+    // Flush both outputs, anything written so far reaches its destination
+    pub fn flush(&mut self) {
+        let _ = self.output.flush();
+        let _ = self.error_output.flush();
+    }
+
+    // Register a Rust closure as a global function scripts can call. Errors it returns are raised
+    // at the call, where a script can catch them like any other.
+    pub fn register_fn<F>(&mut self, name: &str, arity: usize, function: F)
+    where F: Fn(&mut Interpreter, &[Value]) -> Result<Value, RuntimeError> + 'static {
+        let host: HostFunction = HostFunction {name: name.to_string(), arity: arity, function: Rc::new(function)};
+        self.define_global(name, Literal::Callable(Rc::new(host)));
+    }
+
+    // Call a function value from Rust, such as a callback a script handed to a registered closure.
+    // Errors are given back rather than left unwinding.
+    pub fn call(&mut self, callee: &Value, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        // There is no call site in the script, so the call is placed wherever the calling native was called from
+        let line: u32 = self.call_stack.last().map(|a| a.line).unwrap_or(0);
+        for argument in &arguments {
+            self.heap.track(argument);
+        }
+        return crate::with_stack(|| self.call_at(callee, arguments, line));
+    }
+
+    // Call a function value on behalf of a call made on "line"
+    fn call_at(&mut self, callee: &Value, arguments: Vec<Value>, line: u32) -> Result<Value, RuntimeError> {
+        let function: Rc<dyn LoxCallable> = match callee {
+            Literal::Callable(a) => a.clone(),
+            _ => return Err(self.place(RuntimeError::native("Can only call functions and classes.".to_string())))
+        };
+
+        self.check_call(&*function, arguments.len())?;
+
+        // Lox functions leave their errors unwinding rather than handing them back
+        self.call_stack.push(CallFrame {function: function.name(), line: line});
+        let result: Result<Literal, RuntimeError> = match function.call(self, arguments) {
+            Ok(a) => match self.unwinding.take() {
+                Some(Unwind::Error(b)) => Err(b),
+                _ => Ok(a)
+            },
+            Err(a) => Err(self.place(a))
+        };
+        self.call_stack.pop();
+
+        return result;
+    }
+
+    // Whether a function can be called with this many arguments, and the script has the budget and
+    // stack left to. Each call is a step.
+    fn check_call(&mut self, function: &dyn LoxCallable, arguments: usize) -> Result<(), RuntimeError> {
+        if arguments != function.arity() {
+            let message: String = format!("Expected {} arguments but got {}.", function.arity(), arguments);
+            return Err(self.place(RuntimeError::native(message)));
+        }
+
+        self.budget.steps += 1;
+        if let Some(a) = self.exhausted() {
+            return Err(self.place(RuntimeError::aborted(a)));
+        }
+
+        // The parser limits how deep a single function can nest, so checking here on each call
+        // is enough to stop the Rust stack from overflowing
+        if self.call_stack.len() >= self.limits.max_call_depth || self.depth >= self.limits.max_nesting_depth {
+            return Err(self.place(RuntimeError::native("Stack overflow.".to_string())));
+        }
+
+        return Ok(());
+    }
+
+    // Raise an error at "token", unless it already knows where it came from
+    fn raise(&mut self, token: &Token, error: RuntimeError) {
+        let mut error: RuntimeError = self.place(error);
+        if error.is_unplaced() {
+            error.token = Box::new(token.clone());
+        }
+        self.unwinding = Some(Unwind::Error(error));
+    }
+
+    // Give an error raised by a native the calls in progress, it gets its line from the call site
+    fn place(&self, mut error: RuntimeError) -> RuntimeError {
+        if error.is_unplaced() && error.trace.is_empty() {
+            error.trace = self.call_stack.clone();
+        }
+
+        return error;
+    }
+
+    // Interpret statements, stopping at the first error that nothing caught
+    fn interpret(&mut self, statements: &[Stmt]) -> Result<Literal, Vec<Diagnostic>> {
+        let mut result: Literal = Literal::Nill;
+        for statement in statements {
+            // Hang on to the value of expression statements, handy at the prompt
+            result = Literal::Nill;
+            match statement {
+                Stmt::Expression {expression} => {
+                    if let Some(a) = self.evaluate(expression) {
+                        result = a;
+                    }
                 }
+                _ => self.execute_statement(statement)
             }
-            None => {
-                self.environment.define(name.lexeme, Literal::Nill)
+
+            if let Some(Unwind::Error(error)) = self.unwinding.take() {
+                return Err(vec![error.to_diagnostic()]);
             }
         }
+
+        return Ok(result);
     }
 
-    fn visit_block(&mut self, statements: Vec<Stmt>) {
-        self.execute_block(statements, Environment::build_environment(self.instance, Box::new(self.environment.clone())));
+    // Run the body of a Lox function, returning what it returned. If an error is raised it is left
+    // unwinding for the caller to notice.
+    pub fn call_function(&mut self, function: &LoxFunction, arguments: Vec<Literal>) -> Literal {
+        let mut environment: Environment = Environment::build_environment(function.closure.borrow().clone());
+        // The parameters are the first slots of the call's scope, as the resolver numbered them
+        for argument in arguments {
+            environment.define(argument);
+        }
+
+        let previous: Option<SharedEnvironment> = self.environment.replace(environment.share());
+        self.suspended.push(previous);
+        for statement in function.body.iter() {
+            self.execute_statement(statement);
+            if self.unwinding.is_some() {
+                break;
+            }
+        }
+        self.environment = self.suspended.pop().unwrap();
+
+        match self.unwinding.take() {
+            Some(Unwind::Return(a)) => return a,
+            other => {
+                self.unwinding = other;
+                return Literal::Nill
+            }
+        }
     }
 
-    fn visit_if(&mut self, condition: Expr, then_branch: Box<Stmt>, else_branch: Option<Box<Stmt>>) {
-        let condition_visited: Option<Literal> = self.visit(condition);
-        if condition_visited.is_some() && Self::is_truthy(condition_visited.unwrap()) {
-            self.execute(*then_branch);
-        } else if else_branch.is_some() {
-            self.execute(*else_branch.unwrap());
+    // Evaluate an expression, keeping track of how deeply nested we are
+    fn evaluate(&mut self, expr: &Expr) -> Option<Literal> {
+        self.depth += 1;
+        let value: Option<Literal> = self.visit(expr);
+        self.depth -= 1;
+        return value;
+    }
+
+    // Execute a statement, keeping track of how deeply nested we are
+    fn execute_statement(&mut self, stmt: &Stmt) {
+        self.depth += 1;
+        self.execute(stmt);
+        self.depth -= 1;
+    }
+
+    // Raise an error, it unwinds until a try statement catches it or it reaches the top level
+    fn error(&mut self, token: &Token, message: String) {
+        self.unwinding = Some(Unwind::Error(RuntimeError::new(token.clone(), message, self.call_stack.clone())));
+    }
+
+    // Define a variable in the innermost scope, in the next slot if that is a local one
+    fn define(&mut self, name: Symbol, value: Literal) {
+        match &self.environment {
+            Some(a) => a.borrow_mut().define(value),
+            None => self.globals.define(name, value)
         }
     }
 
-    fn visit_while(&mut self, condition: Expr, body: Box<Stmt>) {
-        while Self::is_truthy(self.visit(condition.clone()).unwrap()) {
-            self.execute(*body.clone());
+    // Look a variable up in the slot the resolver found for it, or in the globals if it didn't find one
+    fn look_up(&self, name: &Token, slot: Option<Slot>) -> Result<Literal, String> {
+        match (slot, &self.environment) {
+            (Some(a), Some(b)) => return b.borrow().get(a).ok_or_else(|| format!("Undefined variable {}.", name.lexeme)),
+            _ => return self.globals.get(name.lexeme.clone())
         }
     }
-}
-// See ExprVisitor at Expression for implementation requirements
-impl ExprVisitor<Literal> for Interpreter {
-    // Evaluate a binary expression
-    fn visit_binary(&mut self, b_left: Box<Expr>, operator: Token, b_right: Box<Expr>) -> Option<Literal> {
-        let v_left = self.visit(*b_left);
-        let v_right = self.visit(*b_right);
 
-        let left: Literal;
-        let right: Literal;
-        match (v_left, v_right) {
+    // Assign to an existing variable, found the same way as look_up
+    fn assign(&mut self, name: &Token, slot: Option<Slot>, value: Literal) -> Result<(), String> {
+        match (slot, &self.environment) {
             (Some(a), Some(b)) => {
-                left = a;
-                right = b;
-            }
-            _ => {
-                return None
+                if !b.borrow_mut().assign(a, value) {
+                    return Err(format!("Undefined variable {}.", name.lexeme));
+                }
+                return Ok(());
             }
+            _ => return self.globals.assign(name.lexeme.clone(), value)
         }
+    }
+
+    fn binary_error(left: Literal, right: Literal) -> RuntimeError {
+        return RuntimeError::native(format!("{:?} and {:?} must both be numbers", left, right));
+    }
 
-        match operator.token_type {
+    // Apply a binary operator to its operands, an error is left for the caller to place at the operator
+    fn binary_operation(&mut self, operator: &TokenType, left: Literal, right: Literal) -> Result<Literal, RuntimeError> {
+        match operator {
             // Subtraction
             TokenType::Minus => {
-                if let (Literal::Num(a), Literal::Num(b)) = (left.clone(), right.clone()) {
-                    return Some(Literal::Num(a-b))
-                } else {
-                    self.binary_error(left, operator, right);
-                    return None
+                if let (Literal::Num(a), Literal::Num(b)) = (&left, &right) {
+                    return Ok(Literal::Num(a-b))
                 }
             },
 
             // Addition
             TokenType::Plus => {
-                match (left.clone(), right.clone()) {
+                // Joining strings is how a script runs out of memory, so check before building the result
+                let joined: usize = match (&left, &right) {
+                    (Literal::Str(a), Literal::Str(b)) => a.len() + b.len(),
+                    (Literal::Str(a), Literal::Num(_)) | (Literal::Num(_), Literal::Str(a)) => a.len(),
+                    _ => 0
+                };
+                self.allocate(joined)?;
+
+                match (left, right) {
                     // String Concatenation (with and without numbers)
-                    (Literal::Num(a), Literal::Str(b)) => {
-                        return Some(Literal::Str(format!("{}{}", a, b)))
-                    },
-                    (Literal::Str(a), Literal::Num(b)) => {
-                        return Some(Literal::Str(format!("{}{}", a, b)))
-                    },
-                    (Literal::Str(a), Literal::Str(b)) => {
-                        return Some(Literal::Str(format!("{}{}", a, b)))
-                    },
+                    (Literal::Num(a), Literal::Str(b)) => return Ok(Literal::Str(format!("{}{}", a, b).into())),
+                    (Literal::Str(a), Literal::Num(b)) => return Ok(Literal::Str(format!("{}{}", a, b).into())),
+                    (Literal::Str(a), Literal::Str(b)) => return Ok(Literal::Str(format!("{}{}", a, b).into())),
                     // Actual Addition
-                    (Literal::Num(a), Literal::Num(b)) => {
-                        return Some(Literal::Num(a+b))
-                    },
-                    (a, b) => {
-                        self.error(operator, format!("{} and {} must be either a String or a Num", a, b));
-                        return None
-                    }
+                    (Literal::Num(a), Literal::Num(b)) => return Ok(Literal::Num(a+b)),
+                    (a, b) => return Err(RuntimeError::native(format!("{} and {} must be either a String or a Num", a, b)))
                 }
             },
 
             // Division
             TokenType::Slash => {
-                if let (Literal::Num(a), Literal::Num(b)) = (left.clone(), right.clone()) {
+                if let (Literal::Num(a), Literal::Num(b)) = (&left, &right) {
                     // Check if we are dividing by 0
-                    if b == 0.0 {
-                        self.error(operator, "Can't divide by 0".to_string());
-                        return None
+                    if *b == 0.0 {
+                        return Err(RuntimeError::native("Can't divide by 0".to_string()))
                     }
-                    return Some(Literal::Num(a/b))
-                } else {
-                    self.binary_error(left, operator, right);
-                    return None
+                    return Ok(Literal::Num(a/b))
                 }
             },
 
             // Multiplication
             TokenType::Star => {
-                if let (Literal::Num(a), Literal::Num(b)) = (left.clone(), right.clone()) {
-                    return Some(Literal::Num(a*b))
-                } else {
-                    self.binary_error(left, operator, right);
-                    return None
+                if let (Literal::Num(a), Literal::Num(b)) = (&left, &right) {
+                    return Ok(Literal::Num(a*b))
                 }
             },
 
-            // Check if a is greater than b
+            // Comparisons
             TokenType::Greater => {
-                if let (Literal::Num(a), Literal::Num(b)) = (left.clone(), right.clone()) {
-                    if a > b {
-                        return Some(Literal::True)
-                    } else {
-                        return Some(Literal::False)
-                    }
-                } else {
-                    self.binary_error(left, operator, right);
-                    return None
+                if let (Literal::Num(a), Literal::Num(b)) = (&left, &right) {
+                    return Ok(Self::from_bool(a > b))
                 }
             },
-
-            // Check if a is greater than or equal to b
             TokenType::GreaterEqual => {
-                if let (Literal::Num(a), Literal::Num(b)) = (left.clone(), right.clone()) {
-                    if a >= b {
-                        return Some(Literal::True)
-                    } else {
-                        return Some(Literal::False)
-                    }
-                } else {
-                    self.binary_error(left, operator, right);
-                    return None
+                if let (Literal::Num(a), Literal::Num(b)) = (&left, &right) {
+                    return Ok(Self::from_bool(a >= b))
                 }
             },
-
-            // Check if a is less than b
             TokenType::Less => {
-                if let (Literal::Num(a), Literal::Num(b)) = (left.clone(), right.clone()) {
-                    if a < b {
-                        return Some(Literal::True)
-                    } else {
-                        return Some(Literal::False)
-                    }
-                } else {
-                    self.binary_error(left, operator, right);
-                    return None
+                if let (Literal::Num(a), Literal::Num(b)) = (&left, &right) {
+                    return Ok(Self::from_bool(a < b))
                 }
             },
-
-            // Check if a is less than or equal to b
             TokenType::LessEqual => {
-                if let (Literal::Num(a), Literal::Num(b)) = (left.clone(), right.clone()) {
-                    if a <= b {
-                        return Some(Literal::True)
-                    } else {
-                        return Some(Literal::False)
-                    }
-                } else {
-                    self.binary_error(left, operator, right);
-                    return None
+                if let (Literal::Num(a), Literal::Num(b)) = (&left, &right) {
+                    return Ok(Self::from_bool(a <= b))
                 }
             },
 
             // Check if a is not equal to b
             TokenType::BangEqual => {
-                if let (Literal::Num(_), Literal::Num(_)) = (left.clone(), right.clone()) {
-                    match !Self::is_equal(left, right) {
-                        true => return Some(Literal::True),
-                        false => return Some(Literal::False)
-                    }
-                } else {
-                    self.binary_error(left, operator, right);
-                    return None
+                if let (Literal::Num(_), Literal::Num(_)) = (&left, &right) {
+                    return Ok(Self::from_bool(!Self::is_equal(left, right)))
                 }
             },
 
             // Check if a is equal to b
             TokenType::EqualEqual => {
-                if let (Literal::Num(_), Literal::Num(_)) = (left.clone(), right.clone()) {
-                    match Self::is_equal(left, right) {
-                        true => return Some(Literal::True),
-                        false => return Some(Literal::False)
-                    }
-                } else {
-                    self.binary_error(left, operator, right);
-                    return None
+                if let (Literal::Num(_), Literal::Num(_)) = (&left, &right) {
+                    return Ok(Self::from_bool(Self::is_equal(left, right)))
                 }
             },
-            _ => return Some(Literal::Nill)
+            _ => return Ok(Literal::Nill)
         }
+
+        return Err(Self::binary_error(left, right));
     }
 
-    // Evaluate a grouping expression
-    fn visit_grouping(&mut self, expression: Box<Expr>) -> Option<Literal> {
-        return self.visit(*expression)
+    // Apply a unary operator to its operand
+    fn unary_operation(operator: &TokenType, right: Literal) -> Result<Literal, RuntimeError> {
+        match operator {
+            // If the right value is a negative, take the negative of the number
+            TokenType::Minus => {
+                match right {
+                    Literal::Num(n) => return Ok(Literal::Num(-n)),
+                    _ => return Err(RuntimeError::native(format!("{:?} Must be a number...", right)))
+                }
+            }
+
+            // If the right value is a bang, take the opposite of the boolean value
+            TokenType::Bang => return Ok(Self::from_bool(!Self::is_truthy(&right))),
+            // Else just return nill
+            _ => return Ok(Literal::Nill)
+        }
     }
 
-    // Evaluate a literal expression
-    fn visit_literal(&mut self, value: Literal) -> Option<Literal> {
-        return Some(value)
+    // Look up a property, only strings, lists, maps and errors have properties for now
+    fn get_property(&mut self, object: Literal, name: Symbol) -> Result<Literal, String> {
+        match object {
+            Literal::Str(_) => return string::get_property(self, object, &name.text()),
+            Literal::List(_) => return list::get_property(self, object, &name.text()),
+            Literal::Map(_) => return map::get_property(self, object, &name.text()),
+            Literal::Error(a) => return RuntimeError::get_property(a, &name.text()),
+            _ => return Err(format!("Only strings, lists, maps and errors have properties, got {}.", object))
+        }
     }
 
-    // Evaluate a unary expression
-    fn visit_unary(&mut self, operator: Token, b_right: Box<Expr>) -> Option<Literal> {
-        let v_right = self.visit(*b_right);
+    // Write a value out on a line of its own
+    fn print(&mut self, value: Literal) {
+        // Nothing can be done about output that can't be written, so carry on regardless
+        let line: String = format!("{}\n", Self::stringify(value));
+        let _ = self.output.write_all(line.as_bytes());
+    }
 
-        let right: Literal;
-        match v_right {
-            Some(a) => {
-                right = a;
+    // Convert a Rust bool into a Lox boolean
+    pub fn from_bool(value: bool) -> Literal {
+        match value {
+            true => return Literal::True,
+            false => return Literal::False
+        }
+    }
+
+    // Return trues (Nill and False are false, anything else true)
+    pub fn is_truthy(literal: &Literal) -> bool {
+        match literal {
+            Literal::Nill => return false,
+            Literal::False => return false,
+            Literal::True => return true,
+            _ => return true
+        }
+    }
+    
+    // Check equality
+    fn is_equal(left: Literal, right: Literal) -> bool {
+        match (left, right) {
+            (Literal::Nill, Literal::Nill) => true,
+            (Literal::True, Literal::True) => true,
+            (Literal::False, Literal::False) => true,
+            (Literal::Str(a), Literal::Str(b)) => a == b,
+            (Literal::Num(a), Literal::Num(b)) => a == b,
+            _ => false
+        }
+    }
+
+    // Convert literal to proper strings for display
+    pub fn stringify(literal: Literal) -> String {
+        return Self::stringify_within(literal, &mut Vec::new());
+    }
+
+    // Stringify a value inside the lists and maps in "seen", which a list or map already among them
+    // stands in for as [...] or {...} rather than printing itself forever
+    fn stringify_within(literal: Literal, seen: &mut Vec<*const ()>) -> String {
+        match literal {
+            Literal::Nill => return String::from("nill"),
+            Literal::Str(a) => return a.to_string(),
+            Literal::Error(a) => return a.message.clone(),
+            Literal::List(a) => {
+                let pointer: *const () = Rc::as_ptr(&a) as *const ();
+                if seen.contains(&pointer) {
+                    return String::from("[...]")
+                }
+                seen.push(pointer);
+                let items: Vec<String> = a.borrow().iter().map(|b| Self::stringify_item(b.clone(), seen)).collect();
+                seen.pop();
+                return format!("[{}]", items.join(", "))
+            }
+            Literal::Map(a) => {
+                let pointer: *const () = Rc::as_ptr(&a) as *const ();
+                if seen.contains(&pointer) {
+                    return String::from("{...}")
+                }
+                seen.push(pointer);
+                let map = a.borrow();
+                let mut keys: Vec<&String> = map.keys().collect();
+                keys.sort();
+                let items: Vec<String> = keys.iter().map(|b| format!("{:?}: {}", b, Self::stringify_item(map[*b].clone(), seen))).collect();
+                seen.pop();
+                return format!("{{{}}}", items.join(", "))
+            }
+            Literal::Num(a) => {
+                let a = a.to_string();
+                if a.ends_with(".0") {
+                    return a[..a.len() - 2].to_string()
+                } else {
+                    return a
+                }
             }
             _ => {
-                return None
+                return literal.to_string()
             }
         }
-        match operator.token_type {
-            // If the right value is a negative, take the negative of the number
-            TokenType::Minus => {
-                match right {
-                    Literal::Num(n) => return Some(Literal::Num(-n)),
-                    _ => {
-                        let message = format!("{:?} Must be a number...", right);
-                        self.error(operator, message);
-                        return None
+    }
+
+    // Stringify a value held in a list or map, strings keep their quotes so ["a, b"] and ["a", "b"] differ
+    fn stringify_item(literal: Literal, seen: &mut Vec<*const ()>) -> String {
+        match literal {
+            Literal::Str(_) => return literal.to_string(),
+            _ => return Self::stringify_within(literal, seen)
+        }
+    }
+
+    // Execute statements in the given environment, bailing out early if an error is raised
+    fn execute_block(&mut self, statements: &[Stmt], environment: Environment) {
+        let previous: Option<SharedEnvironment> = self.environment.replace(environment.share());
+
+        for statement in statements {
+            self.execute_statement(statement);
+            if self.unwinding.is_some() {
+                break;
+            }
+        }
+
+        self.environment = previous;
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::build_interpreter(Limits::default(), Capabilities::default(), &[])
+    }
+}
+
+impl StmtVisitor<> for Interpreter {
+    fn visit_expression(&mut self, expression: &Expr) {
+        self.evaluate(expression);
+    }
+
+    fn visit_print(&mut self, expression: &Expr) {
+        if let Some(a) = self.evaluate(expression) {
+            self.print(a);
+        }
+    }
+
+    fn visit_var(&mut self, name: &Token, right: Option<&Expr>) {
+        let value: Option<Literal>;
+        match right {
+            Some(a) => {
+                value = self.evaluate(a);
+                if let Some(b) = value {
+                    self.define(name.lexeme.clone(), b);
+                }
+            }
+            None => {
+                self.define(name.lexeme.clone(), Literal::Nill)
+            }
+        }
+    }
+
+    fn visit_block(&mut self, statements: &[Stmt]) {
+        self.execute_block(statements, Environment::build_environment(self.environment.clone()));
+    }
+
+    fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>) {
+        let condition_visited: Literal = match self.evaluate(condition) {
+            Some(a) => a,
+            None => return
+        };
+        if Self::is_truthy(&condition_visited) {
+            self.execute_statement(then_branch);
+        } else if let Some(a) = else_branch {
+            self.execute_statement(a);
+        }
+    }
+
+    fn visit_while(&mut self, keyword: &Token, condition: &Expr, body: &Stmt) {
+        loop {
+            let condition_visited: Literal = match self.evaluate(condition) {
+                Some(a) => a,
+                None => return
+            };
+            if !Self::is_truthy(&condition_visited) {
+                return
+            }
+
+            self.execute_statement(body);
+            if self.unwinding.is_some() {
+                return
+            }
+
+            // Going round again is a step, as the virtual machine's Loop instruction is
+            self.budget.steps += 1;
+            if let Some(a) = self.exhausted() {
+                self.raise(keyword, RuntimeError::aborted(a));
+                return
+            }
+        }
+    }
+
+    fn visit_try(&mut self, try_branch: &[Stmt], name: Option<&Token>, catch_branch: Option<&[Stmt]>, finally_branch: Option<&[Stmt]>) {
+        self.execute_block(try_branch, Environment::build_environment(self.environment.clone()));
+
+        // Hand the error over to the catch block as a value, a return or an abort passes straight through
+        if let Some(statements) = catch_branch {
+            match self.unwinding.take() {
+                Some(Unwind::Error(error)) if !error.aborted => {
+                    let mut environment: Environment = Environment::build_environment(self.environment.clone());
+                    if name.is_some() {
+                        let value: Literal = Literal::Error(Rc::new(error));
+                        self.heap.track(&value);
+                        environment.define(value);
                     }
+                    self.execute_block(statements, environment);
                 }
+                other => self.unwinding = other
             }
+        }
 
-            // If the right value is a bang, take the opposite of the boolean value
-            TokenType::Bang => {
-                match !Self::is_truthy(right) {
-                    true => return Some(Literal::True),
-                    false => return Some(Literal::False)
+        // The finally block runs unless the script is being aborted, afterwards anything still unwinding
+        // carries on unless it raised its own error
+        if let Some(statements) = finally_branch {
+            match self.unwinding.take() {
+                Some(Unwind::Error(error)) if error.aborted => self.unwinding = Some(Unwind::Error(error)),
+                pending => {
+                    self.execute_block(statements, Environment::build_environment(self.environment.clone()));
+                    if self.unwinding.is_none() {
+                        self.unwinding = pending;
+                    }
                 }
             }
-            // Else just return nill
-            _ => {
-                return Some(Literal::Nill)
+        }
+    }
+
+    fn visit_throw(&mut self, keyword: &Token, value: &Expr) {
+        let value: Literal = match self.evaluate(value) {
+            Some(a) => a,
+            None => return
+        };
+
+        // Throwing a caught error rethrows it as it was
+        match value {
+            Literal::Error(a) => self.unwinding = Some(Unwind::Error((*a).clone())),
+            _ => self.unwinding = Some(Unwind::Error(RuntimeError::thrown(keyword.clone(), value, self.call_stack.clone())))
+        }
+    }
+
+    fn visit_function(&mut self, name: &Token, params: &[Token], body: &Rc<Vec<Stmt>>) {
+        let function: LoxFunction = LoxFunction {
+            name: name.clone(),
+            params: params.to_vec(),
+            body: body.clone(),
+            closure: RefCell::new(self.environment.clone())
+        };
+        let function: Literal = Literal::Callable(Rc::new(function));
+        self.heap.track(&function);
+        if let Some(a) = &self.environment {
+            self.heap.track_environment(a);
+        }
+        self.define(name.lexeme.clone(), function);
+    }
+
+    fn visit_return(&mut self, _keyword: &Token, value: Option<&Expr>) {
+        let mut returned: Literal = Literal::Nill;
+        if let Some(a) = value {
+            match self.evaluate(a) {
+                Some(b) => returned = b,
+                None => return
+            }
+        }
+
+        self.unwinding = Some(Unwind::Return(returned));
+    }
+}
+// See ExprVisitor at Expression for implementation requirements
+impl ExprVisitor<Literal> for Interpreter {
+    // Evaluate a binary expression
+    fn visit_binary(&mut self, b_left: &Expr, operator: &Token, b_right: &Expr) -> Option<Literal> {
+        let left: Literal = self.evaluate(b_left)?;
+        let right: Literal = self.evaluate(b_right)?;
+
+        match self.binary_operation(&operator.token_type, left, right) {
+            Ok(a) => return Some(a),
+            Err(a) => {
+                self.raise(operator, a);
+                return None
+            }
+        }
+    }
+
+    // Evaluate a grouping expression
+    fn visit_grouping(&mut self, expression: &Expr) -> Option<Literal> {
+        return self.evaluate(expression)
+    }
+
+    // Evaluate a literal expression
+    fn visit_literal(&mut self, value: &Literal) -> Option<Literal> {
+        return Some(value.clone())
+    }
+
+    // Evaluate a unary expression
+    fn visit_unary(&mut self, operator: &Token, b_right: &Expr) -> Option<Literal> {
+        let right: Literal = self.evaluate(b_right)?;
+
+        match Self::unary_operation(&operator.token_type, right) {
+            Ok(a) => return Some(a),
+            Err(a) => {
+                self.raise(operator, a);
+                return None
             }
         }
     }
 
-    fn visit_variable(&mut self, token: Token) -> Option<Literal> {
-        match self.environment.get(token.clone()) {
+    fn visit_variable(&mut self, token: &Token, slot: Option<Slot>) -> Option<Literal> {
+        match self.look_up(token, slot) {
             Ok(a) => return Some(a),
             Err(a) => {
                 self.error(token, a);
@@ -361,30 +1142,64 @@ impl ExprVisitor<Literal> for Interpreter {
         }
     }
 
-    fn visit_assignment(&mut self, name: Token, value: Box<Expr>) -> Option<Literal> {
-        let literal: Option<Literal> = self.visit(*value);
-        self.environment.assign(name, literal.clone().unwrap());
-        return literal
+    fn visit_assignment(&mut self, name: &Token, value: &Expr, slot: Option<Slot>) -> Option<Literal> {
+        let literal: Literal = self.evaluate(value)?;
+        match self.assign(name, slot, literal.clone()) {
+            Ok(_) => return Some(literal),
+            Err(a) => {
+                self.error(name, a);
+                return None
+            }
+        }
     }
 
-    fn visit_logical(&mut self, left: Box<Expr>, operator: Token, right: Box<Expr>) -> Option<Literal> {
-        let left: Option<Literal> = self.visit(*left);
+    fn visit_logical(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Option<Literal> {
+        let left: Literal = self.evaluate(left)?;
 
         if matches!(operator.token_type, TokenType::Or) {
-            if left.is_some() && Self::is_truthy(left.clone().unwrap()) {
-                return left;
+            if Self::is_truthy(&left) {
+                return Some(left);
             }
         } else {
-            if left.is_some() && !Self::is_truthy(left.clone().unwrap()) {
-                return left;
+            if !Self::is_truthy(&left) {
+                return Some(left);
             }
         }
 
-        let right: Option<Literal> = self.visit(*right);
-        if right.is_some() {
-            return right
-        } else {
-            return None;
+        return self.evaluate(right);
+    }
+
+    fn visit_call(&mut self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> Option<Literal> {
+        let callee: Literal = self.evaluate(callee)?;
+
+        let mut evaluated: Vec<Literal> = Vec::new();
+        for argument in arguments {
+            match self.evaluate(argument) {
+                Some(a) => evaluated.push(a),
+                None => return None
+            }
+        }
+
+        match self.call_at(&callee, evaluated, paren.line) {
+            Ok(a) => return Some(a),
+            Err(a) => {
+                self.raise(paren, a);
+                return None
+            }
+        }
+    }
+
+    // Evaluate a property access
+    fn visit_get(&mut self, object: &Expr, name: &Token) -> Option<Literal> {
+        let object: Literal = self.evaluate(object)?;
+
+        let property: Result<Literal, String> = self.get_property(object, name.lexeme.clone());
+        match property {
+            Ok(a) => return Some(a),
+            Err(a) => {
+                self.error(name, a);
+                return None
+            }
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,572 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::{Diagnostic, Value};
+use crate::callable::{Closure, LoxCallable, SharedUpvalue, Upvalue};
+use crate::chunk::{Chunk, Constant, OpCode, Prototype};
+use crate::error::{CallFrame, RuntimeError};
+use crate::intern::Symbol;
+use crate::token::{Literal, Token, TokenType};
+use super::Interpreter;
+
+/*
+Virtual machine
+
+Runs the bytecode the compiler makes. Every call gets a frame, a window onto the one stack starting
+at the function being called, followed by its arguments and then its other locals. Calls from one
+compiled function to another just push a frame, anything else (natives, host functions) is called
+the same way the tree-walker calls it, and a native calling back into a compiled function runs a
+fresh loop on top of the frames already there.
+
+Try statements push handlers, which say where to go and how much of the stack and frames to keep
+when an error is raised, or for a finally, when a return is made. What a finally block interrupted is
+kept as a pending completion, which EndFinally picks back up once the block is done.
+
+Errors have the same messages, lines and traces as they do on the tree-walker. The tokens are gone
+by now, so an error is reported at the operator, name or keyword its instruction was compiled from.
+*/
+
+// A call in progress
+struct Frame {
+    function: Rc<Prototype>,
+    upvalues: Rc<[SharedUpvalue]>,
+    // The next instruction to run, kept up to date whenever the frame stops running
+    ip: usize,
+    // Where the frame starts on the stack, its first slot holds the function called
+    base: usize,
+    // How many calls were in progress, and completions pending, once the call was made
+    calls: usize,
+    pending: usize
+}
+
+// What a finally block has interrupted, to carry on with once it is done
+enum Completion {
+    Normal,
+    Error(RuntimeError),
+    Return(Literal)
+}
+
+// Set up by a try statement, for its catch or finally block
+struct Handler {
+    finally: bool,
+    // Where the block starts
+    target: usize,
+    // What to cut the frames, stack, calls and pending completions back to before running the block
+    frame: usize,
+    stack: usize,
+    calls: usize,
+    pending: usize
+}
+
+#[derive(Default)]
+pub struct Vm {
+    stack: Vec<Literal>,
+    frames: Vec<Frame>,
+    handlers: Vec<Handler>,
+    // Upvalues still pointing at the stack, ordered by the slot they point at
+    open_upvalues: Vec<SharedUpvalue>,
+    pending: Vec<Completion>
+}
+
+impl Vm {
+    // Every value the machine is holding on to
+    pub fn values(&self) -> impl Iterator<Item = &Literal> {
+        let returning = self.pending.iter().filter_map(|a| match a {
+            Completion::Return(b) => Some(b),
+            _ => None
+        });
+        return self.stack.iter().chain(returning);
+    }
+}
+
+impl Interpreter {
+    // Run the top level of a compiled script, stopping at the first error that nothing caught
+    pub fn run_script(&mut self, script: Rc<Prototype>) -> Result<Value, Vec<Diagnostic>> {
+        let script: Closure = Closure {function: script, upvalues: Vec::new().into()};
+        match self.call_closure(&script, Vec::new()) {
+            Ok(a) => return Ok(a),
+            Err(a) => return Err(vec![a.to_diagnostic()])
+        }
+    }
+
+    // Run a compiled function on top of whatever is running already, once the call has been checked
+    pub fn call_closure(&mut self, closure: &Closure, arguments: Vec<Literal>) -> Result<Literal, RuntimeError> {
+        let base: usize = self.vm.stack.len();
+        self.vm.stack.push(Literal::Nill);
+        self.vm.stack.extend(arguments);
+        self.vm.frames.push(Frame {
+            function: closure.function.clone(),
+            upvalues: closure.upvalues.clone(),
+            ip: 0,
+            base: base,
+            calls: self.call_stack.len(),
+            pending: self.vm.pending.len()
+        });
+
+        let entry: usize = self.vm.frames.len() - 1;
+        return self.execute_frames(entry);
+    }
+
+    // Run frames until the one at "entry" returns, or an error nothing caught leaves it
+    fn execute_frames(&mut self, entry: usize) -> Result<Literal, RuntimeError> {
+        'frames: loop {
+            let frame: &Frame = self.vm.frames.last().expect("No frame running");
+            let function: Rc<Prototype> = frame.function.clone();
+            let upvalues: Rc<[SharedUpvalue]> = frame.upvalues.clone();
+            let base: usize = frame.base;
+            let mut ip: usize = frame.ip;
+            let chunk: &Chunk = &function.chunk;
+
+            let error: RuntimeError = loop {
+                let start: usize = ip;
+                let op: OpCode = match chunk.code.get(start).map(|a| OpCode::from_byte(*a)) {
+                    Some(Some(a)) => a,
+                    Some(None) => break self.place(RuntimeError::native(format!("Unknown opcode {}.", chunk.code[start]))),
+                    None => break self.corrupted(&function, start, "is past the end of the code")
+                };
+                ip += 1 + op.operand_size();
+                if ip > chunk.code.len() {
+                    break self.corrupted(&function, start, "is cut short");
+                }
+
+                // Code loaded from a file is verified, but a chunk built some other way may still
+                // ask for more than is there, which is an error rather than a crash
+                let operand: usize = match op.operand_size() {
+                    0 => 0,
+                    _ => chunk.code[start + 1] as usize
+                };
+                let (taken, _) = op.stack_effect(operand);
+                if self.vm.stack.len() < base + 1 + taken {
+                    break self.corrupted(&function, start, "takes more values than the stack holds");
+                }
+                if self.trace_execution {
+                    self.trace_instruction(chunk, start);
+                }
+
+                match op {
+                    OpCode::Constant => {
+                        let value: Literal = match chunk.constants.get(chunk.read_u16(start + 1) as usize) {
+                            Some(Constant::Num(a)) => Literal::Num(*a),
+                            Some(Constant::Str(a)) => Literal::Str(a.clone()),
+                            _ => break self.corrupted(&function, start, "refers to a missing or wrong constant")
+                        };
+                        self.vm.stack.push(value);
+                    }
+                    OpCode::Nil => self.vm.stack.push(Literal::Nill),
+                    OpCode::True => self.vm.stack.push(Literal::True),
+                    OpCode::False => self.vm.stack.push(Literal::False),
+                    OpCode::Pop => {
+                        self.pop();
+                    }
+                    OpCode::GetLocal => {
+                        let value: Literal = match self.vm.stack.get(base + operand) {
+                            Some(a) => a.clone(),
+                            None => break self.corrupted(&function, start, "uses a missing local slot")
+                        };
+                        self.vm.stack.push(value);
+                    }
+                    OpCode::SetLocal => {
+                        let value: Literal = self.peek().clone();
+                        match self.vm.stack.get_mut(base + operand) {
+                            Some(a) => *a = value,
+                            None => break self.corrupted(&function, start, "uses a missing local slot")
+                        }
+                    }
+                    OpCode::GetGlobal => {
+                        let name: Symbol = Self::name(chunk, start + 1);
+                        match self.globals.get(name.clone()) {
+                            Ok(a) => self.vm.stack.push(a),
+                            Err(a) => break self.raise_at(RuntimeError::native(a), TokenType::Id, name, chunk.line(start))
+                        }
+                    }
+                    OpCode::DefineGlobal => {
+                        let value: Literal = self.pop();
+                        self.globals.define(Self::name(chunk, start + 1), value);
+                    }
+                    OpCode::SetGlobal => {
+                        let name: Symbol = Self::name(chunk, start + 1);
+                        let value: Literal = self.peek().clone();
+                        if let Err(a) = self.globals.assign(name.clone(), value) {
+                            break self.raise_at(RuntimeError::native(a), TokenType::Id, name, chunk.line(start));
+                        }
+                    }
+                    OpCode::GetUpvalue => {
+                        let upvalue: &SharedUpvalue = match upvalues.get(operand) {
+                            Some(a) => a,
+                            None => break self.corrupted(&function, start, "uses a missing upvalue")
+                        };
+                        let value: Literal = match &*upvalue.borrow() {
+                            Upvalue::Open(a) => self.vm.stack[*a].clone(),
+                            Upvalue::Closed(a) => a.clone()
+                        };
+                        self.vm.stack.push(value);
+                    }
+                    OpCode::SetUpvalue => {
+                        let value: Literal = self.peek().clone();
+                        let upvalue: &SharedUpvalue = match upvalues.get(operand) {
+                            Some(a) => a,
+                            None => break self.corrupted(&function, start, "uses a missing upvalue")
+                        };
+                        match &mut *upvalue.borrow_mut() {
+                            Upvalue::Open(a) => self.vm.stack[*a] = value,
+                            Upvalue::Closed(a) => *a = value
+                        }
+                    }
+                    OpCode::GetProperty => {
+                        let name: Symbol = Self::name(chunk, start + 1);
+                        let object: Literal = self.pop();
+                        match self.get_property(object, name.clone()) {
+                            Ok(a) => self.vm.stack.push(a),
+                            Err(a) => break self.raise_at(RuntimeError::native(a), TokenType::Id, name, chunk.line(start))
+                        }
+                    }
+                    OpCode::Equal | OpCode::NotEqual | OpCode::Greater | OpCode::GreaterEqual | OpCode::Less | OpCode::LessEqual
+                        | OpCode::Add | OpCode::Subtract | OpCode::Multiply | OpCode::Divide => {
+                        let right: Literal = self.pop();
+                        let left: Literal = self.pop();
+                        // Numbers are by far the most common, so they skip the general case
+                        let value: Literal = match (op, &left, &right) {
+                            (OpCode::Equal, Literal::Num(a), Literal::Num(b)) => Self::from_bool(a == b),
+                            (OpCode::NotEqual, Literal::Num(a), Literal::Num(b)) => Self::from_bool(a != b),
+                            (OpCode::Add, Literal::Num(a), Literal::Num(b)) => Literal::Num(a + b),
+                            (OpCode::Subtract, Literal::Num(a), Literal::Num(b)) => Literal::Num(a - b),
+                            (OpCode::Multiply, Literal::Num(a), Literal::Num(b)) => Literal::Num(a * b),
+                            (OpCode::Divide, Literal::Num(a), Literal::Num(b)) if *b != 0.0 => Literal::Num(a / b),
+                            (OpCode::Greater, Literal::Num(a), Literal::Num(b)) => Self::from_bool(a > b),
+                            (OpCode::GreaterEqual, Literal::Num(a), Literal::Num(b)) => Self::from_bool(a >= b),
+                            (OpCode::Less, Literal::Num(a), Literal::Num(b)) => Self::from_bool(a < b),
+                            (OpCode::LessEqual, Literal::Num(a), Literal::Num(b)) => Self::from_bool(a <= b),
+                            _ => {
+                                let (token_type, lexeme) = Self::operator(op);
+                                match self.binary_operation(&token_type, left, right) {
+                                    Ok(a) => a,
+                                    Err(a) => break self.raise_at(a, token_type, Symbol::detached(lexeme), chunk.line(start))
+                                }
+                            }
+                        };
+                        self.vm.stack.push(value);
+                    }
+                    OpCode::Not | OpCode::Negate => {
+                        let right: Literal = self.pop();
+                        let (token_type, lexeme) = Self::operator(op);
+                        match Self::unary_operation(&token_type, right) {
+                            Ok(a) => self.vm.stack.push(a),
+                            Err(a) => break self.raise_at(a, token_type, Symbol::detached(lexeme), chunk.line(start))
+                        }
+                    }
+                    OpCode::Print => {
+                        let value: Literal = self.pop();
+                        self.print(value);
+                    }
+                    OpCode::Jump => ip += chunk.read_u16(start + 1) as usize,
+                    OpCode::JumpIfFalse => {
+                        if !Self::is_truthy(self.peek()) {
+                            ip += chunk.read_u16(start + 1) as usize;
+                        }
+                    }
+                    OpCode::Loop => {
+                        self.budget.steps += 1;
+                        if let Some(a) = self.exhausted() {
+                            let keyword: Symbol = Self::name(chunk, start + 3);
+                            break self.raise_at(RuntimeError::aborted(a), TokenType::While, keyword, chunk.line(start));
+                        }
+                        ip = match ip.checked_sub(chunk.read_u16(start + 1) as usize) {
+                            Some(a) => a,
+                            None => break self.corrupted(&function, start, "jumps before the start of the code")
+                        };
+                    }
+                    OpCode::Call => {
+                        let count: usize = operand;
+                        let callee_index: usize = self.vm.stack.len() - 1 - count;
+                        let line: u32 = chunk.line(start);
+                        let callee: Literal = self.vm.stack[callee_index].clone();
+
+                        // A compiled function runs in this same loop, in a new frame
+                        if let Literal::Callable(a) = &callee {
+                            if let Some(closure) = a.closure() {
+                                if let Err(b) = self.check_call(closure, count) {
+                                    break self.raise_at(b, TokenType::RParen, Symbol::detached(")"), line);
+                                }
+
+                                self.vm.frames.last_mut().expect("No frame running").ip = ip;
+                                self.call_stack.push(CallFrame {function: closure.name(), line: line});
+                                self.vm.frames.push(Frame {
+                                    function: closure.function.clone(),
+                                    upvalues: closure.upvalues.clone(),
+                                    ip: 0,
+                                    base: callee_index,
+                                    calls: self.call_stack.len(),
+                                    pending: self.vm.pending.len()
+                                });
+                                continue 'frames;
+                            }
+                        }
+
+                        let arguments: Vec<Literal> = self.vm.stack.split_off(callee_index + 1);
+                        self.vm.stack.pop();
+                        match self.call_at(&callee, arguments, line) {
+                            Ok(a) => self.vm.stack.push(a),
+                            Err(a) => break self.raise_at(a, TokenType::RParen, Symbol::detached(")"), line)
+                        }
+                    }
+                    OpCode::Closure => {
+                        let prototype: Rc<Prototype> = match chunk.constants.get(chunk.read_u16(start + 1) as usize) {
+                            Some(Constant::Function(a)) => a.clone(),
+                            _ => break self.corrupted(&function, start, "refers to a missing or wrong constant")
+                        };
+
+                        // A local function closes over the slot it is about to be pushed into
+                        let mut captured: Vec<SharedUpvalue> = Vec::with_capacity(prototype.upvalues.len());
+                        for upvalue in prototype.upvalues.iter() {
+                            let index: usize = upvalue.index as usize;
+                            match upvalue.local {
+                                true if base + index <= self.vm.stack.len() => captured.push(self.capture_upvalue(base + index)),
+                                false if index < upvalues.len() => captured.push(upvalues[index].clone()),
+                                _ => break
+                            }
+                        }
+                        if captured.len() < prototype.upvalues.len() {
+                            break self.corrupted(&function, start, "closes over a missing variable");
+                        }
+
+                        let closure: Literal = Literal::Callable(Rc::new(Closure {function: prototype, upvalues: captured.into()}));
+                        self.heap.track(&closure);
+                        self.vm.stack.push(closure);
+                    }
+                    OpCode::CloseUpvalue => {
+                        self.close_upvalues(self.vm.stack.len() - 1);
+                        self.pop();
+                    }
+                    OpCode::Return => {
+                        let value: Literal = self.pop();
+                        self.vm.frames.last_mut().expect("No frame running").ip = ip;
+                        match self.return_from(value, entry) {
+                            Some(a) => return Ok(a),
+                            None => continue 'frames
+                        }
+                    }
+                    OpCode::PushCatch | OpCode::PushFinally => {
+                        let handler: Handler = Handler {
+                            finally: op == OpCode::PushFinally,
+                            target: ip + chunk.read_u16(start + 1) as usize,
+                            frame: self.vm.frames.len() - 1,
+                            stack: self.vm.stack.len(),
+                            calls: self.call_stack.len(),
+                            pending: self.vm.pending.len()
+                        };
+                        self.vm.handlers.push(handler);
+                    }
+                    OpCode::PopCatch => {
+                        self.vm.handlers.pop();
+                    }
+                    OpCode::PopFinally => {
+                        self.vm.handlers.pop();
+                        self.vm.pending.push(Completion::Normal);
+                    }
+                    OpCode::EndFinally => {
+                        match self.vm.pending.pop() {
+                            Some(Completion::Error(a)) => break a,
+                            Some(Completion::Return(a)) => {
+                                self.vm.frames.last_mut().expect("No frame running").ip = ip;
+                                match self.return_from(a, entry) {
+                                    Some(b) => return Ok(b),
+                                    None => continue 'frames
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    OpCode::Throw => {
+                        let value: Literal = self.pop();
+                        // Throwing a caught error rethrows it as it was
+                        match value {
+                            Literal::Error(a) => break (*a).clone(),
+                            _ => {
+                                let keyword: Token = Self::token(TokenType::Throw, Symbol::detached("throw"), chunk.line(start));
+                                break RuntimeError::thrown(keyword, value, self.call_stack.clone());
+                            }
+                        }
+                    }
+                }
+            };
+
+            self.unwind(error, entry)?;
+        }
+    }
+
+    // Write out the stack, then the instruction about to run
+    fn trace_instruction(&mut self, chunk: &Chunk, offset: usize) {
+        let mut stack: String = String::from("          ");
+        for value in &self.vm.stack {
+            stack.push_str(&format!("[ {} ]", Self::stringify_item(value.clone(), &mut Vec::new())));
+        }
+        let (instruction, _) = chunk.disassemble_instruction(offset);
+        let _ = self.write_error(&format!("{}\n{}\n", stack, instruction));
+    }
+
+    // Only called once the instruction is known to have what it takes on the stack, so nil is never
+    // actually made up
+    fn pop(&mut self) -> Literal {
+        return self.vm.stack.pop().unwrap_or(Literal::Nill);
+    }
+
+    fn peek(&self) -> &Literal {
+        return self.vm.stack.last().unwrap_or(&Literal::Nill);
+    }
+
+    // The name held by the constant the operand at "offset" refers to
+    fn name(chunk: &Chunk, offset: usize) -> Symbol {
+        match chunk.constants.get(chunk.read_u16(offset) as usize) {
+            Some(Constant::Name(a)) => return a.clone(),
+            _ => return Symbol::detached("")
+        }
+    }
+
+    // An instruction that can't run as it is, in code that wasn't verified
+    fn corrupted(&self, function: &Prototype, offset: usize, problem: &str) -> RuntimeError {
+        let message: String = format!("Corrupted bytecode, the instruction at {:04} in {} {}.", offset, function.name, problem);
+        return self.place(RuntimeError::native(message));
+    }
+
+    // The operator an instruction was compiled from, for the operations shared with the tree-walker
+    fn operator(op: OpCode) -> (TokenType, &'static str) {
+        match op {
+            OpCode::Greater => return (TokenType::Greater, ">"),
+            OpCode::GreaterEqual => return (TokenType::GreaterEqual, ">="),
+            OpCode::Less => return (TokenType::Less, "<"),
+            OpCode::LessEqual => return (TokenType::LessEqual, "<="),
+            OpCode::Add => return (TokenType::Plus, "+"),
+            OpCode::Subtract | OpCode::Negate => return (TokenType::Minus, "-"),
+            OpCode::Multiply => return (TokenType::Star, "*"),
+            OpCode::Divide => return (TokenType::Slash, "/"),
+            OpCode::Not => return (TokenType::Bang, "!"),
+            OpCode::NotEqual => return (TokenType::BangEqual, "!="),
+            _ => return (TokenType::EqualEqual, "==")
+        }
+    }
+
+    fn token(token_type: TokenType, lexeme: Symbol, line: u32) -> Token {
+        return Token {token_type: token_type, lexeme: lexeme, literal: None, line: line, column: 0};
+    }
+
+    // Place an error at the token an instruction was compiled from, unless it already knows where it came from
+    fn raise_at(&self, error: RuntimeError, token_type: TokenType, lexeme: Symbol, line: u32) -> RuntimeError {
+        let mut error: RuntimeError = self.place(error);
+        if error.is_unplaced() {
+            error.token = Box::new(Self::token(token_type, lexeme, line));
+        }
+        return error;
+    }
+
+    // The upvalue for a slot on the stack, shared with any other closure already using that slot
+    fn capture_upvalue(&mut self, slot: usize) -> SharedUpvalue {
+        let index: usize = self.vm.open_upvalues.partition_point(|a| Self::open_slot(a) < slot);
+        if let Some(a) = self.vm.open_upvalues.get(index) {
+            if Self::open_slot(a) == slot {
+                return a.clone();
+            }
+        }
+
+        let upvalue: SharedUpvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.heap.track_upvalue(&upvalue);
+        self.vm.open_upvalues.insert(index, upvalue.clone());
+        return upvalue;
+    }
+
+    fn open_slot(upvalue: &SharedUpvalue) -> usize {
+        match &*upvalue.borrow() {
+            Upvalue::Open(a) => return *a,
+            Upvalue::Closed(_) => return usize::MAX
+        }
+    }
+
+    // Move the variables in slots from "from" up off of the stack and into their upvalues
+    fn close_upvalues(&mut self, from: usize) {
+        while let Some(a) = self.vm.open_upvalues.last() {
+            let slot: usize = Self::open_slot(a);
+            if slot < from {
+                break;
+            }
+
+            let value: Literal = self.vm.stack[slot].clone();
+            *a.borrow_mut() = Upvalue::Closed(value);
+            self.vm.open_upvalues.pop();
+        }
+    }
+
+    // Go back to where a handler was pushed, dropping everything done since
+    fn restore(&mut self, handler: &Handler) {
+        self.vm.frames.truncate(handler.frame + 1);
+        self.call_stack.truncate(handler.calls);
+        self.close_upvalues(handler.stack);
+        self.vm.stack.truncate(handler.stack);
+        self.vm.pending.truncate(handler.pending);
+    }
+
+    // Return from the running frame, by way of any finally blocks in it. Gives back the value if
+    // that was the entry frame, None if another frame should run.
+    fn return_from(&mut self, value: Literal, entry: usize) -> Option<Literal> {
+        let index: usize = self.vm.frames.len() - 1;
+        while let Some(handler) = self.vm.handlers.last() {
+            if handler.frame != index {
+                break;
+            }
+
+            let handler: Handler = self.vm.handlers.pop().expect("No handler");
+            if handler.finally {
+                self.restore(&handler);
+                self.vm.pending.push(Completion::Return(value));
+                self.vm.frames[index].ip = handler.target;
+                return None;
+            }
+        }
+
+        let frame: Frame = self.vm.frames.pop().expect("No frame running");
+        self.close_upvalues(frame.base);
+        self.vm.stack.truncate(frame.base);
+        self.vm.pending.truncate(frame.pending);
+        if index == entry {
+            return Some(value);
+        }
+
+        self.call_stack.pop();
+        self.vm.stack.push(value);
+        return None;
+    }
+
+    // Hand an error to the innermost handler set up since "entry" that takes it, or if there isn't
+    // one, drop the frames from "entry" on and give the error back
+    fn unwind(&mut self, error: RuntimeError, entry: usize) -> Result<(), RuntimeError> {
+        while let Some(handler) = self.vm.handlers.last() {
+            if handler.frame < entry {
+                break;
+            }
+
+            // The script is being aborted, so neither catch nor finally blocks run
+            let handler: Handler = self.vm.handlers.pop().expect("No handler");
+            if error.aborted {
+                continue;
+            }
+
+            self.restore(&handler);
+            match handler.finally {
+                true => self.vm.pending.push(Completion::Error(error)),
+                false => {
+                    let value: Literal = Literal::Error(Rc::new(error));
+                    self.heap.track(&value);
+                    self.vm.stack.push(value);
+                }
+            }
+            self.vm.frames[handler.frame].ip = handler.target;
+            return Ok(());
+        }
+
+        let frame: &Frame = &self.vm.frames[entry];
+        let (base, calls, pending) = (frame.base, frame.calls, frame.pending);
+        self.vm.frames.truncate(entry);
+        self.call_stack.truncate(calls);
+        self.close_upvalues(base);
+        self.vm.stack.truncate(base);
+        self.vm.pending.truncate(pending);
+        return Err(error);
+    }
+}
@@ -0,0 +1,309 @@
+use std::rc::Rc;
+use crate::expression::{Expr, ExprVisitor, Slot};
+use crate::stmt::{Stmt, StmtVisitor};
+use crate::token::{Literal, Token};
+
+/*
+JSON output
+
+Tokens and statements written out as JSON, for editors and other tools to read (crlox tokens --json
+and crlox ast --json). This is the format meant for machines, the text listings are only for people
+to read. Each is one document, an object holding the schema version and the tokens or statements:
+
+  {"version": 2, "tokens": [token...]}
+  {"version": 2, "statements": [statement...]}
+
+  token      {"type": "Var", "lexeme": "var", "literal": null, "line": 1, "column": 1}, with the
+             literal a number or a string for those tokens and null otherwise
+  number     JSON has no infinity or NaN, so a number that is one (a number too long to fit becomes
+             infinity) is written as the string "inf", "-inf" or "nan". The token's type or the
+             literal's kind tells it apart from a string.
+
+Statements and expressions are objects whose "type" says what they are, with their parts under the
+names below. Those written with a token of their own also have the "line" and "column" it starts at:
+the operator of operators, the name of variables and functions, the keyword of whiles, throws and
+returns, and the closing parenthesis of calls.
+
+  Expression  expression             Block     statements
+  Print       expression             If        condition, then, else (statement or null)
+  Var         name, initializer      While     condition, body
+              (expression or null)   Try       body, name (or null), catch and finally (each a
+  Throw       value                            list of statements or null)
+  Return      value (or null)        Function  name, params (list of names), body
+
+  Binary      operator, left, right  Variable  name
+  Logical     operator, left, right  Assign    name, value
+  Unary       operator, right        Call      callee, arguments
+  Grouping    expression             Get       object, name
+  Literal     value (a number, a string, true, false or null), kind ("number", "string", "boolean"
+              or "nil")
+
+The statements are those the parser made, before they are optimized. Fields are only ever added to
+a version, anything else changes SCHEMA_VERSION. Version 2 wrote non-finite numbers as strings rather
+than null, and added the kind of literals.
+*/
+
+pub const SCHEMA_VERSION: u32 = 2;
+
+// Text with the characters JSON strings can't hold escaped, without the quotes
+fn escape(text: &str) -> String {
+    let mut escaped: String = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            a if (a as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", a as u32)),
+            a => escaped.push(a)
+        }
+    }
+    return escaped;
+}
+
+// A string as a JSON string, quoted and escaped. The token listing writes string values this way too.
+pub fn string(text: &str) -> String {
+    return format!("\"{}\"", escape(text));
+}
+
+// A literal from the source as a JSON value, see "number" above for those that aren't finite
+fn literal(value: &Literal) -> String {
+    match value {
+        Literal::Str(a) => return string(a),
+        Literal::Num(a) if a.is_nan() => return string("nan"),
+        Literal::Num(a) if a.is_infinite() => return string(if *a > 0.0 { "inf" } else { "-inf" }),
+        Literal::Num(a) => return format!("{}", a),
+        Literal::True => return "true".to_string(),
+        Literal::False => return "false".to_string(),
+        _ => return "null".to_string()
+    }
+}
+
+// What sort of value a literal is, which its JSON alone doesn't always say
+fn kind(value: &Literal) -> &'static str {
+    match value {
+        Literal::Num(_) => return "number",
+        Literal::Str(_) => return "string",
+        Literal::True | Literal::False => return "boolean",
+        _ => return "nil"
+    }
+}
+
+fn array(items: Vec<String>) -> String {
+    return format!("[{}]", items.join(", "));
+}
+
+// An object with its fields in the order given
+fn object(fields: &[(&str, String)]) -> String {
+    let fields: Vec<String> = fields.iter().map(|(a, b)| format!("{}: {}", string(a), b)).collect();
+    return format!("{{{}}}", fields.join(", "));
+}
+
+fn token(token: &Token) -> String {
+    return object(&[
+        ("type", string(&token.token_type.to_string())),
+        ("lexeme", string(&token.lexeme.text())),
+        ("literal", token.literal.as_ref().map(literal).unwrap_or_else(|| "null".to_string())),
+        ("line", token.line.to_string()),
+        ("column", token.column.to_string())
+    ]);
+}
+
+// The tokens of a script as a JSON document
+pub fn tokens(tokens: &[Token]) -> String {
+    let tokens: Vec<String> = tokens.iter().map(token).collect();
+    return object(&[("version", SCHEMA_VERSION.to_string()), ("tokens", array(tokens))]);
+}
+
+// Writes statements out as JSON, each visited statement leaving its object on "output"
+pub struct JsonWriter {
+    output: Vec<String>
+}
+
+impl JsonWriter {
+    pub fn json_writer_builder() -> JsonWriter {
+        return JsonWriter {
+            output: Vec::new()
+        }
+    }
+
+    // The statements of a script as a JSON document
+    pub fn write(&mut self, statements: &[Stmt]) -> String {
+        let statements: String = self.statements(statements);
+        return object(&[("version", SCHEMA_VERSION.to_string()), ("statements", statements)]);
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) -> String {
+        let mut written: Vec<String> = Vec::new();
+        for statement in statements {
+            written.push(self.statement(statement));
+        }
+        return array(written);
+    }
+
+    fn statement(&mut self, statement: &Stmt) -> String {
+        self.execute(statement);
+        return self.output.pop().unwrap_or_default();
+    }
+
+    fn expression(&mut self, expr: &Expr) -> String {
+        return self.visit(expr).unwrap_or_default();
+    }
+
+    // A node's type and fields, placed at "at" if it has a token of its own
+    fn node(node_type: &str, at: Option<&Token>, fields: Vec<(&str, String)>) -> String {
+        let mut all: Vec<(&str, String)> = vec![("type", string(node_type))];
+        all.extend(fields);
+        if let Some(a) = at {
+            all.push(("line", a.line.to_string()));
+            all.push(("column", a.column.to_string()));
+        }
+        return object(&all);
+    }
+
+    fn push(&mut self, node_type: &str, at: Option<&Token>, fields: Vec<(&str, String)>) {
+        self.output.push(Self::node(node_type, at, fields));
+    }
+}
+
+impl StmtVisitor<> for JsonWriter {
+    fn visit_expression(&mut self, expression: &Expr) {
+        let expression: String = self.expression(expression);
+        self.push("Expression", None, vec![("expression", expression)]);
+    }
+
+    fn visit_print(&mut self, expression: &Expr) {
+        let expression: String = self.expression(expression);
+        self.push("Print", None, vec![("expression", expression)]);
+    }
+
+    fn visit_var(&mut self, name: &Token, right: Option<&Expr>) {
+        let initializer: String = match right {
+            Some(a) => self.expression(a),
+            None => "null".to_string()
+        };
+        self.push("Var", Some(name), vec![("name", string(&name.lexeme.text())), ("initializer", initializer)]);
+    }
+
+    fn visit_block(&mut self, statements: &[Stmt]) {
+        let statements: String = self.statements(statements);
+        self.push("Block", None, vec![("statements", statements)]);
+    }
+
+    fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>) {
+        let condition: String = self.expression(condition);
+        let then_branch: String = self.statement(then_branch);
+        let else_branch: String = match else_branch {
+            Some(a) => self.statement(a),
+            None => "null".to_string()
+        };
+        self.push("If", None, vec![("condition", condition), ("then", then_branch), ("else", else_branch)]);
+    }
+
+    fn visit_while(&mut self, keyword: &Token, condition: &Expr, body: &Stmt) {
+        let condition: String = self.expression(condition);
+        let body: String = self.statement(body);
+        self.push("While", Some(keyword), vec![("condition", condition), ("body", body)]);
+    }
+
+    fn visit_try(&mut self, try_branch: &[Stmt], name: Option<&Token>, catch_branch: Option<&[Stmt]>, finally_branch: Option<&[Stmt]>) {
+        let body: String = self.statements(try_branch);
+        let name: String = name.map(|a| string(&a.lexeme.text())).unwrap_or_else(|| "null".to_string());
+        let catch_branch: String = match catch_branch {
+            Some(a) => self.statements(a),
+            None => "null".to_string()
+        };
+        let finally_branch: String = match finally_branch {
+            Some(a) => self.statements(a),
+            None => "null".to_string()
+        };
+        self.push("Try", None, vec![("body", body), ("name", name), ("catch", catch_branch), ("finally", finally_branch)]);
+    }
+
+    fn visit_throw(&mut self, keyword: &Token, value: &Expr) {
+        let value: String = self.expression(value);
+        self.push("Throw", Some(keyword), vec![("value", value)]);
+    }
+
+    fn visit_function(&mut self, name: &Token, params: &[Token], body: &Rc<Vec<Stmt>>) {
+        let params: Vec<String> = params.iter().map(|a| string(&a.lexeme.text())).collect();
+        let body: String = self.statements(body);
+        self.push("Function", Some(name), vec![("name", string(&name.lexeme.text())), ("params", array(params)), ("body", body)]);
+    }
+
+    fn visit_return(&mut self, keyword: &Token, value: Option<&Expr>) {
+        let value: String = match value {
+            Some(a) => self.expression(a),
+            None => "null".to_string()
+        };
+        self.push("Return", Some(keyword), vec![("value", value)]);
+    }
+}
+
+impl ExprVisitor<String> for JsonWriter {
+    fn visit_binary(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Option<String> {
+        let (left, right): (String, String) = (self.expression(left), self.expression(right));
+        return Some(Self::node("Binary", Some(operator), vec![("operator", string(&operator.lexeme.text())), ("left", left), ("right", right)]));
+    }
+
+    fn visit_grouping(&mut self, expression: &Expr) -> Option<String> {
+        let expression: String = self.expression(expression);
+        return Some(Self::node("Grouping", None, vec![("expression", expression)]));
+    }
+
+    fn visit_literal(&mut self, value: &Literal) -> Option<String> {
+        return Some(Self::node("Literal", None, vec![("value", literal(value)), ("kind", string(kind(value)))]));
+    }
+
+    fn visit_unary(&mut self, operator: &Token, right: &Expr) -> Option<String> {
+        let right: String = self.expression(right);
+        return Some(Self::node("Unary", Some(operator), vec![("operator", string(&operator.lexeme.text())), ("right", right)]));
+    }
+
+    fn visit_variable(&mut self, token: &Token, _slot: Option<Slot>) -> Option<String> {
+        return Some(Self::node("Variable", Some(token), vec![("name", string(&token.lexeme.text()))]));
+    }
+
+    fn visit_assignment(&mut self, name: &Token, value: &Expr, _slot: Option<Slot>) -> Option<String> {
+        let value: String = self.expression(value);
+        return Some(Self::node("Assign", Some(name), vec![("name", string(&name.lexeme.text())), ("value", value)]));
+    }
+
+    fn visit_logical(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Option<String> {
+        let (left, right): (String, String) = (self.expression(left), self.expression(right));
+        return Some(Self::node("Logical", Some(operator), vec![("operator", string(&operator.lexeme.text())), ("left", left), ("right", right)]));
+    }
+
+    fn visit_call(&mut self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> Option<String> {
+        let callee: String = self.expression(callee);
+        let arguments: Vec<String> = arguments.iter().map(|a| self.expression(a)).collect();
+        return Some(Self::node("Call", Some(paren), vec![("callee", callee), ("arguments", array(arguments))]));
+    }
+
+    fn visit_get(&mut self, object: &Expr, name: &Token) -> Option<String> {
+        let object: String = self.expression(object);
+        return Some(Self::node("Get", Some(name), vec![("object", object), ("name", string(&name.lexeme.text()))]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_strings() {
+        assert_eq!(string("a\"b\\c\nd\u{1}"), "\"a\\\"b\\\\c\\nd\\u0001\"");
+    }
+
+    #[test]
+    fn tags_numbers_json_cannot_hold() {
+        assert_eq!(literal(&Literal::Num(f64::INFINITY)), "\"inf\"");
+        assert_eq!(literal(&Literal::Num(f64::NEG_INFINITY)), "\"-inf\"");
+        assert_eq!(literal(&Literal::Num(f64::NAN)), "\"nan\"");
+        assert_eq!(literal(&Literal::Num(1.5)), "1.5");
+
+        let node: String = JsonWriter::json_writer_builder().visit_literal(&Literal::Num(f64::INFINITY)).unwrap_or_default();
+        assert_eq!(node, "{\"type\": \"Literal\", \"value\": \"inf\", \"kind\": \"number\"}");
+    }
+}
@@ -0,0 +1,279 @@
+// The code base mirrors the Java of Crafting Interpreters, explicit returns and all.
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+pub mod interpreter;
+pub mod token;
+mod scanner;
+mod expression;
+mod parser;
+mod resolver;
+mod optimizer;
+mod ast_printer;
+mod json;
+mod formatter;
+mod chunk;
+mod compiler;
+mod loxc;
+mod stmt;
+mod environment;
+pub mod callable;
+pub mod error;
+pub mod diagnostic;
+pub mod convert;
+pub mod output;
+mod memory;
+pub mod gc;
+mod intern;
+mod natives;
+use self::token::{Token, TokenType};
+use self::parser::{Parser};
+use self::resolver::Resolver;
+use self::optimizer::Optimizer;
+use self::compiler::Compiler;
+use self::chunk::Prototype;
+use self::stmt::Stmt;
+use self::intern::Interner;
+use std::rc::Rc;
+
+pub use self::diagnostic::{Diagnostic, DiagnosticKind};
+pub use self::interpreter::Interpreter;
+pub use self::convert::{FromValue, IntoValue};
+pub use self::error::RuntimeError;
+pub use self::output::CallbackWriter;
+
+// Compile the examples in the README as doctests, so they can't go stale
+#[cfg(doctest)]
+#[doc = include_str!("../README.md")]
+pub struct ReadmeDoctests;
+
+// A value as seen by Lox code, as handed between a host and the interpreter
+pub type Value = token::Literal;
+
+/* 
+Overview
+
+Program flow:
+Run from prompt or file -> Create an Interpreter -> Scan and tokenize -> 
+Parse tokens and create expressions -> Optimize -> Resolve local variables -> Interpret tokens and execute
+
+Scanner:
+A scanner consists of an input source, the source converted into chars, the tokens, as well as
+the position in the source. From here we go through and tokenize, finding certain key words,
+differentiating numbers and strings, etc. There is absolutely no processing being done to 
+simplify the expressions, all we are doing is finding out what the source is saying in an easier
+way.
+
+Parser:
+The parser turns these tokens into statements. Tokens consist of the token type, the 
+lexeme (what it is in the source,) an optional literal, and the line the token appears on. An
+expression takes our token (or Literal) and then gives it context. The parser's goal is to take
+the relative tokens and determine context, such as equality, comparisons, grouping, statements, etc.
+
+Optimizer:
+Anything that can be worked out before running, like arithmetic on numbers written in the source
+or an if whose condition is a literal, is worked out once here. See optimizer.rs.
+
+Resolver:
+Before anything runs, the resolver works out which scope each local variable lives in and where
+in it, so the interpreter can find them without looking them up by name. See resolver.rs.
+
+Interpreter:
+Now that we have a set of statements, we can now understand context. Mind you, throughout all these
+steps we have ruled out syntax errors and are now only left with run time errors. These errors are 
+anything that happen at run time, eg. when the program is already running. Because of how basic 
+Lox is, some type errors could be determined earlier. The interpreter takes these statements and 
+simplifies, it actually does the operations. This means that the Interpreter is what makes Lox, Lox.
+It is the part that runs all our code we've scanned and parsed.
+
+Compiler and virtual machine:
+Instead of walking the statements, they can be compiled to bytecode and run on a stack based
+virtual machine, which is usually somewhat quicker (10-40% on the scripts in bench/). Both are meant
+to give the same results, errors and all, which tests/backends.rs checks by running the same
+programs on each. See compiler.rs, chunk.rs and interpreter/vm.rs. Compiled scripts can be saved and
+loaded again as .loxc files, see loxc.rs.
+
+AST printer:
+Prints the statements the parser made, before they are optimized, to check how source was read.
+See ast_printer.rs, and json.rs for the tokens and statements as JSON, for other tools to read.
+
+Formatter:
+Reprints source in one canonical layout, working from the tokens and comments. See formatter.rs.
+*/
+
+// How deep a script may go before it is stopped. The defaults fit comfortably in STACK_SIZE, which
+// scripts are given whatever thread they are run on.
+#[derive(Copy, Clone, Debug)]
+pub struct Limits {
+    // Lox function calls in progress at once
+    pub max_call_depth: usize,
+    // Nesting of expressions and statements, both within the source and while running it
+    pub max_nesting_depth: usize
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_call_depth: 1024,
+            max_nesting_depth: 10000
+        }
+    }
+}
+
+// Which natives with effects outside of the interpreter a script may use. A native whose
+// capability is turned off is still defined, but calling it raises an error saying why.
+#[derive(Copy, Clone, Debug)]
+pub struct Capabilities {
+    // Reading, writing and listing files
+    pub file_io: bool,
+    // Reading environment variables
+    pub env: bool,
+    // Ending the whole process with exit()
+    pub exit: bool,
+    // Reading the system clock
+    pub clock: bool,
+    // Reading from standard input
+    pub stdin: bool
+}
+
+impl Capabilities {
+    // Nothing outside of the interpreter is reachable, for running untrusted scripts
+    pub fn sandbox() -> Capabilities {
+        Capabilities {
+            file_io: false,
+            env: false,
+            exit: false,
+            clock: false,
+            stdin: false
+        }
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Capabilities {
+            file_io: true,
+            env: true,
+            exit: true,
+            clock: true,
+            stdin: true
+        }
+    }
+}
+
+// How an interpreter runs scripts, walking the tree or compiling to bytecode for the virtual machine
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum Backend {
+    #[default]
+    TreeWalker,
+    Vm
+}
+
+// How tokens and statements are written out for a person to read, or as JSON for other tools
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum DumpFormat {
+    #[default]
+    Text,
+    Json
+}
+
+// Stack the command line gives the thread running the interpreter, which recurses as it walks the tree
+pub const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+// Run "f" with at least half of STACK_SIZE free, switching to a stack of its own if the host's
+// thread has less left. Scanning, parsing and running all recurse, so this is what lets the
+// default limits be reached on any thread without overflowing it.
+fn with_stack<T>(f: impl FnOnce() -> T) -> T {
+    return stacker::maybe_grow(STACK_SIZE / 2, STACK_SIZE, f);
+}
+
+// The Lox instance handed to the scanner and parser, which collects the errors they find
+#[derive(Default, Clone, Debug)]
+pub struct Lox {
+    had_error: bool,
+    limits: Limits,
+    diagnostics: Vec<Diagnostic>
+} 
+
+impl Lox {
+    // Call error message for scanner
+    pub fn scanner_error(&mut self, line: u32, message: &str) {
+        if !self.had_error {
+            self.had_error = true;
+        }
+        
+        Self::report(self, DiagnosticKind::Scan, line, "", message);
+    }
+
+    // Call error message for parser
+    pub fn parser_error(&mut self, token: Token, message: &str) {
+        if !self.had_error {
+            self.had_error = true;
+        }
+
+        match token.token_type {
+            TokenType::Eof => {
+                Self::report(self, DiagnosticKind::Parse, token.line, " at end", message)
+            }
+            _ => {
+                Self::report(self, DiagnosticKind::Parse, token.line, &format!(" at '{}'", token.lexeme), message);
+            }
+        }
+    }
+
+    // Record an error as a diagnostic
+    fn report(&mut self, kind: DiagnosticKind, line: u32, where_is: &str, message: &str) {
+        self.diagnostics.push(Diagnostic {
+            kind: kind,
+            line: line,
+            location: where_is.to_string(),
+            message: message.to_string(),
+            traceback: Vec::new()
+        });
+    }
+
+    // Scan source into tokens, with its comments as tokens as well if "comments" is set, or give back
+    // the errors found along the way. Names are interned into "interner".
+    fn scan(input: &str, limits: Limits, comments: bool, interner: &mut Interner) -> Result<Vec<token::Token>, Vec<Diagnostic>> {
+        let instance: Lox = Lox {had_error: false, limits: limits, diagnostics: Vec::new()};
+        let mut a: scanner::Scanner = scanner::scanner_builder(instance, input.to_string());
+        a.keep_comments = comments;
+        a.interner = std::mem::take(interner);
+        let tokens: Vec<token::Token> = a.scan_tokens();
+        *interner = std::mem::take(&mut a.interner);
+        match a.instance.had_error {
+            false => return Ok(tokens),
+            true => return Err(a.instance.diagnostics)
+        }
+    }
+
+    // Scan and parse source into statements exactly as written, or give back the errors found along the way
+    fn parse_tree(input: &str, limits: Limits, interner: &mut Interner) -> Result<Vec<Stmt>, Vec<Diagnostic>> {
+        let instance: Lox = Lox {had_error: false, limits: limits, diagnostics: Vec::new()};
+        let mut a: scanner::Scanner = scanner::scanner_builder(instance, input.to_string());
+        a.interner = std::mem::take(interner);
+        let tokens: Vec<token::Token> = a.scan_tokens();
+        *interner = std::mem::take(&mut a.interner);
+        let mut parser: Parser = Parser::parser_builder(tokens, a.instance);
+        match parser.parse() {
+            Some(a) => return Ok(a),
+            None => return Err(parser.instance.diagnostics)
+        }
+    }
+
+    // Scan and parse source into statements ready to run, optimized and resolved
+    fn parse(input: &str, limits: Limits, interner: &mut Interner) -> Result<Vec<Stmt>, Vec<Diagnostic>> {
+        let statements: Vec<Stmt> = Optimizer::optimizer_builder().optimize(Self::parse_tree(input, limits, interner)?);
+        Resolver::resolver_builder().resolve(&statements);
+        return Ok(statements);
+    }
+
+    // Scan, parse and compile source into the prototype of its top level, for the virtual machine
+    fn compile(input: &str, limits: Limits, interner: &mut Interner) -> Result<Rc<Prototype>, Vec<Diagnostic>> {
+        let statements: Vec<Stmt> = Self::parse(input, limits, interner)?;
+        let instance: Lox = Lox {had_error: false, limits: limits, diagnostics: Vec::new()};
+        let mut compiler: Compiler = Compiler::compiler_builder(instance);
+        match compiler.compile(&statements) {
+            Some(a) => return Ok(a),
+            None => return Err(compiler.instance.diagnostics)
+        }
+    }
+}
@@ -0,0 +1,468 @@
+use std::rc::Rc;
+use crate::chunk::{Chunk, Constant, OpCode, Prototype, UpvalueRef};
+use crate::intern::{Interner, Symbol};
+
+/*
+Compiled files
+
+A script compiled ahead of time (crlox compile) is saved as a .loxc file, which runs without being
+scanned, parsed or compiled again. The file is a header followed by the script's top level function:
+
+  header    "LOXC", the format version (u16), then a checksum (u32) of everything after the header
+  function  name (string), arity (u8), upvalue count (u16) and each upvalue as a local flag (u8)
+            and index (u8), constant count (u32) and each constant, code length (u32) and the code,
+            then the line table as a count (u32) and each run's offset and line (u32, u32)
+  constant  a tag (u8) and its value: 0 a number (f64 bits, u64), 1 a string, 2 a name, 3 a function
+  string    length in bytes (u32) then UTF-8
+
+Numbers are big endian, like the operands in the code. The checksum is 32 bit FNV-1a.
+
+Whatever is loaded is checked before it runs, the checksum first, then that every instruction is
+whole, that constants are of the kind their instruction expects, that jumps land on instructions,
+that code can't run off of its end and that it never takes more off of the stack than is there or
+uses a local slot past its top. Bump FORMAT_VERSION whenever the layout or the opcodes change.
+*/
+
+pub const FORMAT_VERSION: u16 = 2;
+
+const MAGIC: &[u8; 4] = b"LOXC";
+const HEADER_SIZE: usize = 10;
+
+const TAG_NUM: u8 = 0;
+const TAG_STR: u8 = 1;
+const TAG_NAME: u8 = 2;
+const TAG_FUNCTION: u8 = 3;
+
+// Save a compiled script
+pub fn write(script: &Prototype) -> Vec<u8> {
+    let mut payload: Vec<u8> = Vec::new();
+    write_function(&mut payload, script);
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(HEADER_SIZE + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
+    bytes.extend_from_slice(&checksum(&payload).to_be_bytes());
+    bytes.extend_from_slice(&payload);
+    return bytes;
+}
+
+// Load a compiled script, or say what is wrong with it. Functions nested deeper than "max_depth" are
+// refused, as loading them recurses. The names it uses are interned into "interner", the one the
+// script will run alongside.
+pub fn read(bytes: &[u8], max_depth: usize, interner: &mut Interner) -> Result<Rc<Prototype>, String> {
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err("not a compiled Lox file".to_string());
+    }
+    if bytes.len() < HEADER_SIZE {
+        return Err("corrupted, the header is cut short".to_string());
+    }
+
+    let version: u16 = u16::from_be_bytes([bytes[4], bytes[5]]);
+    if version != FORMAT_VERSION {
+        return Err(format!("compiled for bytecode format version {} but this crlox reads version {}, compile it again", version, FORMAT_VERSION));
+    }
+
+    let expected: u32 = u32::from_be_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]);
+    if checksum(&bytes[HEADER_SIZE..]) != expected {
+        return Err("corrupted, its checksum doesn't match".to_string());
+    }
+
+    let mut reader: Reader = Reader {bytes: bytes, position: HEADER_SIZE, max_depth: max_depth, interner: interner};
+    let script: Prototype = reader.function(0).map_err(|a| format!("corrupted, {}", a))?;
+    if reader.position != bytes.len() {
+        return Err("corrupted, there is data after the script".to_string());
+    }
+
+    return Ok(Rc::new(script));
+}
+
+fn checksum(bytes: &[u8]) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in bytes {
+        hash ^= *byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+
+    return hash;
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: usize) {
+    bytes.extend_from_slice(&(value as u32).to_be_bytes());
+}
+
+fn write_string(bytes: &mut Vec<u8>, text: &str) {
+    write_u32(bytes, text.len());
+    bytes.extend_from_slice(text.as_bytes());
+}
+
+fn write_function(bytes: &mut Vec<u8>, function: &Prototype) {
+    write_string(bytes, &function.name.text());
+    bytes.push(function.arity as u8);
+
+    bytes.extend_from_slice(&(function.upvalues.len() as u16).to_be_bytes());
+    for upvalue in &function.upvalues {
+        bytes.push(upvalue.local as u8);
+        bytes.push(upvalue.index);
+    }
+
+    let chunk: &Chunk = &function.chunk;
+    write_u32(bytes, chunk.constants.len());
+    for constant in &chunk.constants {
+        match constant {
+            Constant::Num(a) => {
+                bytes.push(TAG_NUM);
+                bytes.extend_from_slice(&a.to_bits().to_be_bytes());
+            }
+            Constant::Str(a) => {
+                bytes.push(TAG_STR);
+                write_string(bytes, a);
+            }
+            Constant::Name(a) => {
+                bytes.push(TAG_NAME);
+                write_string(bytes, &a.text());
+            }
+            Constant::Function(a) => {
+                bytes.push(TAG_FUNCTION);
+                write_function(bytes, a);
+            }
+        }
+    }
+
+    write_u32(bytes, chunk.code.len());
+    bytes.extend_from_slice(&chunk.code);
+
+    write_u32(bytes, chunk.lines.len());
+    for (offset, line) in &chunk.lines {
+        write_u32(bytes, *offset);
+        write_u32(bytes, *line as usize);
+    }
+}
+
+// Reads a file's functions, checking each as it goes
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    max_depth: usize,
+    interner: &'a mut Interner
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() - self.position < count {
+            return Err("the file ends early".to_string());
+        }
+
+        let taken: &'a [u8] = &self.bytes[self.position..self.position + count];
+        self.position += count;
+        return Ok(taken);
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        return Ok(self.take(1)?[0]);
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes: &[u8] = self.take(2)?;
+        return Ok(u16::from_be_bytes([bytes[0], bytes[1]]));
+    }
+
+    fn u32(&mut self) -> Result<usize, String> {
+        let bytes: &[u8] = self.take(4)?;
+        return Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize);
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let length: usize = self.u32()?;
+        match std::str::from_utf8(self.take(length)?) {
+            Ok(a) => return Ok(a.to_string()),
+            Err(_) => return Err("a string isn't valid UTF-8".to_string())
+        }
+    }
+
+    fn function(&mut self, depth: usize) -> Result<Prototype, String> {
+        if depth > self.max_depth {
+            return Err("functions are nested too deeply".to_string());
+        }
+
+        let name: Symbol = Symbol::detached(&self.string()?);
+        let arity: usize = self.u8()? as usize;
+
+        let mut upvalues: Vec<UpvalueRef> = Vec::new();
+        for _ in 0..self.u16()? {
+            let local: bool = match self.u8()? {
+                0 => false,
+                1 => true,
+                _ => return Err(format!("an upvalue of {} has an invalid local flag", name))
+            };
+            upvalues.push(UpvalueRef {local: local, index: self.u8()?});
+        }
+
+        let mut chunk: Chunk = Chunk::default();
+        for _ in 0..self.u32()? {
+            let constant: Constant = match self.u8()? {
+                TAG_NUM => {
+                    let bytes: &[u8] = self.take(8)?;
+                    let mut bits: [u8; 8] = [0; 8];
+                    bits.copy_from_slice(bytes);
+                    Constant::Num(f64::from_bits(u64::from_be_bytes(bits)))
+                }
+                TAG_STR => Constant::Str(self.string()?.into()),
+                TAG_NAME => {
+                    let name: String = self.string()?;
+                    Constant::Name(self.interner.intern(&name))
+                }
+                TAG_FUNCTION => Constant::Function(Rc::new(self.function(depth + 1)?)),
+                a => return Err(format!("a constant of {} has unknown kind {}", name, a))
+            };
+            chunk.constants.push(constant);
+        }
+
+        let length: usize = self.u32()?;
+        chunk.code = self.take(length)?.to_vec();
+
+        for _ in 0..self.u32()? {
+            let run: (usize, u32) = (self.u32()?, self.u32()? as u32);
+            chunk.lines.push(run);
+        }
+
+        let function: Prototype = Prototype {name: name, arity: arity, upvalues: upvalues, chunk: chunk};
+        verify(&function)?;
+        return Ok(function);
+    }
+}
+
+// Check the code of a function can run without reading past its end or finding the wrong kind of constant
+fn verify(function: &Prototype) -> Result<(), String> {
+    let chunk: &Chunk = &function.chunk;
+    let code: &[u8] = &chunk.code;
+    let name: Symbol = function.name.clone();
+
+    let constant = |offset: usize| chunk.constants.get(chunk.read_u16(offset) as usize);
+    let missing = |offset: usize| format!("the instruction at {:04} in {} refers to a missing or wrong constant", offset, name);
+
+    // Where each instruction starts, and where jumps go
+    let mut starts: Vec<bool> = vec![false; code.len()];
+    let mut targets: Vec<(usize, Option<usize>)> = Vec::new();
+    let mut last: Option<OpCode> = None;
+    let mut offset: usize = 0;
+    while offset < code.len() {
+        starts[offset] = true;
+        let op: OpCode = match OpCode::from_byte(code[offset]) {
+            Some(a) => a,
+            None => return Err(format!("unknown opcode {} at {:04} in {}", code[offset], offset, name))
+        };
+        let next: usize = offset + 1 + op.operand_size();
+        if next > code.len() {
+            return Err(format!("the instruction at {:04} in {} is cut short", offset, name));
+        }
+
+        match op {
+            OpCode::Constant if !matches!(constant(offset + 1), Some(Constant::Num(_)) | Some(Constant::Str(_))) => {
+                return Err(missing(offset));
+            }
+            OpCode::GetGlobal | OpCode::DefineGlobal | OpCode::SetGlobal | OpCode::GetProperty
+                if !matches!(constant(offset + 1), Some(Constant::Name(_))) => {
+                return Err(missing(offset));
+            }
+            OpCode::GetUpvalue | OpCode::SetUpvalue if code[offset + 1] as usize >= function.upvalues.len() => {
+                return Err(format!("the instruction at {:04} in {} uses a missing upvalue", offset, name));
+            }
+            OpCode::Closure => {
+                match constant(offset + 1) {
+                    Some(Constant::Function(a)) => {
+                        // Upvalues of the new function that aren't locals here must be upvalues here
+                        if a.upvalues.iter().any(|b| !b.local && b.index as usize >= function.upvalues.len()) {
+                            return Err(format!("{} closes over a missing upvalue of {}", a.name, name));
+                        }
+                    }
+                    _ => return Err(missing(offset))
+                }
+            }
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::PushCatch | OpCode::PushFinally => {
+                targets.push((offset, Some(next + chunk.read_u16(offset + 1) as usize)));
+            }
+            OpCode::Loop => {
+                if !matches!(constant(offset + 3), Some(Constant::Name(_))) {
+                    return Err(missing(offset));
+                }
+                targets.push((offset, next.checked_sub(chunk.read_u16(offset + 1) as usize)));
+            }
+            _ => {}
+        }
+
+        last = Some(op);
+        offset = next;
+    }
+
+    if last != Some(OpCode::Return) {
+        return Err(format!("the code of {} doesn't end in a return", name));
+    }
+    for (offset, target) in targets {
+        match target {
+            Some(a) if a < code.len() && starts[a] => {}
+            _ => return Err(format!("the jump at {:04} in {} doesn't land on an instruction", offset, name))
+        }
+    }
+
+    return verify_stack(function);
+}
+
+// Follow every path through the code, which by now is known to be made of whole instructions and
+// jumps that land on them, working out how deep the frame's stack is before each instruction. A
+// frame starts out holding the function and its arguments, and no instruction may take more than
+// the values above the function, or use a local slot that isn't there yet. Paths that meet must
+// agree on the depth, as the code after them can only have been compiled for one.
+fn verify_stack(function: &Prototype) -> Result<(), String> {
+    let chunk: &Chunk = &function.chunk;
+    let code: &[u8] = &chunk.code;
+    let name: Symbol = function.name.clone();
+
+    let mut depths: Vec<Option<usize>> = vec![None; code.len()];
+    let mut pending: Vec<(usize, usize)> = vec![(0, 1 + function.arity)];
+    while let Some((offset, depth)) = pending.pop() {
+        match depths[offset] {
+            Some(a) if a == depth => continue,
+            Some(_) => return Err(format!("the paths reaching {:04} in {} leave the stack at different depths", offset, name)),
+            None => depths[offset] = Some(depth)
+        }
+
+        // Every opcode was checked by verify
+        let op: OpCode = match OpCode::from_byte(code[offset]) {
+            Some(a) => a,
+            None => return Err(format!("unknown opcode {} at {:04} in {}", code[offset], offset, name))
+        };
+        let operand: usize = match op.operand_size() {
+            0 => 0,
+            _ => code[offset + 1] as usize
+        };
+        let (taken, left) = op.stack_effect(operand);
+        if taken > depth - 1 {
+            return Err(format!("the instruction at {:04} in {} takes more values than the stack holds", offset, name));
+        }
+
+        match op {
+            OpCode::GetLocal | OpCode::SetLocal if operand >= depth => {
+                return Err(format!("the instruction at {:04} in {} uses local slot {} of {}", offset, name, operand, depth));
+            }
+            OpCode::Closure => {
+                if let Some(Constant::Function(a)) = chunk.constants.get(chunk.read_u16(offset + 1) as usize) {
+                    // The slot the new closure goes in counts, a local function can call itself
+                    if a.upvalues.iter().any(|b| b.local && b.index as usize > depth) {
+                        return Err(format!("{} closes over a missing local of {}", a.name, name));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        let next: usize = offset + 1 + op.operand_size();
+        let after: usize = depth - taken + left;
+        match op {
+            OpCode::Return | OpCode::Throw => {}
+            OpCode::Jump => pending.push((next + chunk.read_u16(offset + 1) as usize, after)),
+            OpCode::Loop => pending.push((next - chunk.read_u16(offset + 1) as usize, after)),
+            OpCode::JumpIfFalse | OpCode::PushFinally => {
+                pending.push((next + chunk.read_u16(offset + 1) as usize, after));
+                pending.push((next, after));
+            }
+            // A catch block starts with the error on the stack
+            OpCode::PushCatch => {
+                pending.push((next + chunk.read_u16(offset + 1) as usize, after + 1));
+                pending.push((next, after));
+            }
+            _ => pending.push((next, after))
+        }
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Diagnostic, Interpreter, Limits};
+
+    // A script made of the given code, every instruction on line 1
+    fn script(code: &[u8], constants: Vec<Constant>) -> Prototype {
+        let mut chunk: Chunk = Chunk::default();
+        for byte in code {
+            chunk.write(*byte, 1);
+        }
+        chunk.constants = constants;
+        return Prototype {name: Symbol::detached("script"), arity: 0, upvalues: Vec::new(), chunk: chunk};
+    }
+
+    fn load(function: &Prototype) -> Result<Rc<Prototype>, String> {
+        return read(&write(function), Limits::default().max_nesting_depth, &mut Interner::default());
+    }
+
+    // Run a script on the VM without verifying it first, as a host building chunks by hand might
+    fn run_unverified(function: Prototype) -> Result<crate::Value, Vec<Diagnostic>> {
+        let mut interpreter: Interpreter = Interpreter::default();
+        interpreter.set_output(std::io::sink());
+        return interpreter.run_script(Rc::new(function));
+    }
+
+    fn assert_rejected(code: &[u8], constants: Vec<Constant>, problem: &str) {
+        match load(&script(code, constants.clone())) {
+            Ok(_) => panic!("loaded corrupted code {:?}", code),
+            Err(a) => assert!(a.contains(problem), "'{}' doesn't say '{}'", a, problem)
+        }
+        match run_unverified(script(code, constants)) {
+            Ok(_) => panic!("ran corrupted code {:?}", code),
+            Err(a) => assert!(a[0].message.starts_with("Corrupted bytecode"), "{}", a[0].message)
+        }
+    }
+
+    #[test]
+    fn compiled_scripts_load() {
+        let source: &str = "fun f(a, b) { var c = a + b; fun g() { return c; } return g; }\n\
+                            { fun loop(n) { if (n > 0) return loop(n - 1); return n; } print loop(3); }\n\
+                            try { throw f(1, 2)(); } catch (e) { print e; } finally { print \"done\"; }";
+        let bytes: Vec<u8> = Interpreter::default().compile(source).expect("compiles");
+        assert!(read(&bytes, 100, &mut Interner::default()).is_ok());
+    }
+
+    #[test]
+    fn rejects_popping_an_empty_stack() {
+        assert_rejected(&[OpCode::Pop as u8, OpCode::Nil as u8, OpCode::Return as u8], Vec::new(), "takes more values");
+    }
+
+    #[test]
+    fn rejects_returning_nothing() {
+        assert_rejected(&[OpCode::Return as u8], Vec::new(), "takes more values");
+    }
+
+    #[test]
+    fn rejects_calls_with_missing_arguments() {
+        assert_rejected(&[OpCode::Nil as u8, OpCode::Call as u8, 3, OpCode::Return as u8], Vec::new(), "takes more values");
+    }
+
+    #[test]
+    fn rejects_missing_local_slots() {
+        assert_rejected(&[OpCode::GetLocal as u8, 5, OpCode::Return as u8], Vec::new(), "local slot 5");
+        assert_rejected(&[OpCode::Nil as u8, OpCode::SetLocal as u8, 2, OpCode::Return as u8], Vec::new(), "local slot 2");
+    }
+
+    #[test]
+    fn rejects_closing_over_missing_locals() {
+        let mut inner: Prototype = script(&[OpCode::Nil as u8, OpCode::Return as u8], Vec::new());
+        inner.upvalues.push(UpvalueRef {local: true, index: 9});
+        assert_rejected(&[OpCode::Closure as u8, 0, 0, OpCode::Return as u8], vec![Constant::Function(Rc::new(inner))], "closes over a missing");
+    }
+
+    #[test]
+    fn rejects_paths_that_disagree_on_depth() {
+        // The jump skips one of the two pushes, so the second Nil is reached at two depths
+        let code: [u8; 7] = [OpCode::True as u8, OpCode::JumpIfFalse as u8, 0, 1, OpCode::Nil as u8, OpCode::Nil as u8, OpCode::Return as u8];
+        match load(&script(&code, Vec::new())) {
+            Ok(_) => panic!("loaded code whose paths disagree"),
+            Err(a) => assert!(a.contains("different depths"), "{}", a)
+        }
+    }
+
+    #[test]
+    fn rejects_missing_constants() {
+        assert_rejected(&[OpCode::Constant as u8, 0, 7, OpCode::Return as u8], vec![Constant::Num(1.0)], "missing or wrong constant");
+    }
+}
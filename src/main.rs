@@ -1,169 +1,271 @@
+// The code base mirrors the Java of Crafting Interpreters, explicit returns and all.
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
 use std::env;
-use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
 use text_io::read;
-mod interpreter;
-mod token;
-mod scanner;
-mod expression;
-mod parser;
-mod stmt;
-mod environment;
-use self::token::{Token, TokenType};
-use self::parser::{Parser};
-use self::interpreter::{Interpreter};
-use self::stmt::Stmt;
-
-/* 
-Overview
-
-Program flow:
-Run from prompt or file -> Create Lox instance -> Scan and tokenize -> 
-Parse tokens and create expressions -> Interpret tokens and execute
-
-Scanner:
-A scanner consists of an input source, the source converted into chars, the tokens, as well as
-the position in the source. From here we go through and tokenize, finding certain key words,
-differentiating numbers and strings, etc. There is absolutely no processing being done to 
-simplify the expressions, all we are doing is finding out what the source is saying in an easier
-way.
-
-Parser:
-The parser turns these tokens into statements. Tokens consist of the token type, the 
-lexeme (what it is in the source,) an optional literal, and the line the token appears on. An
-expression takes our token (or Literal) and then gives it context. The parser's goal is to take
-the relative tokens and determine context, such as equality, comparisons, grouping, statements, etc.
-
-Interpreter:
-Now that we have a set of statements, we can now understand context. Mind you, throughout all these
-steps we have ruled out syntax errors and are now only left with run time errors. These errors are 
-anything that happen at run time, eg. when the program is already running. Because of how basic 
-Lox is, some type errors could be determined earlier. The interpreter takes these statements and 
-simplifies, it actually does the operations. This means that the Interpreter is what makes Lox, Lox.
-It is the part that runs all our code we've scanned and parsed.
-*/
-
-#[derive(Default, Copy, Clone, Debug)]
-pub struct Lox {
-    had_error: bool,
-    had_runtime_error: bool
-} 
-
-impl Lox {
-    // Call error message for scanner
-    pub fn scanner_error(&mut self, line: u32, message: &str) {
-        if !self.had_error {
-            self.had_error = true;
-        }
-        
-        Self::report(self, line, "", message);
+use crlox::{Backend, Capabilities, Diagnostic, DiagnosticKind, DumpFormat, Interpreter, Limits, STACK_SIZE};
+
+// What the command line options ask for
+#[derive(Default)]
+struct Options {
+    limits: Limits,
+    capabilities: Capabilities,
+    backend: Backend,
+    trace_execution: bool,
+    gc_stress: bool,
+    // List the bytecode instead of running the script
+    disassemble: bool,
+    // Print the parsed statements instead of running the script
+    dump_ast: bool,
+    max_steps: Option<u64>,
+    max_memory: Option<usize>,
+    timeout: Option<Duration>
+}
+
+// Build the interpreter the options ask for, "arguments" are passed on to the script
+fn build(options: &Options, arguments: &[String]) -> Interpreter {
+    let mut interpreter: Interpreter = Interpreter::build_interpreter(options.limits, options.capabilities, arguments);
+    interpreter.set_backend(options.backend);
+    interpreter.set_trace_execution(options.trace_execution);
+    interpreter.set_gc_stress(options.gc_stress);
+    return interpreter;
+}
+
+// Give the interpreter a fresh budget, for the whole script or for each line at the prompt
+fn set_budget(interpreter: &mut Interpreter, options: &Options) {
+    interpreter.set_max_steps(options.max_steps);
+    interpreter.set_max_memory(options.max_memory);
+    if let Some(a) = options.timeout {
+        interpreter.set_timeout(a);
     }
+}
 
-    // Call error message for parser
-    pub fn parser_error(&mut self, token: Token, message: &str) {
-        if !self.had_error {
-            self.had_error = true;
+// Report errors on the interpreter's error output (stderr), then leave with the matching exit code
+fn report(interpreter: &mut Interpreter, diagnostics: Vec<Diagnostic>) -> i32 {
+    interpreter.report(&diagnostics);
+    interpreter.flush();
+    let mut code: i32 = 70; // Exit on interpreter error.
+    for diagnostic in diagnostics {
+        if let Some(a) = diagnostic.exit_status() {
+            return a; // Exit with the status the script called exit() with.
+        } else if diagnostic.kind == DiagnosticKind::Io {
+            code = 66; // Exit on missing or unreadable input.
+        } else if diagnostic.is_syntax_error() {
+            code = 65; // Exit on scanner/parser error.
         }
+    }
 
-        match token.token_type {
-            TokenType::Eof => {
-                Self::report(self, token.line, " at end", message)
-            }
-            _ => {
-                Self::report(self, token.line, &*format!(" at '{}'", token.lexeme), message);
-            }
+    return code;
+}
+
+// Called when running from a file, "arguments" are passed on to the script
+fn run_file(path: &str, arguments: &[String], options: &Options) {
+    let mut interpreter: Interpreter = build(options, arguments);
+    let listing: Option<Result<String, Vec<Diagnostic>>> = if options.dump_ast {
+        Some(interpreter.dump_ast_file(path, DumpFormat::Text))
+    } else if options.disassemble {
+        Some(interpreter.disassemble_file(path))
+    } else {
+        None
+    };
+    match listing {
+        Some(Ok(a)) => {
+            print!("{}", a);
+            return;
         }
+        Some(Err(a)) => std::process::exit(report(&mut interpreter, a)),
+        None => {}
     }
 
-    // Call error message for interpreter, also known as run time error.
-    pub fn interpreter_error(&mut self, token: Token, message: &str) {
-        if !self.had_runtime_error {
-            self.had_runtime_error = true;
-        }
+    set_budget(&mut interpreter, options);
+    if let Err(a) = interpreter.run_file(path) {
+        std::process::exit(report(&mut interpreter, a));
+    }
+}
 
-        match token.token_type {
-            TokenType::Eof => {
-                Self::report(self, token.line, " at end", message)
-            }
-            _ => {
-                Self::report(self, token.line, &*format!(" at '{}'", token.lexeme), message);
+// Read a line at a time, everything defined sticks around for the next line
+fn run_prompt(options: &Options) {
+    let mut interpreter: Interpreter = build(options, &[]);
+    loop {
+        let line: String = read!("{}\n");
+        if line == "quit" {break;}
+        set_budget(&mut interpreter, options);
+        if let Err(a) = interpreter.run(&line) {
+            let exited: bool = a.iter().any(|b| b.exit_status().is_some());
+            let code: i32 = report(&mut interpreter, a);
+            if exited {
+                std::process::exit(code);
             }
         }
     }
+}
 
-    // Report an error as a formatted error message
-    fn report(&self, line: u32, where_is: &str, message: &str) {
-        eprintln!("[line {}] Error{}: {}", line, where_is, message);
+// Compile a script ahead of time, "arguments" being what follows compile: script [-o output]. The
+// output defaults to the script with a .loxc extension.
+fn compile_file(arguments: &[String], options: &Options) {
+    let (path, output): (&String, String) = match arguments {
+        [a] => (a, Path::new(a).with_extension("loxc").to_string_lossy().into_owned()),
+        [a, b, c] if b == "-o" => (a, c.clone()),
+        _ => usage()
+    };
+
+    let mut interpreter: Interpreter = build(options, &[]);
+    if let Err(a) = interpreter.compile_file(path, &output) {
+        std::process::exit(report(&mut interpreter, a));
     }
+}
 
-    // Called when running from a file
-    fn run_file(&mut self, path: &str) {
-        // Read that juicy file as a string.
-        let data: String = fs::read_to_string(path).expect("Failed To Resolve File!");
-
-        // Run the scanner 
-        Self::run(self.clone(), data);
-        if self.had_error {
-            std::process::exit(65); // Exit on scanner/parser error.
-        } else if self.had_runtime_error {
-            std::process::exit(70); // Exit on interpreter error.
-        }
+// Print a script's tokens or statements, "arguments" being what follows tokens or ast: [--json] script
+fn dump(command: &str, arguments: &[String], options: &Options) {
+    let (path, format): (&String, DumpFormat) = match arguments {
+        [a] => (a, DumpFormat::Text),
+        [a, b] if a == "--json" => (b, DumpFormat::Json),
+        _ => usage()
+    };
+
+    let mut interpreter: Interpreter = build(options, &[]);
+    let listing: Result<String, Vec<Diagnostic>> = match command {
+        "tokens" => interpreter.dump_tokens_file(path, format),
+        _ => interpreter.dump_ast_file(path, format)
+    };
+    match listing {
+        Ok(a) => print!("{}", a),
+        Err(a) => std::process::exit(report(&mut interpreter, a))
     }
+}
 
-    // This seems to create a new line for each iteration?? Need to fix...
-    fn run_prompt(&mut self) {
-        loop {
-            let line: String = read!("{}\n");
-            if line == "quit" {break;}
-            Self::run(self.clone(), line);
-            self.had_error = false;
-        }
+// Format scripts in place, "arguments" being what follows fmt: [--check] script... With --check
+// nothing is written, the scripts that aren't formatted are listed and it exits with 1 if there are any.
+fn format_files(arguments: &[String], options: &Options) {
+    let (check, paths): (bool, &[String]) = match arguments {
+        [a, b @ ..] if a == "--check" => (true, b),
+        _ => (false, arguments)
+    };
+    if paths.is_empty() {
+        usage();
     }
 
-    // Run the scanner, debug statements included
-    fn run(self, input: String) {
-        let mut a: scanner::Scanner = scanner::scanner_builder(self, input);
-        let tokens: Vec<token::Token> = a.scan_tokens();
-        // println!("Scanner:");
-        // for i in tokens.clone() {
-        //     println!("{}", i);
-        // }
-        let mut parser: Parser = Parser::parser_builder(tokens, self);
-        let statements: Option<Vec<Stmt>> = parser.parse();
-        match statements {
-            Some(a) => {
-                let mut inter: Interpreter = Interpreter::build_interpreter(self);
-                inter.interpret(a);
-            }
-            None => {
-                return
+    let mut interpreter: Interpreter = build(options, &[]);
+    let mut unformatted: bool = false;
+    for path in paths {
+        match interpreter.format_file(path, check) {
+            Ok(false) if check => {
+                println!("{} is not formatted", path);
+                unformatted = true;
             }
+            Ok(_) => {}
+            Err(a) => std::process::exit(report(&mut interpreter, a))
         }
     }
+
+    if unformatted {
+        std::process::exit(1);
+    }
+}
+
+fn usage() -> ! {
+    println!("Usage: crlox [options] [--] [script [args...]]");
+    println!("       crlox [options] compile script [-o output.loxc]");
+    println!("       crlox [options] tokens|ast [--json] script");
+    println!("       crlox [options] fmt [--check] script...");
+    println!("A script named compile, tokens, ast or fmt is run by putting -- before it.");
+    println!("Options: [--max-call-depth n] [--max-nesting-depth n] [--max-steps n] [--max-memory bytes] [--timeout ms] [--sandbox] [--gc-stress] [--vm] [--trace-exec] [--disassemble] [--dump-ast]");
+    std::process::exit(64); // Exit
 }
 
 fn main() {
+    // Run on a thread with a big stack, so that deep recursion hits the interpreter's limits rather than the end of the stack
+    let child = thread::Builder::new().stack_size(STACK_SIZE).spawn(run_cli).expect("Failed To Spawn Interpreter!");
+    if child.join().is_err() {
+        std::process::exit(101); // Exit on a panic
+    }
+}
+
+fn run_cli() {
     // Args, including initial command. If ran as binary, this should under normal circumstances 
-    // be length 2 if running from file, and length 1 if running from prompt. 
+    // be length 2 or more if running from file, and length 1 if running from prompt. 
     let args: Vec<String> = env::args().collect();
+    let mut options: Options = Options::default();
+
+    // Options come before the script, switches are matched first and everything else takes a number
+    let mut index: usize = 1;
+    let mut script_only: bool = false;
+    while index < args.len() && args[index].starts_with("--") {
+        match args[index].as_str() {
+            // The end of the options, what follows is a script even if it is named like a subcommand
+            "--" => {
+                script_only = true;
+                index += 1;
+                break;
+            }
+            "--sandbox" => {
+                options.capabilities = Capabilities::sandbox();
+                index += 1;
+                continue;
+            }
+            // Collect on every allocation, to find objects the garbage collector loses track of
+            "--gc-stress" => {
+                options.gc_stress = true;
+                index += 1;
+                continue;
+            }
+            // Compile to bytecode and run it on the virtual machine, rather than walking the tree
+            "--vm" => {
+                options.backend = Backend::Vm;
+                index += 1;
+                continue;
+            }
+            // Run on the virtual machine, writing out the stack and each instruction before running it
+            "--trace-exec" => {
+                options.backend = Backend::Vm;
+                options.trace_execution = true;
+                index += 1;
+                continue;
+            }
+            "--disassemble" => {
+                options.disassemble = true;
+                index += 1;
+                continue;
+            }
+            // Print the statements as parsed, to check how precedence and for loops came out
+            "--dump-ast" => {
+                options.dump_ast = true;
+                index += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        let value: Option<u64> = args.get(index + 1).and_then(|b| b.parse().ok());
+        match (args[index].as_str(), value) {
+            ("--max-call-depth", Some(b)) => options.limits.max_call_depth = b as usize,
+            ("--max-nesting-depth", Some(b)) => options.limits.max_nesting_depth = b as usize,
+            ("--max-steps", Some(b)) => options.max_steps = Some(b),
+            ("--max-memory", Some(b)) => options.max_memory = Some(b as usize),
+            ("--timeout", Some(b)) => options.timeout = Some(Duration::from_millis(b)),
+            _ => usage()
+        }
+        index += 2;
+    }
     
     // Check for valid command usage
-    if args.len() > 2 {
-        println!("Usage: crlox [optional script]");
-        std::process::exit(64); // Exit
-    } else if args.len() == 2 {
-        // Grab reference to the second argument
-        let path = &args[1];
-
-        // Declare a mutable instance of Lox 
-        let mut a = Lox{had_error: false, had_runtime_error: false};
-
-        // Run the Lox File
-        a.run_file(path);
+    let command: &str = match args.get(index) {
+        Some(a) if !script_only => a,
+        _ => ""
+    };
+    if command == "compile" {
+        compile_file(&args[index + 1..], &options);
+    } else if command == "fmt" {
+        format_files(&args[index + 1..], &options);
+    } else if command == "tokens" || command == "ast" {
+        dump(&args[index], &args[index + 1..], &options);
+    } else if args.len() > index {
+        // Grab reference to the script, anything after it belongs to the script
+        run_file(&args[index], &args[index + 1..], &options);
     } else {
-        // Declare a mutable instance of Lox
-        let mut a = Lox{had_error: false, had_runtime_error: false};
-
         // Run Lox as a Prompt
-        a.run_prompt();
+        run_prompt(&options);
     }
-}
\ No newline at end of file
+}
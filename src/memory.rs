@@ -0,0 +1,120 @@
+use std::collections::HashSet;
+use std::mem;
+use std::rc::Rc;
+use crate::callable::{SharedUpvalue, Upvalue};
+use crate::environment::{Environment, SharedEnvironment};
+use crate::gc::Tracer;
+use crate::token::Literal;
+
+// Adds up the bytes taken by values, following lists, maps, errors, functions and scopes to what they hold.
+// Strings belong to the value holding them so each copy counts, while anything shared through an
+// Rc only counts once however many values point at it.
+#[derive(Default)]
+pub struct Meter {
+    pub bytes: usize,
+    // Shared values already counted, by address
+    seen: HashSet<usize>,
+    // Shared values counted but whose contents still need counting
+    pending: Vec<Literal>,
+    // Likewise for scopes
+    scopes: Vec<SharedEnvironment>
+}
+
+impl Meter {
+    // Count a value and, once finished, everything reachable from it
+    pub fn visit(&mut self, value: &Literal) {
+        self.bytes += mem::size_of::<Literal>();
+        let address: usize = match value {
+            Literal::Str(a) => {
+                self.bytes += a.len();
+                return;
+            }
+            Literal::List(a) => Rc::as_ptr(a) as *const () as usize,
+            Literal::Map(a) => Rc::as_ptr(a) as *const () as usize,
+            Literal::Callable(a) => Rc::as_ptr(a) as *const () as usize,
+            Literal::Error(a) => Rc::as_ptr(a) as *const () as usize,
+            _ => return
+        };
+
+        // Going through a worklist rather than recursing keeps deeply nested lists off the Rust stack
+        if self.seen.insert(address) {
+            self.pending.push(value.clone());
+        }
+    }
+
+    // Count every value in a scope and, once finished, the scopes around it
+    pub fn scope(&mut self, environment: &Environment) {
+        for value in environment.values() {
+            self.visit(value);
+        }
+        if let Some(a) = &environment.enclosing {
+            self.environment(a);
+        }
+    }
+
+    // Count the contents of everything visited so far, giving back the total
+    pub fn finish(&mut self) -> usize {
+        loop {
+            if let Some(a) = self.scopes.pop() {
+                if let Ok(b) = a.try_borrow() {
+                    self.scope(&b);
+                }
+                continue;
+            }
+            let value: Literal = match self.pending.pop() {
+                Some(a) => a,
+                None => break
+            };
+
+            match value {
+                Literal::List(a) => {
+                    for b in a.borrow().iter() {
+                        self.visit(b);
+                    }
+                }
+                Literal::Map(a) => {
+                    for (b, c) in a.borrow().iter() {
+                        self.bytes += b.capacity();
+                        self.visit(c);
+                    }
+                }
+                Literal::Callable(a) => {
+                    a.trace(self);
+                }
+                Literal::Error(a) => {
+                    self.bytes += mem::size_of_val(&*a) + a.message.capacity();
+                    self.visit(&a.value);
+                }
+                _ => {}
+            }
+        }
+
+        return self.bytes;
+    }
+}
+
+impl Tracer for Meter {
+    fn value(&mut self, value: &Literal) {
+        self.visit(value);
+    }
+
+    // Scopes are shared between the code running in them and the functions declared there, so they only count once
+    fn environment(&mut self, environment: &SharedEnvironment) {
+        if self.seen.insert(Rc::as_ptr(environment) as *const () as usize) {
+            self.bytes += mem::size_of::<Environment>();
+            self.scopes.push(environment.clone());
+        }
+    }
+
+    // Upvalues are shared between closures in the same way, an open one's variable is on the stack and counted there
+    fn upvalue(&mut self, upvalue: &SharedUpvalue) {
+        if self.seen.insert(Rc::as_ptr(upvalue) as *const () as usize) {
+            self.bytes += mem::size_of::<Upvalue>();
+            if let Ok(a) = upvalue.try_borrow() {
+                if let Upvalue::Closed(b) = &*a {
+                    self.visit(b);
+                }
+            }
+        }
+    }
+}
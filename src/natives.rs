@@ -0,0 +1,90 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::callable::{BoundMethod, HostFn, HostFunction, MethodFn, NativeFn, NativeFunction};
+use crate::error::RuntimeError;
+use crate::interpreter::Interpreter;
+use crate::token::Literal;
+
+pub mod math;
+pub mod string;
+pub mod list;
+pub mod map;
+pub mod io;
+pub mod system;
+
+// A table of built-in methods, each entry being a name, an arity and the implementation.
+pub type MethodTable = [(&'static str, usize, MethodFn)];
+
+// Define a native function as a global.
+pub fn define_native(interpreter: &mut Interpreter, name: &str, arity: usize, function: NativeFn) {
+    let native: NativeFunction = NativeFunction {name: name.to_string(), arity: arity, function: function};
+    interpreter.define_global(name, Literal::Callable(Rc::new(native)));
+}
+
+// Define a native if its capability is enabled, otherwise a stand in with the same name and arity
+// that explains why it can't be called.
+pub fn define_capability(interpreter: &mut Interpreter, name: &str, arity: usize, function: NativeFn, enabled: bool, capability: &str) {
+    match enabled {
+        true => define_native(interpreter, name, arity, function),
+        false => define_disabled(interpreter, name, arity, capability)
+    }
+}
+
+// Define a function returning a RuntimeError as a global, for natives that raise more than a message.
+pub fn define_host(interpreter: &mut Interpreter, name: &str, arity: usize, function: Rc<HostFn>) {
+    let host: HostFunction = HostFunction {name: name.to_string(), arity: arity, function: function};
+    interpreter.define_global(name, Literal::Callable(Rc::new(host)));
+}
+
+// Define a stand in for a native whose capability is disabled, explaining why it can't be called.
+pub fn define_disabled(interpreter: &mut Interpreter, name: &str, arity: usize, capability: &str) {
+    let message: String = format!("{}() is not available, the {} capability is disabled.", name, capability);
+    define_host(interpreter, name, arity, Rc::new(move |_, _| Err(RuntimeError::native(message.clone()))));
+}
+
+// Look up "name" in a method table and bind it to the receiver.
+pub fn bind_method(interpreter: &mut Interpreter, table: &MethodTable, receiver: Literal, name: &str) -> Option<Literal> {
+    for (method_name, arity, method) in table {
+        if *method_name == name {
+            let bound: BoundMethod = BoundMethod {receiver: RefCell::new(receiver), name: name.to_string(), arity: *arity, method: *method};
+            let method: Literal = Literal::Callable(Rc::new(bound));
+            interpreter.track(&method);
+            return Some(method);
+        }
+    }
+
+    return None;
+}
+
+// Wrap a vector up as a Lox list.
+pub fn new_list(interpreter: &mut Interpreter, items: Vec<Literal>) -> Literal {
+    let list: Literal = Literal::List(Rc::new(RefCell::new(items)));
+    interpreter.track(&list);
+    return list;
+}
+
+// Grab argument "index" as a number, or complain on behalf of native "name".
+pub fn number_argument(name: &str, arguments: &[Literal], index: usize) -> Result<f64, String> {
+    match &arguments[index] {
+        Literal::Num(a) => return Ok(*a),
+        a => return Err(format!("{}() expects a number but got {}.", name, a))
+    }
+}
+
+// Grab argument "index" as a whole number, used for positions and counts.
+pub fn integer_argument(name: &str, arguments: &[Literal], index: usize) -> Result<i64, String> {
+    let a: f64 = number_argument(name, arguments, index)?;
+    if a.fract() != 0.0 || !a.is_finite() {
+        return Err(format!("{}() expects a whole number but got {}.", name, a));
+    }
+
+    return Ok(a as i64);
+}
+
+// Grab argument "index" as a string, or complain on behalf of native "name".
+pub fn string_argument(name: &str, arguments: &[Literal], index: usize) -> Result<String, String> {
+    match &arguments[index] {
+        Literal::Str(a) => return Ok(a.to_string()),
+        a => return Err(format!("{}() expects a string but got {}.", name, a))
+    }
+}
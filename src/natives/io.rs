@@ -0,0 +1,116 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use crate::interpreter::Interpreter;
+use crate::Capabilities;
+use crate::natives::{define_capability, define_native, new_list, string_argument};
+use crate::token::Literal;
+
+// Install the file and standard stream natives into the global environment.
+pub fn define_io(interpreter: &mut Interpreter, capabilities: Capabilities) {
+    let files: bool = capabilities.file_io;
+    define_capability(interpreter, "readFile", 1, read_file, files, "file I/O");
+    define_capability(interpreter, "readLines", 1, read_lines, files, "file I/O");
+    define_capability(interpreter, "writeFile", 2, write_file, files, "file I/O");
+    define_capability(interpreter, "appendFile", 2, append_file, files, "file I/O");
+    define_capability(interpreter, "fileExists", 1, file_exists, files, "file I/O");
+    define_capability(interpreter, "listDir", 1, list_dir, files, "file I/O");
+    define_capability(interpreter, "readLine", 0, read_line, capabilities.stdin, "stdin");
+    define_native(interpreter, "eprint", 1, eprint);
+}
+
+// Account for reading a file in before doing so, a file that can't be read is left to fail later
+fn reserve_file(interpreter: &mut Interpreter, path: &str) -> Result<(), String> {
+    match fs::metadata(path) {
+        Ok(a) => return interpreter.reserve(a.len() as usize),
+        Err(_) => return Ok(())
+    }
+}
+
+// Read a whole file in as a string
+fn read_file(interpreter: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    let path: String = string_argument("readFile", &arguments, 0)?;
+    reserve_file(interpreter, &path)?;
+    match fs::read_to_string(&path) {
+        Ok(a) => return Ok(Literal::Str(a.into())),
+        Err(a) => return Err(format!("Could not read file '{}': {}.", path, a))
+    }
+}
+
+// Read a file in as a list of lines, without their line endings
+fn read_lines(interpreter: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    let path: String = string_argument("readLines", &arguments, 0)?;
+    reserve_file(interpreter, &path)?;
+    match fs::read_to_string(&path) {
+        Ok(a) => return Ok(new_list(interpreter, a.lines().map(|b| Literal::Str(b.into())).collect())),
+        Err(a) => return Err(format!("Could not read file '{}': {}.", path, a))
+    }
+}
+
+// Replace the contents of a file, creating it if needed
+fn write_file(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    let path: String = string_argument("writeFile", &arguments, 0)?;
+    let contents: String = Interpreter::stringify(arguments[1].clone());
+    match fs::write(&path, contents) {
+        Ok(_) => return Ok(Literal::Nill),
+        Err(a) => return Err(format!("Could not write file '{}': {}.", path, a))
+    }
+}
+
+// Add to the end of a file, creating it if needed
+fn append_file(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    let path: String = string_argument("appendFile", &arguments, 0)?;
+    let contents: String = Interpreter::stringify(arguments[1].clone());
+    let file = fs::OpenOptions::new().append(true).create(true).open(&path);
+    match file.and_then(|mut a| a.write_all(contents.as_bytes())) {
+        Ok(_) => return Ok(Literal::Nill),
+        Err(a) => return Err(format!("Could not append to file '{}': {}.", path, a))
+    }
+}
+
+fn file_exists(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    let path: String = string_argument("fileExists", &arguments, 0)?;
+    return Ok(Interpreter::from_bool(Path::new(&path).exists()));
+}
+
+// The names of the entries in a directory, sorted so the output is stable
+fn list_dir(interpreter: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    let path: String = string_argument("listDir", &arguments, 0)?;
+    let entries = match fs::read_dir(&path) {
+        Ok(a) => a,
+        Err(a) => return Err(format!("Could not list directory '{}': {}.", path, a))
+    };
+
+    let mut names: Vec<String> = Vec::new();
+    for entry in entries {
+        match entry {
+            Ok(a) => names.push(a.file_name().to_string_lossy().to_string()),
+            Err(a) => return Err(format!("Could not list directory '{}': {}.", path, a))
+        }
+    }
+    names.sort();
+
+    return Ok(new_list(interpreter, names.into_iter().map(|a| Literal::Str(a.into())).collect()));
+}
+
+// Read one line from standard input without its line ending, nil once the input runs out
+fn read_line(_: &mut Interpreter, _: Vec<Literal>) -> Result<Literal, String> {
+    let mut line: String = String::new();
+    match io::stdin().lock().read_line(&mut line) {
+        Ok(0) => return Ok(Literal::Nill),
+        Ok(_) => {
+            let trimmed: usize = line.trim_end_matches(&['\n', '\r'][..]).len();
+            line.truncate(trimmed);
+            return Ok(Literal::Str(line.into()));
+        }
+        Err(a) => return Err(format!("Could not read from stdin: {}.", a))
+    }
+}
+
+// Write a value to the error output (standard error by default), without a trailing newline
+fn eprint(interpreter: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    match interpreter.write_error(&Interpreter::stringify(arguments[0].clone())) {
+        Ok(_) => return Ok(Literal::Nill),
+        Err(a) => return Err(format!("Could not write to the error output: {}.", a))
+    }
+}
@@ -0,0 +1,67 @@
+use crate::interpreter::Interpreter;
+use crate::natives::{bind_method, integer_argument, MethodTable};
+use crate::token::Literal;
+
+// Methods available on every list value.
+const LIST_METHODS: &MethodTable = &[
+    ("get", 1, get),
+    ("set", 2, set),
+    ("push", 1, push)
+];
+
+// Resolve "name" on a list, either the len property or a bound method.
+pub fn get_property(interpreter: &mut Interpreter, receiver: Literal, name: &str) -> Result<Literal, String> {
+    if let Literal::List(a) = &receiver {
+        if name == "len" {
+            return Ok(Literal::Num(a.borrow().len() as f64));
+        }
+    }
+
+    match bind_method(interpreter, LIST_METHODS, receiver, name) {
+        Some(a) => return Ok(a),
+        None => return Err(format!("Undefined property '{}' on list.", name))
+    }
+}
+
+// Check that "index" is a valid position in a list of the given length
+fn check_index(name: &str, arguments: &[Literal], length: usize) -> Result<usize, String> {
+    let index: i64 = integer_argument(name, arguments, 0)?;
+    if index < 0 || index as usize >= length {
+        return Err(format!("List index {} out of range for length {}.", index, length));
+    }
+
+    return Ok(index as usize);
+}
+
+fn get(_: &mut Interpreter, receiver: &Literal, arguments: Vec<Literal>) -> Result<Literal, String> {
+    match receiver {
+        Literal::List(a) => {
+            let items = a.borrow();
+            let index: usize = check_index("get", &arguments, items.len())?;
+            return Ok(items[index].clone());
+        }
+        _ => unreachable!("list method bound to a non list")
+    }
+}
+
+fn set(_: &mut Interpreter, receiver: &Literal, arguments: Vec<Literal>) -> Result<Literal, String> {
+    match receiver {
+        Literal::List(a) => {
+            let mut items = a.borrow_mut();
+            let index: usize = check_index("set", &arguments, items.len())?;
+            items[index] = arguments[1].clone();
+            return Ok(arguments[1].clone());
+        }
+        _ => unreachable!("list method bound to a non list")
+    }
+}
+
+fn push(_: &mut Interpreter, receiver: &Literal, arguments: Vec<Literal>) -> Result<Literal, String> {
+    match receiver {
+        Literal::List(a) => {
+            a.borrow_mut().push(arguments[0].clone());
+            return Ok(Literal::Nill);
+        }
+        _ => unreachable!("list method bound to a non list")
+    }
+}
@@ -0,0 +1,81 @@
+use crate::interpreter::Interpreter;
+use crate::natives::{bind_method, new_list, string_argument, MethodTable};
+use crate::token::Literal;
+
+// Methods available on every map value, keys are always strings.
+const MAP_METHODS: &MethodTable = &[
+    ("get", 1, get),
+    ("set", 2, set),
+    ("has", 1, has),
+    ("remove", 1, remove),
+    ("keys", 0, keys)
+];
+
+// Resolve "name" on a map, either the len property or a bound method.
+pub fn get_property(interpreter: &mut Interpreter, receiver: Literal, name: &str) -> Result<Literal, String> {
+    if let Literal::Map(a) = &receiver {
+        if name == "len" {
+            return Ok(Literal::Num(a.borrow().len() as f64));
+        }
+    }
+
+    match bind_method(interpreter, MAP_METHODS, receiver, name) {
+        Some(a) => return Ok(a),
+        None => return Err(format!("Undefined property '{}' on map.", name))
+    }
+}
+
+// The value stored under a key, nil if there isn't one
+fn get(_: &mut Interpreter, receiver: &Literal, arguments: Vec<Literal>) -> Result<Literal, String> {
+    match receiver {
+        Literal::Map(a) => {
+            let key: String = string_argument("get", &arguments, 0)?;
+            return Ok(a.borrow().get(&key).cloned().unwrap_or(Literal::Nill));
+        }
+        _ => unreachable!("map method bound to a non map")
+    }
+}
+
+fn set(_: &mut Interpreter, receiver: &Literal, arguments: Vec<Literal>) -> Result<Literal, String> {
+    match receiver {
+        Literal::Map(a) => {
+            let key: String = string_argument("set", &arguments, 0)?;
+            a.borrow_mut().insert(key, arguments[1].clone());
+            return Ok(arguments[1].clone());
+        }
+        _ => unreachable!("map method bound to a non map")
+    }
+}
+
+fn has(_: &mut Interpreter, receiver: &Literal, arguments: Vec<Literal>) -> Result<Literal, String> {
+    match receiver {
+        Literal::Map(a) => {
+            let key: String = string_argument("has", &arguments, 0)?;
+            return Ok(Interpreter::from_bool(a.borrow().contains_key(&key)));
+        }
+        _ => unreachable!("map method bound to a non map")
+    }
+}
+
+// Take a key out of the map, giving back what was stored under it (nil if nothing was)
+fn remove(_: &mut Interpreter, receiver: &Literal, arguments: Vec<Literal>) -> Result<Literal, String> {
+    match receiver {
+        Literal::Map(a) => {
+            let key: String = string_argument("remove", &arguments, 0)?;
+            return Ok(a.borrow_mut().remove(&key).unwrap_or(Literal::Nill));
+        }
+        _ => unreachable!("map method bound to a non map")
+    }
+}
+
+// The keys as a sorted list of strings
+fn keys(interpreter: &mut Interpreter, receiver: &Literal, _: Vec<Literal>) -> Result<Literal, String> {
+    match receiver {
+        Literal::Map(a) => {
+            let mut keys: Vec<String> = a.borrow().keys().cloned().collect();
+            keys.sort();
+            return Ok(new_list(interpreter, keys.into_iter().map(|a| Literal::Str(a.into())).collect()));
+        }
+        _ => unreachable!("map method bound to a non map")
+    }
+}
@@ -0,0 +1,157 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::interpreter::Interpreter;
+use crate::natives::{define_native, number_argument};
+use crate::token::Literal;
+
+// Pseudo-random number generator (xorshift64*), seedable so that runs can be reproduced.
+#[derive(Clone, Copy, Debug)]
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    // Build a generator from a seed, zero is not a valid xorshift state so it gets nudged.
+    pub fn from_seed(seed: u64) -> Rng {
+        Rng {
+            state: if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed }
+        }
+    }
+
+    // Build a generator seeded from the system clock
+    pub fn from_time() -> Rng {
+        let nanos: u128 = SystemTime::now().duration_since(UNIX_EPOCH).map(|a| a.as_nanos()).unwrap_or(0);
+        return Self::from_seed(nanos as u64);
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        return self.state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+    }
+
+    // Uniformly distributed number in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        return (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+    }
+}
+
+// Install the math natives and constants into the global environment.
+pub fn define_math(interpreter: &mut Interpreter) {
+    interpreter.define_global("pi", Literal::Num(std::f64::consts::PI));
+    interpreter.define_global("infinity", Literal::Num(f64::INFINITY));
+    interpreter.define_global("nan", Literal::Num(f64::NAN));
+
+    define_native(interpreter, "sqrt", 1, sqrt);
+    define_native(interpreter, "pow", 2, pow);
+    define_native(interpreter, "floor", 1, floor);
+    define_native(interpreter, "ceil", 1, ceil);
+    define_native(interpreter, "round", 1, round);
+    define_native(interpreter, "abs", 1, abs);
+    define_native(interpreter, "min", 2, min);
+    define_native(interpreter, "max", 2, max);
+    define_native(interpreter, "sin", 1, sin);
+    define_native(interpreter, "cos", 1, cos);
+    define_native(interpreter, "tan", 1, tan);
+    define_native(interpreter, "atan2", 2, atan2);
+    define_native(interpreter, "log", 1, log);
+    define_native(interpreter, "exp", 1, exp);
+    define_native(interpreter, "isNan", 1, is_nan);
+    define_native(interpreter, "isInfinite", 1, is_infinite);
+    define_native(interpreter, "random", 0, random);
+    define_native(interpreter, "seed", 1, seed);
+}
+
+// Apply a single argument numeric function on behalf of native "name"
+fn unary(name: &str, arguments: Vec<Literal>, function: fn(f64) -> f64) -> Result<Literal, String> {
+    let a: f64 = number_argument(name, &arguments, 0)?;
+    return Ok(Literal::Num(function(a)));
+}
+
+// Apply a two argument numeric function on behalf of native "name"
+fn binary(name: &str, arguments: Vec<Literal>, function: fn(f64, f64) -> f64) -> Result<Literal, String> {
+    let a: f64 = number_argument(name, &arguments, 0)?;
+    let b: f64 = number_argument(name, &arguments, 1)?;
+    return Ok(Literal::Num(function(a, b)));
+}
+
+fn sqrt(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    return unary("sqrt", arguments, f64::sqrt);
+}
+
+fn pow(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    return binary("pow", arguments, f64::powf);
+}
+
+fn floor(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    return unary("floor", arguments, f64::floor);
+}
+
+fn ceil(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    return unary("ceil", arguments, f64::ceil);
+}
+
+// Rounds half away from zero, so round(-2.5) is -3
+fn round(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    return unary("round", arguments, f64::round);
+}
+
+fn abs(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    return unary("abs", arguments, f64::abs);
+}
+
+fn min(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    return binary("min", arguments, f64::min);
+}
+
+fn max(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    return binary("max", arguments, f64::max);
+}
+
+fn sin(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    return unary("sin", arguments, f64::sin);
+}
+
+fn cos(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    return unary("cos", arguments, f64::cos);
+}
+
+fn tan(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    return unary("tan", arguments, f64::tan);
+}
+
+// atan2(y, x), the angle of the point (x, y) in radians
+fn atan2(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    return binary("atan2", arguments, f64::atan2);
+}
+
+// Natural logarithm
+fn log(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    return unary("log", arguments, f64::ln);
+}
+
+fn exp(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    return unary("exp", arguments, f64::exp);
+}
+
+fn is_nan(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    let a: f64 = number_argument("isNan", &arguments, 0)?;
+    return Ok(Interpreter::from_bool(a.is_nan()));
+}
+
+fn is_infinite(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    let a: f64 = number_argument("isInfinite", &arguments, 0)?;
+    return Ok(Interpreter::from_bool(a.is_infinite()));
+}
+
+// Next number from the interpreter's generator, in [0, 1)
+fn random(interpreter: &mut Interpreter, _: Vec<Literal>) -> Result<Literal, String> {
+    return Ok(Literal::Num(interpreter.rng.next_f64()));
+}
+
+// Reseed the interpreter's generator so the following random() calls are reproducible
+fn seed(interpreter: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    let a: f64 = number_argument("seed", &arguments, 0)?;
+    interpreter.rng = Rng::from_seed(a.to_bits());
+    return Ok(Literal::Nill);
+}
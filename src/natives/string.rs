@@ -0,0 +1,151 @@
+use std::mem;
+use crate::interpreter::Interpreter;
+use crate::natives::{bind_method, integer_argument, new_list, string_argument, MethodTable};
+use crate::token::Literal;
+
+// Methods available on every string value, all positions are in characters rather than bytes.
+const STRING_METHODS: &MethodTable = &[
+    ("upper", 0, upper),
+    ("lower", 0, lower),
+    ("trim", 0, trim),
+    ("split", 1, split),
+    ("join", 1, join),
+    ("replace", 2, replace),
+    ("find", 1, find),
+    ("slice", 2, slice),
+    ("contains", 1, contains),
+    ("startsWith", 1, starts_with),
+    ("endsWith", 1, ends_with)
+];
+
+// Resolve "name" on a string, either the len property or a bound method.
+pub fn get_property(interpreter: &mut Interpreter, receiver: Literal, name: &str) -> Result<Literal, String> {
+    if name == "len" {
+        return Ok(Literal::Num(receiver_str(&receiver).chars().count() as f64));
+    }
+
+    match bind_method(interpreter, STRING_METHODS, receiver, name) {
+        Some(a) => return Ok(a),
+        None => return Err(format!("Undefined property '{}' on string.", name))
+    }
+}
+
+// The methods are only ever bound to strings, so grab the text back out.
+fn receiver_str(receiver: &Literal) -> &str {
+    match receiver {
+        Literal::Str(a) => return a,
+        _ => unreachable!("string method bound to a non string")
+    }
+}
+
+// Turn a possibly negative character position into one within 0..=length
+fn clamp_index(index: i64, length: usize) -> usize {
+    if index < 0 {
+        return (length as i64 + index).max(0) as usize;
+    }
+
+    return (index as usize).min(length);
+}
+
+fn upper(_: &mut Interpreter, receiver: &Literal, _: Vec<Literal>) -> Result<Literal, String> {
+    return Ok(Literal::Str(receiver_str(receiver).to_uppercase().into()));
+}
+
+fn lower(_: &mut Interpreter, receiver: &Literal, _: Vec<Literal>) -> Result<Literal, String> {
+    return Ok(Literal::Str(receiver_str(receiver).to_lowercase().into()));
+}
+
+fn trim(_: &mut Interpreter, receiver: &Literal, _: Vec<Literal>) -> Result<Literal, String> {
+    return Ok(Literal::Str(receiver_str(receiver).trim().into()));
+}
+
+// Split on a separator, an empty separator splits into single characters
+fn split(interpreter: &mut Interpreter, receiver: &Literal, arguments: Vec<Literal>) -> Result<Literal, String> {
+    let separator: String = string_argument("split", &arguments, 0)?;
+    let text: &str = receiver_str(receiver);
+
+    let count: usize = match separator.is_empty() {
+        true => text.chars().count(),
+        false => text.matches(&*separator).count() + 1
+    };
+    interpreter.reserve(text.len() + count * mem::size_of::<Literal>())?;
+
+    let parts: Vec<Literal> = if separator.is_empty() {
+        text.chars().map(|a| Literal::Str(a.to_string().into())).collect()
+    } else {
+        text.split(&*separator).map(|a| Literal::Str(a.into())).collect()
+    };
+
+    return Ok(new_list(interpreter, parts));
+}
+
+// Join the items of a list together, using this string as the separator
+fn join(interpreter: &mut Interpreter, receiver: &Literal, arguments: Vec<Literal>) -> Result<Literal, String> {
+    match &arguments[0] {
+        Literal::List(a) => {
+            let separator: &str = receiver_str(receiver);
+            interpreter.reserve(separator.len().saturating_mul(a.borrow().len().saturating_sub(1)))?;
+            let mut items: Vec<String> = Vec::new();
+            for value in a.borrow().iter() {
+                let item: String = Interpreter::stringify(value.clone());
+                interpreter.reserve(item.len())?;
+                items.push(item);
+            }
+            return Ok(Literal::Str(items.join(separator).into()));
+        }
+        a => return Err(format!("join() expects a list but got {}.", a))
+    }
+}
+
+// Replace every occurrence of the first argument with the second
+fn replace(interpreter: &mut Interpreter, receiver: &Literal, arguments: Vec<Literal>) -> Result<Literal, String> {
+    let from: String = string_argument("replace", &arguments, 0)?;
+    let to: String = string_argument("replace", &arguments, 1)?;
+    if from.is_empty() {
+        return Err("replace() can't replace an empty string.".to_string());
+    }
+
+    let text: &str = receiver_str(receiver);
+    let count: usize = text.matches(&*from).count();
+    interpreter.reserve(text.len().saturating_add(count.saturating_mul(to.len())))?;
+    return Ok(Literal::Str(text.replace(&*from, &to).into()));
+}
+
+// Character position of the first occurrence, or -1 if there isn't one
+fn find(_: &mut Interpreter, receiver: &Literal, arguments: Vec<Literal>) -> Result<Literal, String> {
+    let needle: String = string_argument("find", &arguments, 0)?;
+    let text: &str = receiver_str(receiver);
+
+    match text.find(&*needle) {
+        Some(a) => return Ok(Literal::Num(text[..a].chars().count() as f64)),
+        None => return Ok(Literal::Num(-1.0))
+    }
+}
+
+// Characters from start up to (not including) end, negative positions count back from the end
+fn slice(_: &mut Interpreter, receiver: &Literal, arguments: Vec<Literal>) -> Result<Literal, String> {
+    let chars: Vec<char> = receiver_str(receiver).chars().collect();
+    let start: usize = clamp_index(integer_argument("slice", &arguments, 0)?, chars.len());
+    let end: usize = clamp_index(integer_argument("slice", &arguments, 1)?, chars.len());
+
+    if start >= end {
+        return Ok(Literal::Str("".into()));
+    }
+
+    return Ok(Literal::Str(chars[start..end].iter().collect::<String>().into()));
+}
+
+fn contains(_: &mut Interpreter, receiver: &Literal, arguments: Vec<Literal>) -> Result<Literal, String> {
+    let needle: String = string_argument("contains", &arguments, 0)?;
+    return Ok(Interpreter::from_bool(receiver_str(receiver).contains(&*needle)));
+}
+
+fn starts_with(_: &mut Interpreter, receiver: &Literal, arguments: Vec<Literal>) -> Result<Literal, String> {
+    let prefix: String = string_argument("startsWith", &arguments, 0)?;
+    return Ok(Interpreter::from_bool(receiver_str(receiver).starts_with(&*prefix)));
+}
+
+fn ends_with(_: &mut Interpreter, receiver: &Literal, arguments: Vec<Literal>) -> Result<Literal, String> {
+    let suffix: String = string_argument("endsWith", &arguments, 0)?;
+    return Ok(Interpreter::from_bool(receiver_str(receiver).ends_with(&*suffix)));
+}
@@ -0,0 +1,52 @@
+use std::env;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::error::RuntimeError;
+use crate::interpreter::Interpreter;
+use crate::convert::IntoValue;
+use crate::Capabilities;
+use crate::natives::{define_capability, define_disabled, define_host, integer_argument, string_argument};
+use crate::token::Literal;
+
+// Install the process natives into the global environment.
+pub fn define_system(interpreter: &mut Interpreter, capabilities: Capabilities) {
+    define_capability(interpreter, "getEnv", 1, get_env, capabilities.env, "environment");
+    match capabilities.exit {
+        true => define_host(interpreter, "exit", 1, Rc::new(exit)),
+        false => define_disabled(interpreter, "exit", 1, "process exit")
+    }
+    define_capability(interpreter, "clock", 0, clock, capabilities.clock, "clock");
+}
+
+// Define args as a list of what followed the script path.
+pub fn define_arguments(interpreter: &mut Interpreter, arguments: &[String]) {
+    interpreter.set_global("args", arguments.to_vec().into_value());
+}
+
+// The value of an environment variable, nil if it isn't set
+fn get_env(_: &mut Interpreter, arguments: Vec<Literal>) -> Result<Literal, String> {
+    let name: String = string_argument("getEnv", &arguments, 0)?;
+    match env::var(&name) {
+        Ok(a) => return Ok(Literal::Str(a.into())),
+        Err(env::VarError::NotPresent) => return Ok(Literal::Nill),
+        Err(a) => return Err(format!("Could not read environment variable '{}': {}.", name, a))
+    }
+}
+
+// Stop the script with the given status, which the host (the command line) ends the process with.
+// It unwinds like any other error that can't be caught, so nothing is left half done.
+fn exit(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, RuntimeError> {
+    let code: i64 = integer_argument("exit", arguments, 0)?;
+    if !(0..=255).contains(&code) {
+        return Err(RuntimeError::native(format!("exit() expects a status from 0 to 255 but got {}.", code)));
+    }
+    return Err(RuntimeError::exit(code as i32));
+}
+
+// Seconds since the unix epoch, handy for timing
+fn clock(_: &mut Interpreter, _: Vec<Literal>) -> Result<Literal, String> {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(a) => return Ok(Literal::Num(a.as_secs_f64())),
+        Err(a) => return Err(format!("Could not read the clock: {}.", a))
+    }
+}
@@ -0,0 +1,178 @@
+use std::rc::Rc;
+use crate::expression::Expr;
+use crate::interpreter::Interpreter;
+use crate::stmt::Stmt;
+use crate::token::{Literal, TokenType};
+
+/*
+Optimizer
+
+Runs over the statements once they are parsed, before the resolver, and works out whatever can be
+worked out without running anything. Arithmetic, comparisons and string joins on literals are folded
+into a single literal, logical operators with a literal on the left are reduced to the side they
+pick, and ifs and whiles whose condition is a literal lose the code that can never run.
+
+Folding must not change what a script does, so anything that would raise an error at runtime (eg.
+dividing by zero, or adding nil) is left as it is, to raise that error at the same place and time.
+*/
+pub struct Optimizer {}
+
+impl Optimizer {
+    pub fn optimizer_builder() -> Optimizer {
+        return Optimizer {}
+    }
+
+    pub fn optimize(&mut self, statements: Vec<Stmt>) -> Vec<Stmt> {
+        // A script's value is its last statement's if that is an expression statement and nil
+        // otherwise, so whatever becomes of a last statement that isn't one is kept in a block
+        let last: usize = statements.len().saturating_sub(1);
+        let last_is_expression: bool = matches!(statements.last(), Some(Stmt::Expression {..}));
+
+        let mut optimized: Vec<Stmt> = Vec::new();
+        for (i, statement) in statements.into_iter().enumerate() {
+            match self.optimize_statement(statement) {
+                Some(a) if i == last && !last_is_expression && matches!(a, Stmt::Expression {..}) => {
+                    optimized.push(Stmt::Block {statements: vec![a]});
+                }
+                Some(a) => optimized.push(a),
+                None if i == last => optimized.push(Stmt::Block {statements: Vec::new()}),
+                None => {}
+            }
+        }
+
+        return optimized;
+    }
+
+    fn optimize_statements(&mut self, statements: Vec<Stmt>) -> Vec<Stmt> {
+        return statements.into_iter().filter_map(|a| self.optimize_statement(a)).collect();
+    }
+
+    // An optimized statement, or None if it can never do anything
+    fn optimize_statement(&mut self, stmt: Stmt) -> Option<Stmt> {
+        match stmt {
+            Stmt::Expression {expression} => return Some(Stmt::Expression {expression: self.fold(expression)}),
+            Stmt::Print {expression} => return Some(Stmt::Print {expression: self.fold(expression)}),
+            Stmt::Var {name, right} => return Some(Stmt::Var {name: name, right: right.map(|a| self.fold(a))}),
+            Stmt::Block {statements} => return Some(Stmt::Block {statements: self.optimize_statements(statements)}),
+            Stmt::If {condition, then_branch, else_branch} => {
+                let condition: Expr = self.fold(condition);
+                if let Expr::Literal {value} = &condition {
+                    match Interpreter::is_truthy(value) {
+                        true => return self.optimize_statement(*then_branch),
+                        false => return else_branch.and_then(|a| self.optimize_statement(*a))
+                    }
+                }
+
+                return Some(Stmt::If {
+                    condition: condition,
+                    then_branch: Box::new(self.optimize_branch(*then_branch)),
+                    else_branch: else_branch.and_then(|a| self.optimize_statement(*a)).map(Box::new)
+                });
+            }
+            Stmt::While {keyword, condition, body} => {
+                let condition: Expr = self.fold(condition);
+                if let Expr::Literal {value} = &condition {
+                    if !Interpreter::is_truthy(value) {
+                        return None;
+                    }
+                }
+
+                return Some(Stmt::While {keyword: keyword, condition: condition, body: Box::new(self.optimize_branch(*body))});
+            }
+            Stmt::Try {try_branch, name, catch_branch, finally_branch} => {
+                return Some(Stmt::Try {
+                    try_branch: self.optimize_statements(try_branch),
+                    name: name,
+                    catch_branch: catch_branch.map(|a| self.optimize_statements(a)),
+                    finally_branch: finally_branch.map(|a| self.optimize_statements(a))
+                });
+            }
+            Stmt::Throw {keyword, value} => return Some(Stmt::Throw {keyword: keyword, value: self.fold(value)}),
+            Stmt::Function {name, params, body} => {
+                let body: Vec<Stmt> = Rc::try_unwrap(body).unwrap_or_else(|a| (*a).clone());
+                return Some(Stmt::Function {name: name, params: params, body: Rc::new(self.optimize_statements(body))});
+            }
+            Stmt::Return {keyword, value} => return Some(Stmt::Return {keyword: keyword, value: value.map(|a| self.fold(a))})
+        }
+    }
+
+    // A statement that has to stay, as the body of an if or while, is left as an empty block if it can never do anything
+    fn optimize_branch(&mut self, stmt: Stmt) -> Stmt {
+        return self.optimize_statement(stmt).unwrap_or(Stmt::Block {statements: Vec::new()});
+    }
+
+    // An expression with everything that can be worked out ahead of time worked out
+    fn fold(&mut self, expr: Expr) -> Expr {
+        match expr {
+            Expr::Binary {left, operator, right} => {
+                let left: Expr = self.fold(*left);
+                let right: Expr = self.fold(*right);
+                if let (Expr::Literal {value: a}, Expr::Literal {value: b}) = (&left, &right) {
+                    if let Some(c) = Self::fold_binary(&operator.token_type, a, b) {
+                        return Expr::Literal {value: c};
+                    }
+                }
+
+                return Expr::Binary {left: Box::new(left), operator: operator, right: Box::new(right)};
+            }
+            Expr::Grouping {expression} => {
+                let expression: Expr = self.fold(*expression);
+                match expression {
+                    Expr::Literal {..} => return expression,
+                    _ => return Expr::Grouping {expression: Box::new(expression)}
+                }
+            }
+            Expr::Unary {operator, right} => {
+                let right: Expr = self.fold(*right);
+                match (&operator.token_type, &right) {
+                    (TokenType::Minus, Expr::Literal {value: Literal::Num(a)}) => return Expr::Literal {value: Literal::Num(-a)},
+                    (TokenType::Bang, Expr::Literal {value}) => return Expr::Literal {value: Interpreter::from_bool(!Interpreter::is_truthy(value))},
+                    _ => return Expr::Unary {operator: operator, right: Box::new(right)}
+                }
+            }
+            Expr::Assign {name, value, slot} => return Expr::Assign {name: name, value: Box::new(self.fold(*value)), slot: slot},
+            Expr::Logical {left, operator, right} => {
+                let left: Expr = self.fold(*left);
+                let right: Expr = self.fold(*right);
+
+                // "or" gives back the left side if it is true, "and" if it is false, otherwise the right side
+                if let Expr::Literal {value} = &left {
+                    let or: bool = matches!(operator.token_type, TokenType::Or);
+                    match Interpreter::is_truthy(value) == or {
+                        true => return left,
+                        false => return right
+                    }
+                }
+
+                return Expr::Logical {left: Box::new(left), operator: operator, right: Box::new(right)};
+            }
+            Expr::Call {callee, paren, arguments} => {
+                let callee: Expr = self.fold(*callee);
+                let arguments: Vec<Expr> = arguments.into_iter().map(|a| self.fold(a)).collect();
+                return Expr::Call {callee: Box::new(callee), paren: paren, arguments: arguments};
+            }
+            Expr::Get {object, name} => return Expr::Get {object: Box::new(self.fold(*object)), name: name},
+            Expr::Literal {..} | Expr::Variable {..} => return expr
+        }
+    }
+
+    // The value of a binary operator on two literals, None if it raises an error, which it is left to do at runtime
+    fn fold_binary(operator: &TokenType, left: &Literal, right: &Literal) -> Option<Literal> {
+        match (operator, left, right) {
+            (TokenType::Minus, Literal::Num(a), Literal::Num(b)) => return Some(Literal::Num(a - b)),
+            (TokenType::Plus, Literal::Num(a), Literal::Num(b)) => return Some(Literal::Num(a + b)),
+            (TokenType::Plus, Literal::Num(a), Literal::Str(b)) => return Some(Literal::Str(format!("{}{}", a, b).into())),
+            (TokenType::Plus, Literal::Str(a), Literal::Num(b)) => return Some(Literal::Str(format!("{}{}", a, b).into())),
+            (TokenType::Plus, Literal::Str(a), Literal::Str(b)) => return Some(Literal::Str(format!("{}{}", a, b).into())),
+            (TokenType::Slash, Literal::Num(a), Literal::Num(b)) if *b != 0.0 => return Some(Literal::Num(a / b)),
+            (TokenType::Star, Literal::Num(a), Literal::Num(b)) => return Some(Literal::Num(a * b)),
+            (TokenType::Greater, Literal::Num(a), Literal::Num(b)) => return Some(Interpreter::from_bool(a > b)),
+            (TokenType::GreaterEqual, Literal::Num(a), Literal::Num(b)) => return Some(Interpreter::from_bool(a >= b)),
+            (TokenType::Less, Literal::Num(a), Literal::Num(b)) => return Some(Interpreter::from_bool(a < b)),
+            (TokenType::LessEqual, Literal::Num(a), Literal::Num(b)) => return Some(Interpreter::from_bool(a <= b)),
+            (TokenType::EqualEqual, Literal::Num(a), Literal::Num(b)) => return Some(Interpreter::from_bool(a == b)),
+            (TokenType::BangEqual, Literal::Num(a), Literal::Num(b)) => return Some(Interpreter::from_bool(a != b)),
+            _ => return None
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use std::io::{self, Write};
+
+// Somewhere for the interpreter to write to, either for the program's own output or for diagnostics
+pub type Sink = Box<dyn Write>;
+
+// Adapts a callback into a sink, handy for hosts without a writer of their own such as a GUI. Each
+// print arrives in a single call, newline included.
+pub struct CallbackWriter<F: FnMut(&str)> {
+    callback: F
+}
+
+impl<F: FnMut(&str)> CallbackWriter<F> {
+    pub fn new(callback: F) -> CallbackWriter<F> {
+        CallbackWriter {
+            callback: callback
+        }
+    }
+}
+
+impl<F: FnMut(&str)> Write for CallbackWriter<F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (self.callback)(&String::from_utf8_lossy(buf));
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return Ok(());
+    }
+}
@@ -1,29 +1,42 @@
 use crate::token::{Token, TokenType, Literal};
 use crate::expression::{Expr};
+use std::cell::Cell;
+use std::rc::Rc;
 use crate::stmt::Stmt;
 
 pub struct Parser {
     pub tokens: Vec<Token>,
     pub current: i32,
-    pub instance: crate::Lox
+    pub instance: crate::Lox,
+    // How many function bodies deep we are, return is only allowed inside of one
+    pub function_depth: u32,
+    // How deeply nested the expression or statement being parsed is
+    pub depth: usize,
+    // Set once the nesting gets too deep, the rest of the source is skipped without further errors
+    pub abandoned: bool,
+    // Set by an error in the declaration being parsed, which then skips ahead to the next one
+    pub panicking: bool
 }
 
 impl Parser {
     // Return Previous Token
-    fn previous(&mut self) -> Token {
-        let a: Vec<Token> = self.tokens.clone();
-        return a.into_iter().nth((self.current - 1) as usize).expect("Missing Element");
+    fn previous(&self) -> Token {
+        return self.tokens[(self.current - 1) as usize].clone();
     }
     
     // Return Current Token
-    fn peek(&mut self) -> Token {
-        let a: Vec<Token> = self.tokens.clone();
-        return a.into_iter().nth(self.current as usize).expect("Missing Element");
+    fn peek(&self) -> Token {
+        return self.tokens[self.current as usize].clone();
+    }
+
+    // The type of the current token, without copying the token
+    fn peek_type(&self) -> &TokenType {
+        return &self.tokens[self.current as usize].token_type;
     }
 
     // Are we at the end of the line? If so, it has been fun partner...
-    fn is_end(&mut self) -> bool {
-        return matches!(self.peek().token_type, TokenType::Eof);
+    fn is_end(&self) -> bool {
+        return matches!(self.peek_type(), TokenType::Eof);
     }
 
     // Consume a token
@@ -36,11 +49,11 @@ impl Parser {
     }
 
     // Check if current "tokens" TokenType equals requested TokenType "token_type"
-    fn check(&mut self, token_type: TokenType) -> bool {
+    fn check(&self, token_type: TokenType) -> bool {
         if self.is_end() {
             return false;
         } else {
-            return self.peek().token_type == token_type;
+            return *self.peek_type() == token_type;
         }
     }
 
@@ -66,9 +79,36 @@ impl Parser {
         return false;
     }
 
-    // If there is an error send it here to report to the Lox instance
+    // Report an error to the Lox instance, only the first in a declaration as the rest tend to follow from it
     fn error(&mut self, token: Token, message: String) {
-        self.instance.parser_error(token, &*message);
+        if self.abandoned || self.panicking {
+            return;
+        }
+        self.panicking = true;
+
+        self.instance.parser_error(token, &message);
+    }
+
+    // Stands in for an expression that couldn't be parsed
+    fn placeholder() -> Expr {
+        return Expr::Literal {value: Literal::Str("Placeholder".into())};
+    }
+
+    // Parse something nested one level deeper. Nesting is limited so that neither parsing nor running
+    // the tree can overflow the stack, past the limit the rest of the source is given up on.
+    fn nested<T>(&mut self, placeholder: T, parse: fn(&mut Self) -> T) -> T {
+        if self.depth >= self.instance.limits.max_nesting_depth {
+            let token: Token = self.peek();
+            self.error(token, "Too much nesting.".to_string());
+            self.abandoned = true;
+            self.current = (self.tokens.len() - 1) as i32;
+            return placeholder;
+        }
+
+        self.depth += 1;
+        let parsed: T = parse(self);
+        self.depth -= 1;
+        return parsed;
     }
 
     fn consume(&mut self, token_type: TokenType, message: String) -> Option<Token> {
@@ -104,7 +144,7 @@ impl Parser {
         }
 
         if self.match_type(TokenType::Id) {
-            return Expr::Variable {token: self.previous()};
+            return Expr::Variable {token: self.previous(), slot: Cell::new(None)};
         }
 
         if self.match_type(TokenType::LParen) {
@@ -115,11 +155,11 @@ impl Parser {
         } else {
             let a: Token = self.peek();
             self.error(a, "Expect expression.".to_string());
-            return Expr::Literal {value: Literal::Str("Placeholder".to_string())};
+            return Expr::Literal {value: Literal::Str("Placeholder".into())};
         }
     }
 
-    // call → primary ( "(" arguments? ")" )* ;
+    // call → primary ( "(" arguments? ")" | "." IDENTIFIER )* ;
     fn call(&mut self) -> Expr {
         let mut expr: Expr = self.primary();
 
@@ -128,6 +168,11 @@ impl Parser {
                 if let Some(new_expr) = self.finish_call(expr.clone()) {
                     expr = new_expr;
                 }
+            } else if self.match_type(TokenType::Dot) {
+                match self.consume(TokenType::Id, "Expect property name after '.'.".to_string()) {
+                    Some(name) => expr = Expr::Get {object: Box::new(expr), name: name},
+                    None => break
+                }
             } else {
                 break;
             }
@@ -140,7 +185,7 @@ impl Parser {
     fn unary(&mut self) -> Expr {
         if self.match_type_vec(vec![TokenType::Bang, TokenType::Minus]) {
             let operator: Token = self.previous();
-            let right: Expr = self.unary();
+            let right: Expr = self.nested(Self::placeholder(), Self::unary);
             return Expr::Unary {operator: operator, right: Box::new(right)};
         }
 
@@ -227,11 +272,11 @@ impl Parser {
 
         if self.match_type(TokenType::Equal) {
             let equals: Token = self.previous();
-            let value: Expr = self.assignment();
+            let value: Expr = self.nested(Self::placeholder(), Self::assignment);
 
             match expr {
-                Expr::Variable{token} => {
-                    return Expr::Assign{name: token, value: Box::new(value)}
+                Expr::Variable{token, slot: _} => {
+                    return Expr::Assign{name: token, value: Box::new(value), slot: Cell::new(None)}
                 }
                 _ => {
                     self.error(equals, "Invalid assignment target.".to_string());
@@ -244,7 +289,7 @@ impl Parser {
 
     // expression → assignment
     fn expression(&mut self) -> Expr {
-        return self.assignment();
+        return self.nested(Self::placeholder(), Self::assignment);
     }
 
     fn finish_call(&mut self, callee: Expr) -> Option<Expr> {
@@ -278,6 +323,7 @@ impl Parser {
     }
 
     fn for_statement(&mut self) -> Stmt {
+        let keyword: Token = self.previous();
         self.consume(TokenType::LParen, "Expect '(' after 'for'.".to_string());
 
         let initializer: Option<Stmt>;
@@ -289,45 +335,44 @@ impl Parser {
             initializer = Some(self.expression_statement());
         }
 
-        let condition: Expr;
-        if !self.check(TokenType::Semicolon) {
-            condition = self.expression();
+        let condition: Expr = if !self.check(TokenType::Semicolon) {
+            self.expression()
         } else {
-            condition = Expr::Literal{value: Literal::True};
-        }
+            Expr::Literal{value: Literal::True}
+        };
         self.consume(TokenType::Semicolon, "Expect ';' after loop condition.".to_string());
 
-        let increment: Option<Expr>;
-        if !self.check(TokenType::RParen) {
-            increment = Some(self.expression())
+        let increment: Option<Expr> = if !self.check(TokenType::RParen) {
+            Some(self.expression())
         } else {
-            increment = None;
-        }
+            None
+        };
         self.consume(TokenType::RParen, "Expect ')' after for clauses.".to_string());
 
         let mut body: Stmt = self.statement();
 
-        if increment.is_some() {
+        if let Some(a) = increment {
             body = Stmt::Block{
-                statements: vec![body, Stmt::Expression{ expression: increment.unwrap() }]
+                statements: vec![body, Stmt::Expression{ expression: a }]
             }
         }
-        body = Stmt::While{ condition: condition, body: Box::new(body) };
+        body = Stmt::While{ keyword: keyword, condition: condition, body: Box::new(body) };
 
-        if initializer.is_some() {
-            body = Stmt::Block{ statements: vec![initializer.unwrap(), body] }
+        if let Some(a) = initializer {
+            body = Stmt::Block{ statements: vec![a, body] }
         }
 
         return body;
     }
 
     fn while_statement(&mut self) -> Stmt {
+        let keyword: Token = self.previous();
         let _ = self.consume(TokenType::LParen, "Expect '(' after 'while'.".to_string());
         let condition: Expr = self.expression();
         let _ = self.consume(TokenType::RParen, "Expect ')' after 'while'.".to_string());
         let body: Stmt = self.statement();
 
-        return Stmt::While{ condition: condition, body: Box::new(body)}
+        return Stmt::While{ keyword: keyword, condition: condition, body: Box::new(body)}
     }
 
     fn if_statement(&mut self) -> Stmt {
@@ -345,6 +390,59 @@ impl Parser {
         return Stmt::If {condition: condition, then_branch: Box::new(then_branch), else_branch: else_branch}
     }
 
+    // try_statement → "try" block ( "catch" "(" IDENTIFIER ")" block )? ( "finally" block )? ;
+    fn try_statement(&mut self) -> Stmt {
+        let keyword: Token = self.previous();
+        let _ = self.consume(TokenType::LBrace, "Expect '{' after 'try'.".to_string());
+        let try_branch: Vec<Stmt> = self.block();
+
+        let mut name: Option<Token> = None;
+        let mut catch_branch: Option<Vec<Stmt>> = None;
+        if self.match_type(TokenType::Catch) {
+            let _ = self.consume(TokenType::LParen, "Expect '(' after 'catch'.".to_string());
+            name = self.consume(TokenType::Id, "Expect error variable name.".to_string());
+            let _ = self.consume(TokenType::RParen, "Expect ')' after error variable name.".to_string());
+            let _ = self.consume(TokenType::LBrace, "Expect '{' after catch clause.".to_string());
+            catch_branch = Some(self.block());
+        }
+
+        let mut finally_branch: Option<Vec<Stmt>> = None;
+        if self.match_type(TokenType::Finally) {
+            let _ = self.consume(TokenType::LBrace, "Expect '{' after 'finally'.".to_string());
+            finally_branch = Some(self.block());
+        }
+
+        if catch_branch.is_none() && finally_branch.is_none() {
+            self.error(keyword, "Expect 'catch' or 'finally' after try block.".to_string());
+        }
+
+        return Stmt::Try {try_branch: try_branch, name: name, catch_branch: catch_branch, finally_branch: finally_branch}
+    }
+
+    // throw_statement → "throw" expression ";" ;
+    fn throw_statement(&mut self) -> Stmt {
+        let keyword: Token = self.previous();
+        let value: Expr = self.expression();
+        let _: Option<Token> = self.consume(TokenType::Semicolon, "Expect ';' after thrown value.".to_string());
+        return Stmt::Throw {keyword: keyword, value: value}
+    }
+
+    // return_statement → "return" expression? ";" ;
+    fn return_statement(&mut self) -> Stmt {
+        let keyword: Token = self.previous();
+        if self.function_depth == 0 {
+            self.error(keyword.clone(), "Can't return from top-level code.".to_string());
+        }
+
+        let mut value: Option<Expr> = None;
+        if !self.check(TokenType::Semicolon) {
+            value = Some(self.expression());
+        }
+
+        let _: Option<Token> = self.consume(TokenType::Semicolon, "Expect ';' after return value.".to_string());
+        return Stmt::Return {keyword: keyword, value: value}
+    }
+
     fn print_statement(&mut self) -> Stmt {
         let value: Expr = self.expression();
         let _: Option<Token> = self.consume(TokenType::Semicolon, "Expect ';' after value.".to_string());
@@ -364,7 +462,12 @@ impl Parser {
         return statements;
     }
 
+    // Statements nest inside of each other (blocks, if bodies...) so keep track of how deep
     fn statement(&mut self) -> Stmt {
+        return self.nested(Stmt::Expression {expression: Self::placeholder()}, Self::match_statement);
+    }
+
+    fn match_statement(&mut self) -> Stmt {
         if self.match_type(TokenType::For) {
             return self.for_statement();
         }
@@ -374,9 +477,18 @@ impl Parser {
         if self.match_type(TokenType::Print) {
             return self.print_statement();
         }
+        if self.match_type(TokenType::Return) {
+            return self.return_statement();
+        }
         if self.match_type(TokenType::While) {
             return self.while_statement();
         }
+        if self.match_type(TokenType::Try) {
+            return self.try_statement();
+        }
+        if self.match_type(TokenType::Throw) {
+            return self.throw_statement();
+        }
         if self.match_type(TokenType::LBrace) {
             return Stmt::Block{statements: self.block()};
         }
@@ -388,9 +500,9 @@ impl Parser {
         self.advance();
 
         while !self.is_end() {
-            if !(self.previous().token_type == TokenType::Semicolon) {
-                match self.peek().token_type {
-                    TokenType::Class | TokenType::Fun | TokenType::Var | TokenType::For | TokenType::If | TokenType::While | TokenType::Print | TokenType::Return => {
+            if !(self.tokens[(self.current - 1) as usize].token_type == TokenType::Semicolon) {
+                match self.peek_type() {
+                    TokenType::Class | TokenType::Fun | TokenType::Var | TokenType::For | TokenType::If | TokenType::While | TokenType::Print | TokenType::Return | TokenType::Try | TokenType::Throw => {
                         return;
                     }
                     _ => {}
@@ -398,6 +510,8 @@ impl Parser {
             } else {
                 return
             }
+
+            self.advance();
         }
     }
 
@@ -417,27 +531,60 @@ impl Parser {
         }
     }
 
-    fn declaration(&mut self) -> Option<Stmt> {
-        if self.match_type(TokenType::Var) {
-            match self.var_declaration() {
-                Some(a) => return Some(a),
-                None => {
-                    self.synchronize();
-                    return None
+    // function → IDENTIFIER "(" parameters? ")" block ;
+    fn function(&mut self, kind: &str) -> Option<Stmt> {
+        let name: Token = self.consume(TokenType::Id, format!("Expect {} name.", kind))?;
+        self.consume(TokenType::LParen, format!("Expect '(' after {} name.", kind))?;
+
+        let mut params: Vec<Token> = Vec::new();
+        if !self.check(TokenType::RParen) {
+            loop {
+                if params.len() >= 255 {
+                    let peak: Token = self.peek();
+                    self.error(peak, "Can't have more than 255 parameters.".to_string());
+                }
+                params.push(self.consume(TokenType::Id, "Expect parameter name.".to_string())?);
+                if !self.match_type(TokenType::Comma) {
+                    break;
                 }
             }
+        }
+        self.consume(TokenType::RParen, "Expect ')' after parameters.".to_string())?;
+        self.consume(TokenType::LBrace, format!("Expect '{{' before {} body.", kind))?;
+
+        self.function_depth += 1;
+        let body: Vec<Stmt> = self.nested(Vec::new(), Self::block);
+        self.function_depth -= 1;
+
+        return Some(Stmt::Function {name: name, params: params, body: Rc::new(body)})
+    }
+
+    // A declaration or statement. One with an error in it is dropped and the tokens up to the next
+    // statement skipped, so that a bad token is never parsed again.
+    fn declaration(&mut self) -> Option<Stmt> {
+        let declaration: Option<Stmt> = if self.match_type(TokenType::Fun) {
+            self.function("function")
+        } else if self.match_type(TokenType::Var) {
+            self.var_declaration()
         } else {
-            return Some(self.statement())
+            Some(self.statement())
+        };
+
+        if self.panicking {
+            self.panicking = false;
+            self.synchronize();
+            return None
         }
+
+        return declaration
     }
 
     pub fn parse(&mut self) -> Option<Vec<Stmt>>{
         let mut statements: Vec<Stmt> = Vec::new();
 
         while !self.is_end() {
-            match self.declaration() {
-                Some(a) => statements.push(a),
-                None => {}
+            if let Some(a) = self.declaration() {
+                statements.push(a);
             }
         }
 
@@ -452,7 +599,48 @@ impl Parser {
         return Parser {
             tokens: tokens,
             current: 0,
-            instance: instance
+            instance: instance,
+            function_depth: 0,
+            depth: 0,
+            abandoned: false,
+            panicking: false
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::intern::Interner;
+    use crate::{Diagnostic, Limits, Lox};
+
+    // The errors parsing the source gives, as the command line prints them
+    fn errors(source: &str) -> Vec<String> {
+        match Lox::parse_tree(source, Limits::default(), &mut Interner::default()) {
+            Ok(_) => return Vec::new(),
+            Err(a) => return a.iter().map(Diagnostic::to_string).collect()
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn stray_comma_is_skipped() {
+        assert_eq!(errors("1, 2;"), vec!["[line 1] Error at ',': Expect ';' after value."]);
+        assert_eq!(errors(","), vec!["[line 1] Error at ',': Expect expression."]);
+    }
+
+    #[test]
+    fn stray_characters_are_skipped() {
+        assert_eq!(errors("]"), vec!["[line 1] Error: Unexpected character."]);
+        assert_eq!(errors("@ print 1;"), vec!["[line 1] Error: Unexpected character."]);
+        assert_eq!(errors("print ] 1;"), vec!["[line 1] Error: Unexpected character."]);
+    }
+
+    #[test]
+    fn one_error_per_statement() {
+        let source: &str = "print 1, 2;\nvar = 3;\nprint 4;\nfun f() { , ; }\nprint (5;";
+        assert_eq!(errors(source), vec![
+            "[line 1] Error at ',': Expect ';' after value.",
+            "[line 2] Error at '=': Expect variable name.",
+            "[line 4] Error at ',': Expect expression.",
+            "[line 5] Error at ';': Expect ')' after expression."
+        ]);
+    }
+}
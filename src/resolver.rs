@@ -0,0 +1,200 @@
+use std::cell::Cell;
+use crate::expression::{Expr, Slot};
+use crate::intern::Symbol;
+use crate::stmt::Stmt;
+
+/*
+Resolver
+
+Runs over the statements once they are parsed, working out where each local variable will be
+found at runtime so the interpreter can go straight to it instead of searching scope after scope
+by name. It keeps the same scopes the interpreter will make: one for each block, function call,
+and try, catch and finally clause. Each variable is numbered by the order it is declared in within
+its scope, the same order the interpreter will define it in.
+
+Every variable or assignment naming a local gets the Slot it lives in, how many scopes out from
+the innermost one and its number there. Anything else is a global, left for the interpreter to look
+up by name as globals can be declared after the code using them.
+*/
+pub struct Resolver {
+    // The names declared in each local scope, innermost last. Declaring a name again in the same
+    // scope makes a new variable, which hides the first from then on.
+    scopes: Vec<Vec<Symbol>>
+}
+
+impl Resolver {
+    pub fn resolver_builder() -> Resolver {
+        return Resolver {
+            scopes: Vec::new()
+        }
+    }
+
+    pub fn resolve(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.resolve_statement(statement);
+        }
+    }
+
+    // Resolve statements in a scope of their own
+    fn resolve_block(&mut self, statements: &[Stmt], declared: Option<Symbol>) {
+        self.scopes.push(declared.into_iter().collect());
+        self.resolve(statements);
+        self.scopes.pop();
+    }
+
+    // Declare a variable in the innermost scope, globals aren't tracked
+    fn declare(&mut self, name: Symbol) {
+        if let Some(a) = self.scopes.last_mut() {
+            a.push(name);
+        }
+    }
+
+    // Find the innermost local with this name, None if it must be a global
+    fn resolve_local(&self, name: Symbol, slot: &Cell<Option<Slot>>) {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if let Some(index) = scope.iter().rposition(|a| *a == name) {
+                slot.set(Some(Slot {depth: depth, index: index}));
+                return;
+            }
+        }
+
+        slot.set(None);
+    }
+
+    fn resolve_statement(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expression {expression} => self.resolve_expression(expression),
+            Stmt::Print {expression} => self.resolve_expression(expression),
+            Stmt::Var {name, right} => {
+                // The initializer runs before the variable exists, so it sees whatever the name meant before
+                if let Some(a) = right {
+                    self.resolve_expression(a);
+                }
+                self.declare(name.lexeme.clone());
+            }
+            Stmt::Block {statements} => self.resolve_block(statements, None),
+            Stmt::If {condition, then_branch, else_branch} => {
+                self.resolve_expression(condition);
+                self.resolve_statement(then_branch);
+                if let Some(a) = else_branch {
+                    self.resolve_statement(a);
+                }
+            }
+            Stmt::While {keyword: _, condition, body} => {
+                self.resolve_expression(condition);
+                self.resolve_statement(body);
+            }
+            Stmt::Try {try_branch, name, catch_branch, finally_branch} => {
+                self.resolve_block(try_branch, None);
+                if let Some(a) = catch_branch {
+                    self.resolve_block(a, name.as_ref().map(|b| b.lexeme.clone()));
+                }
+                if let Some(a) = finally_branch {
+                    self.resolve_block(a, None);
+                }
+            }
+            Stmt::Throw {keyword: _, value} => self.resolve_expression(value),
+            Stmt::Function {name, params, body} => {
+                // Declared before the body so the function can call itself
+                self.declare(name.lexeme.clone());
+                self.scopes.push(params.iter().map(|a| a.lexeme.clone()).collect());
+                self.resolve(body);
+                self.scopes.pop();
+            }
+            Stmt::Return {keyword: _, value} => {
+                if let Some(a) = value {
+                    self.resolve_expression(a);
+                }
+            }
+        }
+    }
+
+    fn resolve_expression(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Binary {left, operator: _, right} => {
+                self.resolve_expression(left);
+                self.resolve_expression(right);
+            }
+            Expr::Grouping {expression} => self.resolve_expression(expression),
+            Expr::Literal {value: _} => {}
+            Expr::Unary {operator: _, right} => self.resolve_expression(right),
+            Expr::Variable {token, slot} => self.resolve_local(token.lexeme.clone(), slot),
+            Expr::Assign {name, value, slot} => {
+                self.resolve_expression(value);
+                self.resolve_local(name.lexeme.clone(), slot);
+            }
+            Expr::Logical {left, operator: _, right} => {
+                self.resolve_expression(left);
+                self.resolve_expression(right);
+            }
+            Expr::Call {callee, paren: _, arguments} => {
+                self.resolve_expression(callee);
+                for argument in arguments {
+                    self.resolve_expression(argument);
+                }
+            }
+            Expr::Get {object, name: _} => self.resolve_expression(object)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intern::Interner;
+    use crate::{Limits, Lox};
+
+    // The slot of each variable printed, in the order they are printed
+    fn printed(statements: &[Stmt], slots: &mut Vec<(String, Option<Slot>)>) {
+        for statement in statements {
+            match statement {
+                Stmt::Print {expression: Expr::Variable {token, slot}} => slots.push((token.lexeme.to_string(), slot.get())),
+                Stmt::Block {statements} => printed(statements, slots),
+                Stmt::Function {body, ..} => printed(body, slots),
+                Stmt::Try {try_branch, catch_branch, ..} => {
+                    printed(try_branch, slots);
+                    printed(catch_branch.as_deref().unwrap_or_default(), slots);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn slots(source: &str) -> Vec<(String, Option<Slot>)> {
+        let statements: Vec<Stmt> = Lox::parse(source, Limits::default(), &mut Interner::default()).expect("parses");
+        let mut slots: Vec<(String, Option<Slot>)> = Vec::new();
+        printed(&statements, &mut slots);
+        return slots;
+    }
+
+    fn local(name: &str, depth: usize, index: usize) -> (String, Option<Slot>) {
+        return (name.to_string(), Some(Slot {depth: depth, index: index}));
+    }
+
+    #[test]
+    fn globals_have_no_slot() {
+        assert_eq!(slots("var a = 1; print a; print later;"), vec![("a".to_string(), None), ("later".to_string(), None)]);
+    }
+
+    #[test]
+    fn locals_count_scopes_out() {
+        let source: &str = "{ var a = 1; var b = 2; { var c = 3; print c; print b; print a; } print b; }";
+        assert_eq!(slots(source), vec![local("c", 0, 0), local("b", 1, 1), local("a", 1, 0), local("b", 0, 1)]);
+    }
+
+    #[test]
+    fn redeclaring_makes_a_new_slot() {
+        assert_eq!(slots("{ var a = 1; print a; var a = 2; print a; }"), vec![local("a", 0, 0), local("a", 0, 1)]);
+    }
+
+    #[test]
+    fn functions_see_their_parameters_and_what_was_declared_before_them() {
+        let source: &str = "{ var a = 1; fun f(x, y) { print y; print a; print f; } var a = 2; }";
+        assert_eq!(slots(source), vec![local("y", 0, 1), local("a", 1, 0), local("f", 1, 1)]);
+    }
+
+    #[test]
+    fn catch_declares_its_error() {
+        assert_eq!(slots("try { var t = 1; print t; } catch (e) { print e; }"), vec![local("t", 0, 0), local("e", 0, 0)]);
+    }
+}
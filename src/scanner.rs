@@ -1,7 +1,10 @@
 use crate::token::{Token, TokenType, Literal};
+use crate::intern::{Interner, Symbol};
 use std::collections::HashMap;
+use std::rc::Rc;
 
 // Scanner, takes a Lox instance, a source, a token vector associated with it, a start, current, and line.
+// The line a token starts on and where that line starts are kept to place the token.
 #[derive(Default)]
 pub struct Scanner {
     pub instance: crate::Lox,
@@ -11,6 +14,15 @@ pub struct Scanner {
     pub start: u32,
     pub current: u32,
     pub line: u32,
+    pub start_line: u32,
+    pub line_start: u32,
+    pub start_column: u32,
+    // Keep comments as tokens, for the formatter
+    pub keep_comments: bool,
+    // Where names are interned
+    pub interner: Interner,
+    // The strings in the source so far, so each one is only stored once
+    pub strings: HashMap<String, Rc<str>>,
 }
 
 // Simple builder for a scanner.
@@ -23,6 +35,12 @@ pub fn scanner_builder(instance: crate::Lox, input: String) -> Scanner {
         start: 0,
         current: 0,
         line: 1,
+        start_line: 1,
+        line_start: 0,
+        start_column: 1,
+        keep_comments: false,
+        interner: Interner::default(),
+        strings: HashMap::new(),
     };
     
     scanner.source_chars = scanner.source.chars().collect::<Vec<char>>();
@@ -36,11 +54,14 @@ impl Scanner {
         // While we aren't at the end of the file, set the start to the current and then scan the next token.
         while !self.is_end() {
             self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.current - self.line_start + 1;
             self.scan_token();
         }
 
         // Push a token at the end that represents the end of the file.
-        self.tokens.push(Token {token_type: TokenType::Eof, lexeme: String::from(""), literal: None::<Literal>, line: self.line});
+        let column: u32 = self.current - self.line_start + 1;
+        self.tokens.push(Token {token_type: TokenType::Eof, lexeme: Symbol::detached(""), literal: None::<Literal>, line: self.line, column: column});
 
         return self.tokens.clone();
     }
@@ -99,29 +120,28 @@ impl Scanner {
                 if self.find_next('/') {
                     while self.peak() != '\n' && !self.is_end() {
                         self.advance();
-                    } 
+                    }
+                    self.add_comment();
 
                 // Block comment?
                 } else if self.find_next('*') {
-                    // 
                     loop {
-                        self.advance();
-
-                        if self.peak() == '\n' {
-                            self.line = self.line + 1;
+                        if self.is_end() {
+                            self.instance.scanner_error(self.line, "Undetermined Block Comment");
+                            break;
                         }
 
-                        if self.peak() == '*' {
+                        if self.peak() == '*' && self.peak_next() == '/' {
                             self.advance();
-                            if self.peak() == '/' {
-                                self.advance();
-                                break;
-                            }
+                            self.advance();
+                            self.add_comment();
+                            break;
                         }
 
-                        if self.is_end() {
-                            self.instance.scanner_error(self.line, "Undetermined Block Comment");
+                        if self.peak() == '\n' {
+                            self.new_line();
                         }
+                        self.advance();
                     }
                     
                 // Div?
@@ -133,7 +153,10 @@ impl Scanner {
             ' ' => {},
             '\r' => {},
             '\t' => {},
-            '\n' => {self.line = self.line + 1},
+            '\n' => {
+                self.line += 1;
+                self.line_start = self.current;
+            },
             _ => {
                 // If it is a digit
                 if Self::is_digit(c) {
@@ -153,20 +176,37 @@ impl Scanner {
 
     // Are we at the end of the file/prompt?
     fn is_end(&self) -> bool {
-        return self.current >= self.source.len() as u32;
+        return self.current >= self.source_chars.len() as u32;
     }
 
     fn advance(&mut self) -> char {
-        self.current = self.current + 1;
+        self.current += 1;
         return self.source_chars[(self.current - 1) as usize];
     }
 
     fn add_token(&mut self, token_type: TokenType, literal: Option<Literal>) {
-        // Declare the lexeme.
-        let lexeme: String = self.source.get(self.start as usize..self.current as usize).unwrap().to_string();
+        // Declare the lexeme, strings, numbers and comments are never looked up so they aren't interned
+        let text: String = self.text(self.start, self.current);
+        let lexeme: Symbol = match token_type {
+            TokenType::String | TokenType::Num | TokenType::Comment => Symbol::detached(&text),
+            _ => self.interner.intern(&text)
+        };
 
         // Push the token with the new lexeme.
-        self.tokens.push(Token {token_type: token_type, lexeme: lexeme, literal: literal, line: self.line});
+        self.tokens.push(Token {token_type: token_type, lexeme: lexeme, literal: literal, line: self.start_line, column: self.start_column});
+    }
+
+    // A comment is only a token when they are being kept, line comments without the end of their line
+    fn add_comment(&mut self) {
+        if self.keep_comments {
+            self.add_token(TokenType::Comment, None);
+        }
+    }
+
+    // The next char is a new line, which starts after it
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.line_start = self.current + 1;
     }
     
     fn find_next(&mut self, expected: char) -> bool {
@@ -178,10 +218,15 @@ impl Scanner {
             return false;
         }
 
-        self.current = self.current + 1;
+        self.current += 1;
         return true;
     }
 
+    // The source between two character positions, the positions count chars rather than bytes
+    fn text(&self, start: u32, end: u32) -> String {
+        return self.source_chars[start as usize..end as usize].iter().collect();
+    }
+
     // This finds the next, or peaks, the next character
     fn peak(&self) -> char {
         if self.is_end() {
@@ -195,7 +240,7 @@ impl Scanner {
         // While we are before the closing " and we're not at the end, look for new lines and advance
         while self.peak() != '"' && !self.is_end() {
             if self.peak() == '\n' {
-                self.line = self.line + 1;
+                self.new_line();
             }
             self.advance();
         }
@@ -207,7 +252,9 @@ impl Scanner {
         }
 
         self.advance();
-        let value: String = self.source.get((self.start + 1) as usize..(self.current -1) as usize).unwrap().to_string();
+        let value: String = self.text(self.start + 1, self.current - 1);
+        // The same string in the source is only stored once
+        let value: Rc<str> = self.strings.entry(value).or_insert_with_key(|a| Rc::from(a.as_str())).clone();
         self.add_token(TokenType::String, Some(Literal::Str(value)));
     }
 
@@ -226,17 +273,17 @@ impl Scanner {
             }
         }
 
-        let num: String = self.source.get(self.start as usize..self.current as usize).unwrap().to_string();
+        let num: String = self.text(self.start, self.current);
         self.add_token(TokenType::Num, Some(Literal::Num(num.parse().unwrap())));
     }
 
     fn is_digit(c: char) -> bool {
-        return c >= '0' && c <= '9';
+        return c.is_ascii_digit();
     }
 
     fn peak_next(&self) -> char {
         // If the count is greater than or equal to the length of source length
-        if self.current + 1 >= self.source.len() as u32 {
+        if self.current + 1 >= self.source_chars.len() as u32 {
             return '\0'
         }
 
@@ -267,22 +314,20 @@ impl Scanner {
         keywords.insert("true", TokenType::True);
         keywords.insert("var", TokenType::Var);
         keywords.insert("while", TokenType::While);
+        keywords.insert("try", TokenType::Try);
+        keywords.insert("catch", TokenType::Catch);
+        keywords.insert("finally", TokenType::Finally);
+        keywords.insert("throw", TokenType::Throw);
 
         // Locate the string relating to this hashmap
-        let text: &str = self.source.get(self.start as usize..self.current as usize).unwrap();
-        let token_type: &TokenType;
-
+        let text: String = self.text(self.start, self.current);
         // Match the keywords
-        match keywords.get(text) {
+        let token_type: &TokenType = match keywords.get(&*text) {
             // If it is basically any of the above, go ahead and unwrap it and declare token_type as that token.
-            Some(a) => {
-                token_type = a;
-            }
+            Some(a) => a,
             // If it is none of the above, set it as an ID
-            None => {
-                token_type = &TokenType::Id;
-            }
-        }
+            None => &TokenType::Id
+        };
 
         // Add the token.
         self.add_token(token_type.clone(), None);
@@ -290,7 +335,7 @@ impl Scanner {
 
     // Is this Char an Alpha (or _)
     fn is_alpha(c: char) -> bool {
-        return (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_';
+        return c.is_ascii_alphabetic() || c == '_';
     }
 
     // Is this Char either an Alpha/_ or a Numeric?
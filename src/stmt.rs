@@ -1,3 +1,4 @@
+use std::rc::Rc;
 use crate::expression::Expr;
 use crate::token::Token;
 
@@ -22,27 +23,55 @@ pub enum Stmt {
         else_branch: Option<Box<Stmt>>
     },
     While {
+        keyword: Token,
         condition: Expr,
         body: Box<Stmt>
+    },
+    Try {
+        try_branch: Vec<Stmt>,
+        name: Option<Token>,
+        catch_branch: Option<Vec<Stmt>>,
+        finally_branch: Option<Vec<Stmt>>
+    },
+    Throw {
+        keyword: Token,
+        value: Expr
+    },
+    Function {
+        name: Token,
+        params: Vec<Token>,
+        body: Rc<Vec<Stmt>>
+    },
+    Return {
+        keyword: Token,
+        value: Option<Expr>
     }
 }
 
 pub trait StmtVisitor<> {
-    fn execute(&mut self, stmt: Stmt) {
+    fn execute(&mut self, stmt: &Stmt) {
         match stmt {
             Stmt::Expression {expression: a} => self.visit_expression(a),
             Stmt::Print {expression: a} => self.visit_print(a),
-            Stmt::Var {name: a, right: b} => self.visit_var(a, b),
+            Stmt::Var {name: a, right: b} => self.visit_var(a, b.as_ref()),
             Stmt::Block {statements: a} => self.visit_block(a),
-            Stmt::If {condition: a, then_branch: b, else_branch: c} => self.visit_if(a, b, c),
-            Stmt::While {condition: a, body: b} => self.visit_while(a, b),
+            Stmt::If {condition: a, then_branch: b, else_branch: c} => self.visit_if(a, b, c.as_deref()),
+            Stmt::While {keyword: a, condition: b, body: c} => self.visit_while(a, b, c),
+            Stmt::Try {try_branch: a, name: b, catch_branch: c, finally_branch: d} => self.visit_try(a, b.as_ref(), c.as_deref(), d.as_deref()),
+            Stmt::Throw {keyword: a, value: b} => self.visit_throw(a, b),
+            Stmt::Function {name: a, params: b, body: c} => self.visit_function(a, b, c),
+            Stmt::Return {keyword: a, value: b} => self.visit_return(a, b.as_ref()),
         }
     }
 
-    fn visit_expression(&mut self, expression: Expr);
-    fn visit_print(&mut self, expression: Expr);
-    fn visit_var(&mut self, name: Token, right: Option<Expr>);
-    fn visit_block(&mut self, statements: Vec<Stmt>);
-    fn visit_if(&mut self, condition: Expr, then_branch: Box<Stmt>, else_branch: Option<Box<Stmt>>);
-    fn visit_while(&mut self, condition: Expr, body: Box<Stmt>);
-}
\ No newline at end of file
+    fn visit_expression(&mut self, expression: &Expr);
+    fn visit_print(&mut self, expression: &Expr);
+    fn visit_var(&mut self, name: &Token, right: Option<&Expr>);
+    fn visit_block(&mut self, statements: &[Stmt]);
+    fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>);
+    fn visit_while(&mut self, keyword: &Token, condition: &Expr, body: &Stmt);
+    fn visit_try(&mut self, try_branch: &[Stmt], name: Option<&Token>, catch_branch: Option<&[Stmt]>, finally_branch: Option<&[Stmt]>);
+    fn visit_throw(&mut self, keyword: &Token, value: &Expr);
+    fn visit_function(&mut self, name: &Token, params: &[Token], body: &Rc<Vec<Stmt>>);
+    fn visit_return(&mut self, keyword: &Token, value: Option<&Expr>);
+}
@@ -1,12 +1,22 @@
 use std::fmt;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::intern::Symbol;
+use crate::callable::LoxCallable;
+use crate::error::RuntimeError;
 
 #[derive(Clone, Debug)]
 pub enum Literal {
-    Str(String),
+    Str(Rc<str>),
     Num(f64),
     False,
     True,
-    Nill
+    Nill,
+    Callable(Rc<dyn LoxCallable>),
+    List(Rc<RefCell<Vec<Literal>>>),
+    Map(Rc<RefCell<HashMap<String, Literal>>>),
+    Error(Rc<RuntimeError>)
 }
 
 // Each type of Token
@@ -59,27 +69,69 @@ pub enum TokenType {
     True,
     Var,
     While,
+    Try,
+    Catch,
+    Finally,
+    Throw,
+
+    // Only scanned for the formatter, the parser never sees them
+    Comment,
     Eof
 }
 
-// Token struct, consisting of a TokenType, a String, a Literal (if applicable) and a corresponding line (for error checking).
+// Token struct, consisting of a TokenType, a String, a Literal (if applicable) and where it starts: a line
+// (for error checking) and a column, counted in chars from 1. Tokens made up outside of the scanner have column 0.
 #[derive(Clone, Debug)]
 pub struct Token {
     pub token_type: TokenType,
-    pub lexeme: String,
+    pub lexeme: Symbol,
     pub literal: Option<Literal>,
     pub line: u32,
+    pub column: u32,
 }
 
 // Display a Literal
 impl fmt::Display for Literal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.display(&mut Vec::new()))
+    }
+}
+
+impl Literal {
+    // Display a value inside the lists and maps in "seen", any of which it holds again shows as [...] or {...}
+    fn display(&self, seen: &mut Vec<*const ()>) -> String {
         match self {
-            Self::Str(a) => write!(f, "{:?}", a),
-            Self::Num(a) => write!(f, "{:?}", a),
-            Self::False => write!(f, "false"),
-            Self::True => write!(f, "true"),
-            Self::Nill => write!(f, "nill")
+            Self::Str(a) => format!("{:?}", a),
+            Self::Num(a) => format!("{:?}", a),
+            Self::False => "false".to_string(),
+            Self::True => "true".to_string(),
+            Self::Nill => "nill".to_string(),
+            Self::Callable(a) => format!("{:?}", a),
+            Self::List(a) => {
+                let pointer: *const () = Rc::as_ptr(a) as *const ();
+                if seen.contains(&pointer) {
+                    return "[...]".to_string();
+                }
+                seen.push(pointer);
+                let items: Vec<String> = a.borrow().iter().map(|b| b.display(seen)).collect();
+                seen.pop();
+                format!("[{}]", items.join(", "))
+            }
+            Self::Map(a) => {
+                let pointer: *const () = Rc::as_ptr(a) as *const ();
+                if seen.contains(&pointer) {
+                    return "{...}".to_string();
+                }
+                // Keys are sorted so the same map always displays the same way
+                seen.push(pointer);
+                let map = a.borrow();
+                let mut keys: Vec<&String> = map.keys().collect();
+                keys.sort();
+                let items: Vec<String> = keys.iter().map(|b| format!("{:?}: {}", b, map[*b].display(seen))).collect();
+                seen.pop();
+                format!("{{{}}}", items.join(", "))
+            }
+            Self::Error(a) => format!("<error {:?}>", a.message)
         }
     }
 }
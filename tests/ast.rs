@@ -0,0 +1,66 @@
+use crlox::{Diagnostic, DumpFormat, Interpreter};
+
+/*
+Syntax tree dumps
+
+What `crlox ast` and --dump-ast print: the statements as parsed, before they are optimized, as
+S-expressions.
+*/
+
+fn ast(source: &str) -> String {
+    Interpreter::default().dump_ast(source, DumpFormat::Text).unwrap_or_else(|a| panic!("{} failed: {}", source, a[0]))
+}
+
+#[test]
+fn operators_group_by_precedence() {
+    assert_eq!(ast("print 1 + 2 * 3;"), "(print (+ 1 (* 2 3)))\n");
+    assert_eq!(ast("print (1 + 2) * 3;"), "(print (* (group (+ 1 2)) 3))\n");
+    assert_eq!(ast("print 1 - 2 - 3;"), "(print (- (- 1 2) 3))\n");
+    assert_eq!(ast("print -a < 2 == !b;"), "(print (== (< (- a) 2) (! b)))\n");
+    assert_eq!(ast("a = b = c or d and e;"), "(expr (= a (= b (or c (and d e)))))\n");
+    assert_eq!(ast("print f(1, \"s\")(nil).len;"), "(print (. (call (call f 1 \"s\") nil) len))\n");
+}
+
+// Constants are left as written, the optimizer hasn't been run
+#[test]
+fn the_tree_is_not_optimized() {
+    assert_eq!(ast("print 2 * 3;"), "(print (* 2 3))\n");
+    assert_eq!(ast("if (true) print 1;"), "(if true\n  (print 1))\n");
+}
+
+// A for loop comes out as the while loop it stands for
+#[test]
+fn for_loops_become_while_loops() {
+    assert_eq!(ast("for (var i = 0; i < 2; i = i + 1) print i;"), "\
+(block
+  (var i 0)
+  (while (< i 2)
+    (block
+      (print i)
+      (expr (= i (+ i 1))))))
+");
+    assert_eq!(ast("for (;;) print 1;"), "(while true\n  (print 1))\n");
+}
+
+#[test]
+fn statements_nest_by_indent() {
+    let source: &str = "fun f(a, b) { if (a) return b; else return; }\ntry { throw 1; } catch (e) { var x; } finally { print e; }";
+    assert_eq!(ast(source), "\
+(fun f (a b)
+  (if a
+    (return b)
+    (return)))
+(try
+  (throw 1)
+  (catch e
+    (var x))
+  (finally
+    (print e)))
+");
+}
+
+#[test]
+fn syntax_errors_are_reported() {
+    let diagnostics: Vec<Diagnostic> = Interpreter::default().dump_ast("print (1;", DumpFormat::Text).expect_err("fails");
+    assert_eq!(diagnostics[0].to_string(), "[line 1] Error at ';': Expect ')' after expression.");
+}
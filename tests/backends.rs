@@ -0,0 +1,139 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/*
+Backends
+
+The tree-walker and the virtual machine are meant to behave the same, so the same programs are run
+on both and what they write and how they exit compared. Besides the test scripts, programs are
+generated from a fixed seed. They are small and mix types freely, so many of them fail part way
+through, which is where the backends are most likely to differ.
+*/
+
+// A small deterministic generator, so that a failure can be reproduced
+struct Generator {
+    state: u64,
+    depth: usize
+}
+
+const VARIABLES: [&str; 3] = ["a", "b", "c"];
+
+impl Generator {
+    fn next(&mut self, below: usize) -> usize {
+        self.state = self.state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((self.state >> 33) % below as u64) as usize
+    }
+
+    fn expression(&mut self) -> String {
+        if self.depth > 3 {
+            return self.atom();
+        }
+        self.depth += 1;
+        let expression: String = match self.next(8) {
+            0..=2 => self.atom(),
+            3 => format!("-{}", self.expression()),
+            4 => format!("!{}", self.expression()),
+            5 => format!("f({})", self.expression()),
+            _ => {
+                let operator: &str = ["+", "-", "*", "/", "<", ">=", "==", "!=", "and", "or"][self.next(10)];
+                format!("({} {} {})", self.expression(), operator, self.expression())
+            }
+        };
+        self.depth -= 1;
+        expression
+    }
+
+    fn atom(&mut self) -> String {
+        match self.next(7) {
+            0 => "nil".to_string(),
+            1 => ["true", "false"][self.next(2)].to_string(),
+            2 => format!("\"s{}\"", self.next(3)),
+            3 | 4 => VARIABLES[self.next(3)].to_string(),
+            _ => format!("{}", self.next(4))
+        }
+    }
+
+    fn statement(&mut self) -> String {
+        if self.depth > 2 {
+            return format!("print {};", self.expression());
+        }
+        self.depth += 1;
+        let statement: String = match self.next(9) {
+            0 | 1 => format!("print {};", self.expression()),
+            2 => format!("{} = {};", VARIABLES[self.next(3)], self.expression()),
+            3 => format!("if ({}) {} else {}", self.expression(), self.statement(), self.statement()),
+            4 => format!("{{ var {} = {}; {} }}", VARIABLES[self.next(3)], self.expression(), self.statement()),
+            5 => format!("for (var i = 0; i < 3; i = i + 1) {{ {} }}", self.statement()),
+            6 => format!("try {{ {} }} catch (e) {{ print \"caught \" + e.message; }}", self.statement()),
+            7 => format!("try {{ {} }} finally {{ print \"finally\"; }}", self.statement()),
+            _ => format!("throw {};", self.expression())
+        };
+        self.depth -= 1;
+        statement
+    }
+
+    fn program(&mut self) -> String {
+        let mut program: String = String::from("var a = 1; var b = \"s\"; var c = nil;\n");
+        program.push_str(&format!("fun f(x) {{ {} return {}; }}\n", self.statement(), self.expression()));
+        for _ in 0..6 {
+            program.push_str(&self.statement());
+            program.push('\n');
+        }
+        program
+    }
+}
+
+fn run(script: &Path, vm: bool) -> Output {
+    let mut command: Command = Command::new(env!("CARGO_BIN_EXE_crlox"));
+    if vm {
+        command.arg("--vm");
+    }
+    command.arg(script).output().expect("crlox to run")
+}
+
+// Run a script on both backends, giving back how they differed if they did
+fn compare(script: &Path) -> Option<String> {
+    let (walked, compiled) = (run(script, false), run(script, true));
+    if walked.stdout == compiled.stdout && walked.stderr == compiled.stderr && walked.status.code() == compiled.status.code() {
+        return None;
+    }
+    Some(format!(
+        "{}:\n{}\n  tree-walker {:?}: {:?} {:?}\n  vm {:?}: {:?} {:?}",
+        script.display(), fs::read_to_string(script).unwrap_or_default(),
+        walked.status.code(), String::from_utf8_lossy(&walked.stdout), String::from_utf8_lossy(&walked.stderr),
+        compiled.status.code(), String::from_utf8_lossy(&compiled.stdout), String::from_utf8_lossy(&compiled.stderr)
+    ))
+}
+
+#[test]
+fn scripts_agree() {
+    let directory: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("scripts");
+    let differences: Vec<String> = fs::read_dir(directory).expect("tests/scripts")
+        .filter_map(|a| a.ok().map(|b| b.path()))
+        .filter_map(|a| compare(&a))
+        .collect();
+    assert!(differences.is_empty(), "\n{}", differences.join("\n"));
+}
+
+#[test]
+fn generated_programs_agree() {
+    let directory: PathBuf = std::env::temp_dir().join(format!("crlox-backends-{}", std::process::id()));
+    fs::create_dir_all(&directory).expect("creates the directory");
+    let mut generator: Generator = Generator {state: 2024, depth: 0};
+    let mut differences: Vec<String> = Vec::new();
+    let mut failed: usize = 0;
+    for i in 0..300 {
+        let script: PathBuf = directory.join(format!("{}.lox", i));
+        fs::write(&script, generator.program()).expect("writes the program");
+        if run(&script, false).status.code() != Some(0) {
+            failed += 1;
+        }
+        differences.extend(compare(&script));
+    }
+    fs::remove_dir_all(&directory).ok();
+
+    assert!(differences.is_empty(), "{} of 300 differ\n{}", differences.len(), differences.iter().take(5).cloned().collect::<Vec<String>>().join("\n"));
+    // Failing programs are the point, make sure there are plenty
+    assert!(failed > 50, "only {} programs failed", failed);
+}
@@ -0,0 +1,72 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use crlox::{Backend, Diagnostic, DiagnosticKind, Interpreter};
+
+// An interpreter on the given backend, with what it prints collected into "printed"
+fn interpreter(backend: Backend, printed: &Rc<RefCell<String>>) -> Interpreter {
+    let mut interpreter: Interpreter = Interpreter::default();
+    interpreter.set_backend(backend);
+    let printed: Rc<RefCell<String>> = printed.clone();
+    interpreter.set_output_callback(move |a| printed.borrow_mut().push_str(a));
+    interpreter
+}
+
+fn aborted(result: Result<crlox::Value, Vec<Diagnostic>>) -> String {
+    let diagnostics: Vec<Diagnostic> = result.expect_err("is stopped");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].kind, DiagnosticKind::Aborted);
+    diagnostics[0].message.clone()
+}
+
+// The step limit stops a script at the same point on either backend, and try can't catch it
+#[test]
+fn steps_are_counted_alike() {
+    let source: &str = "fun f(n) { print \"call \" + n; }\n\
+                        try { for (var i = 0; i < 100; i = i + 1) { print i; f(i); } } catch (e) { print \"caught\"; }";
+    let mut outputs: Vec<String> = Vec::new();
+    for backend in [Backend::TreeWalker, Backend::Vm] {
+        let printed: Rc<RefCell<String>> = Rc::new(RefCell::new(String::new()));
+        let mut interpreter: Interpreter = interpreter(backend, &printed);
+        interpreter.set_max_steps(Some(10));
+        assert_eq!(aborted(interpreter.run(source)), "Execution step limit of 10 exceeded.");
+        outputs.push(printed.take());
+    }
+
+    // Each pass makes a call and goes round the loop, two steps, so the sixth call is the eleventh step
+    assert_eq!(outputs[0], "0\ncall 0\n1\ncall 1\n2\ncall 2\n3\ncall 3\n4\ncall 4\n5\n");
+    assert_eq!(outputs[0], outputs[1]);
+}
+
+#[test]
+fn timeout_stops_a_loop() {
+    for backend in [Backend::TreeWalker, Backend::Vm] {
+        let printed: Rc<RefCell<String>> = Rc::new(RefCell::new(String::new()));
+        let mut interpreter: Interpreter = interpreter(backend, &printed);
+        interpreter.set_timeout(Duration::from_millis(50));
+        assert_eq!(aborted(interpreter.run("while (true) {}")), "Execution timed out.");
+
+        // Without a deadline it runs as usual
+        interpreter.set_deadline(None);
+        interpreter.run("print 1;").expect("runs");
+    }
+}
+
+#[test]
+fn cancel_flag_stops_the_script() {
+    for backend in [Backend::TreeWalker, Backend::Vm] {
+        let printed: Rc<RefCell<String>> = Rc::new(RefCell::new(String::new()));
+        let mut interpreter: Interpreter = interpreter(backend, &printed);
+        let flag = interpreter.cancel_flag();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            flag.store(true, Ordering::Relaxed);
+        });
+        assert_eq!(aborted(interpreter.run("while (true) {}")), "Execution cancelled.");
+        canceller.join().expect("cancels");
+
+        interpreter.cancel_flag().store(false, Ordering::Relaxed);
+        interpreter.run("print 1;").expect("runs");
+    }
+}
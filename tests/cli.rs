@@ -0,0 +1,107 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/*
+Command line
+
+How the crlox binary reads its arguments and what it writes for the modes that don't run a script
+the usual way.
+*/
+
+fn crlox(arguments: &[&str], directory: &PathBuf) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_crlox")).args(arguments).current_dir(directory).output().expect("crlox to run")
+}
+
+fn text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).to_string()
+}
+
+// A directory of its own for each test, holding the scripts it writes
+fn directory(name: &str) -> PathBuf {
+    let directory: PathBuf = std::env::temp_dir().join(format!("crlox-cli-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&directory).expect("creates the directory");
+    directory
+}
+
+// Scripts named like a subcommand are run when -- comes before them
+#[test]
+fn double_dash_runs_a_script_named_like_a_subcommand() {
+    let directory: PathBuf = directory("subcommands");
+    for name in ["compile", "fmt", "tokens", "ast"] {
+        fs::write(directory.join(name), format!("print \"{} ran\";\n", name)).expect("writes the script");
+        let output: Output = crlox(&["--", name], &directory);
+        assert_eq!(text(&output.stdout), format!("{} ran\n", name));
+        assert_eq!(output.status.code(), Some(0));
+
+        // Options still come first
+        let output: Output = crlox(&["--vm", "--", name, "argument"], &directory);
+        assert_eq!(text(&output.stdout), format!("{} ran\n", name));
+    }
+
+    // Without it the name is the subcommand, here formatting the script named fmt
+    let output: Output = crlox(&["fmt", "--check", "fmt"], &directory);
+    assert_eq!(text(&output.stdout), "");
+    assert_eq!(output.status.code(), Some(0));
+    fs::remove_dir_all(&directory).ok();
+}
+
+// --disassemble lists the bytecode of the script and of each function in it, without running it
+#[test]
+fn disassemble_lists_each_function() {
+    let directory: PathBuf = directory("disassemble");
+    fs::write(directory.join("add.lox"), "fun f(a) { return a + 1; }\nprint f(2);\n").expect("writes the script");
+    let output: Output = crlox(&["--disassemble", "add.lox"], &directory);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(text(&output.stdout), "\
+== script ==
+0000    1 Closure             0 <fn f> []
+0003    | DefineGlobal        1 f
+0006    2 GetGlobal           1 f
+0009    | Constant            2 2
+0012    | Call                1
+0014    | Print
+0015    | Nil
+0016    | Return
+
+== f ==
+0000    1 GetLocal            1
+0002    | Constant            0 1
+0005    | Add
+0006    | Return
+0007    | Nil
+0008    | Return
+");
+
+    // Syntax errors are reported as when running
+    fs::write(directory.join("broken.lox"), "print;\n").expect("writes the script");
+    let output: Output = crlox(&["--disassemble", "broken.lox"], &directory);
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(text(&output.stdout), "");
+    assert_eq!(text(&output.stderr), "[line 1] Error at ';': Expect expression.\n");
+    fs::remove_dir_all(&directory).ok();
+}
+
+// --trace-exec runs on the VM, writing the stack and then each instruction to stderr as it goes
+#[test]
+fn trace_exec_writes_each_instruction() {
+    let directory: PathBuf = directory("trace");
+    fs::write(directory.join("add.lox"), "fun f(a) { return a + 1; }\nprint f(2);\n").expect("writes the script");
+    let output: Output = crlox(&["--trace-exec", "add.lox"], &directory);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(text(&output.stdout), "3\n");
+
+    let trace: String = text(&output.stderr);
+    let lines: Vec<&str> = trace.lines().collect();
+    assert_eq!(lines[0], "          [ nill ]");
+    assert_eq!(lines[1], "0000    1 Closure             0 <fn f> []");
+    // Calls step into the function and back out again
+    assert!(trace.contains("0012    | Call                1\n          [ nill ][ <fn f> ][ 2 ]\n0000    1 GetLocal            1\n"), "{}", trace);
+    assert!(trace.contains("0005    | Add\n          [ nill ][ <fn f> ][ 2 ][ 3 ]\n0006    | Return\n          [ nill ][ 3 ]\n0014    | Print\n"), "{}", trace);
+    assert_eq!(lines.last(), Some(&"0016    | Return"));
+
+    // Without the flag nothing is traced
+    let output: Output = crlox(&["--vm", "add.lox"], &directory);
+    assert_eq!(text(&output.stderr), "");
+    fs::remove_dir_all(&directory).ok();
+}
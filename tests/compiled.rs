@@ -0,0 +1,67 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use crlox::{Diagnostic, Interpreter};
+
+/*
+Compiled files
+
+Scripts compiled with crlox compile run the same as their source does, and files that have been
+damaged are refused rather than run.
+*/
+
+fn temporary(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("crlox-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn round_trip() {
+    let directory: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("scripts");
+    for entry in fs::read_dir(directory).expect("tests/scripts") {
+        let script: PathBuf = entry.expect("an entry").path();
+        let source: String = fs::read_to_string(&script).expect("a readable script");
+        // Those that don't parse can't be compiled, and those that stop early are covered by scripts.rs
+        if source.contains("// exit:") {
+            continue;
+        }
+
+        let compiled: PathBuf = temporary(&format!("{}c", script.file_name().and_then(|a| a.to_str()).unwrap_or("script")));
+        let status: Output = Command::new(env!("CARGO_BIN_EXE_crlox")).arg("compile").arg(&script).arg("-o").arg(&compiled).output().expect("crlox to run");
+        assert!(status.status.success(), "{} didn't compile", script.display());
+
+        let from_source: Output = Command::new(env!("CARGO_BIN_EXE_crlox")).arg("--vm").arg(&script).output().expect("crlox to run");
+        let from_file: Output = Command::new(env!("CARGO_BIN_EXE_crlox")).arg(&compiled).output().expect("crlox to run");
+        fs::remove_file(&compiled).ok();
+        assert_eq!(from_file.stdout, from_source.stdout, "{} ran differently once compiled", script.display());
+        assert_eq!(from_file.status.code(), Some(0));
+    }
+}
+
+// Load some bytes as a compiled file, giving back why they were refused
+fn refusal(name: &str, bytes: &[u8]) -> String {
+    let path: PathBuf = temporary(name);
+    fs::write(&path, bytes).expect("a writable temporary file");
+    let result: Result<crlox::Value, Vec<Diagnostic>> = Interpreter::default().run_file(path.to_str().expect("a UTF-8 path"));
+    fs::remove_file(&path).ok();
+    match result {
+        Ok(_) => panic!("{} was run", name),
+        Err(a) => a[0].message.clone()
+    }
+}
+
+#[test]
+fn refuses_damaged_files() {
+    let bytes: Vec<u8> = Interpreter::default().compile("var a = 1; print a + 2;").expect("compiles");
+
+    let mut flipped: Vec<u8> = bytes.clone();
+    let last: usize = flipped.len() - 1;
+    flipped[last] ^= 0xff;
+    assert!(refusal("flipped.loxc", &flipped).contains("checksum doesn't match"));
+
+    assert!(refusal("short.loxc", &bytes[..7]).contains("header is cut short"));
+    assert!(refusal("magic.loxc", b"not bytecode at all").contains("not a compiled Lox file"));
+
+    let mut version: Vec<u8> = bytes.clone();
+    version[5] = version[5].wrapping_add(1);
+    assert!(refusal("version.loxc", &version).contains("bytecode format version"));
+}
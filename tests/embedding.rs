@@ -0,0 +1,104 @@
+use crlox::{Diagnostic, DiagnosticKind, Interpreter, Value};
+
+/*
+Embedding
+
+Running Lox from a Rust host through the library: the value a run gives back, the diagnostics it
+reports instead of printing, and globals shared between the host and scripts.
+*/
+
+fn quiet() -> Interpreter {
+    let mut interpreter: Interpreter = Interpreter::default();
+    interpreter.set_output(Vec::new());
+    interpreter.set_error_output(Vec::new());
+    interpreter
+}
+
+fn errors(source: &str) -> Vec<Diagnostic> {
+    quiet().run(source).expect_err("fails")
+}
+
+#[test]
+fn run_gives_the_last_expression() {
+    let mut interpreter: Interpreter = quiet();
+    assert_eq!(Interpreter::stringify(interpreter.run("1 + 2;").expect("runs")), "3");
+    assert_eq!(Interpreter::stringify(interpreter.run("var a = \"x\"; a + a;").expect("runs")), "xx");
+    // Only expression statements give a value
+    assert_eq!(Interpreter::stringify(interpreter.run("print 1;").expect("runs")), "nill");
+    assert_eq!(Interpreter::stringify(interpreter.run("").expect("runs")), "nill");
+}
+
+// State carries over from one run to the next, as in the REPL
+#[test]
+fn runs_share_state() {
+    let mut interpreter: Interpreter = quiet();
+    interpreter.run("var count = 0; fun bump() { count = count + 1; return count; }").expect("runs");
+    interpreter.run("bump();").expect("runs");
+    assert_eq!(Interpreter::stringify(interpreter.run("bump();").expect("runs")), "2");
+}
+
+#[test]
+fn syntax_errors_are_diagnostics() {
+    let diagnostics: Vec<Diagnostic> = errors("var a = \"open;\nprint 1 +;");
+    assert_eq!(diagnostics[0].kind, DiagnosticKind::Scan);
+    assert!(diagnostics.iter().all(|a| a.is_syntax_error()));
+
+    let diagnostics: Vec<Diagnostic> = errors("print 1;\nprint 1 +;");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].kind, DiagnosticKind::Parse);
+    assert_eq!(diagnostics[0].line, 2);
+    assert_eq!(diagnostics[0].location, " at ';'");
+    assert_eq!(diagnostics[0].message, "Expect expression.");
+    assert_eq!(diagnostics[0].to_string(), "[line 2] Error at ';': Expect expression.");
+}
+
+// Nothing runs when there is a syntax error anywhere in the source
+#[test]
+fn syntax_errors_stop_everything() {
+    let mut interpreter: Interpreter = quiet();
+    interpreter.run("var ran = false;").expect("runs");
+    interpreter.run("ran = true;\nprint;").expect_err("fails");
+    assert_eq!(Interpreter::stringify(interpreter.get_global("ran").expect("is defined")), "false");
+}
+
+#[test]
+fn runtime_errors_are_diagnostics() {
+    let diagnostics: Vec<Diagnostic> = errors("fun f() {\n  return -\"a\";\n}\nf();");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].kind, DiagnosticKind::Runtime);
+    assert_eq!(diagnostics[0].line, 2);
+    assert!(!diagnostics[0].is_syntax_error());
+    assert_eq!(diagnostics[0].traceback, vec!["[line 4] in script", "[line 2] in f()"]);
+
+    let diagnostics: Vec<Diagnostic> = errors("exit(3);");
+    assert_eq!(diagnostics[0].kind, DiagnosticKind::Exit(3));
+    assert_eq!(diagnostics[0].exit_status(), Some(3));
+}
+
+#[test]
+fn globals_are_shared_with_the_host() {
+    let mut interpreter: Interpreter = quiet();
+    interpreter.set_global("limit", Value::Num(10.0));
+    interpreter.run("var doubled = limit * 2; limit = 1;").expect("runs");
+    assert_eq!(Interpreter::stringify(interpreter.get_global("doubled").expect("is defined")), "20");
+    assert_eq!(Interpreter::stringify(interpreter.get_global("limit").expect("is defined")), "1");
+
+    // Natives are globals like any other
+    assert!(interpreter.get_global("clock").is_some());
+    assert!(interpreter.get_global("undefined").is_none());
+
+    // Defining a global again replaces it
+    interpreter.define_global("limit", Value::Str("high".into()));
+    assert_eq!(Interpreter::stringify(interpreter.run("limit;").expect("runs")), "high");
+}
+
+// Each interpreter has its own globals
+#[test]
+fn interpreters_are_independent() {
+    let mut first: Interpreter = quiet();
+    let mut second: Interpreter = quiet();
+    first.run("var a = 1;").expect("runs");
+    assert!(second.get_global("a").is_none());
+    let diagnostics: Vec<Diagnostic> = second.run("a;").expect_err("fails");
+    assert_eq!(diagnostics[0].message, "Undefined variable a.");
+}
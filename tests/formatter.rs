@@ -0,0 +1,50 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crlox::Interpreter;
+
+/*
+Formatter
+
+Formatting lays source out one way, which formatting again leaves as it is.
+*/
+
+fn format(source: &str) -> String {
+    Interpreter::default().format(source).expect("formats")
+}
+
+#[test]
+fn lays_out_source() {
+    let messy: &str = "var a=1;fun f(x,y){if(x>y){return x;}else{return -y;}}\n\n\n// note\nprint f(a,2); // trailing\n{}\n";
+    let expected: &str = "var a = 1;\nfun f(x, y) {\n    if (x > y) {\n        return x;\n    } else {\n        return -y;\n    }\n}\n\n// note\nprint f(a, 2); // trailing\n{}\n";
+    assert_eq!(format(messy), expected);
+}
+
+#[test]
+fn breaks_long_lines() {
+    let long: String = format!("print {};\n", vec!["1000000"; 20].join(" + "));
+    let formatted: String = format(&long);
+    assert!(formatted.lines().count() > 1);
+    assert!(formatted.lines().all(|a| a.chars().count() <= 100), "{}", formatted);
+    assert_eq!(format(&formatted), formatted);
+}
+
+// Every script in the repository, formatted twice, comes out the same both times
+#[test]
+fn is_idempotent() {
+    let root: &Path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut checked: usize = 0;
+    for directory in [root.join("tests").join("scripts"), root.join("bench")] {
+        for entry in fs::read_dir(directory).expect("a directory of scripts") {
+            let script: PathBuf = entry.expect("an entry").path();
+            let source: String = fs::read_to_string(&script).expect("a readable script");
+            // Scripts that don't parse can't be formatted
+            let once: String = match Interpreter::default().format(&source) {
+                Ok(a) => a,
+                Err(_) => continue
+            };
+            assert_eq!(format(&once), once, "{} changed when formatted again", script.display());
+            checked += 1;
+        }
+    }
+    assert!(checked > 0);
+}
@@ -0,0 +1,75 @@
+use crlox::{Interpreter, Value};
+
+// Cycles nothing can reach are freed by a collection, while those still in use survive it
+#[test]
+fn frees_unreachable_cycles() {
+    let mut interpreter: Interpreter = Interpreter::default();
+    interpreter.set_gc_threshold(usize::MAX);
+    let source: &str = "for (var i = 0; i < 50; i = i + 1) { var list = \"x\".split(\",\"); list.push(list); }\n\
+                        var kept = \"y\".split(\",\"); kept.push(kept);";
+    interpreter.run(source).expect("runs");
+
+    let freed: usize = interpreter.collect_garbage();
+    assert!(freed >= 50, "freed {}", freed);
+
+    // What survived is counted, and another collection finds nothing more to free
+    let live: usize = interpreter.live_objects();
+    assert!(live > 0);
+    assert_eq!(interpreter.collect_garbage(), 0);
+    assert_eq!(interpreter.live_objects(), live);
+
+    let length: Value = interpreter.run("kept.get(1).get(1).len;").expect("kept is still usable");
+    assert_eq!(Interpreter::stringify(length), "2");
+}
+
+// Closures declared in a function close over its scope, which holds them in turn
+#[test]
+fn frees_closures_that_outlive_their_scope() {
+    for vm in [false, true] {
+        let mut interpreter: Interpreter = Interpreter::default();
+        if vm {
+            interpreter.set_backend(crlox::Backend::Vm);
+        }
+        interpreter.set_gc_threshold(usize::MAX);
+        let source: &str = "fun make() { var other; fun first() { return other; } fun second() { return first; } other = second; return first; }\n\
+                            for (var i = 0; i < 20; i = i + 1) { make(); }";
+        interpreter.run(source).expect("runs");
+        assert!(interpreter.collect_garbage() >= 20);
+
+        // Nothing left to free
+        assert_eq!(interpreter.collect_garbage(), 0);
+    }
+}
+
+// A caught error whose value holds the error again is freed along with that value
+#[test]
+fn frees_cycles_through_errors() {
+    for vm in [false, true] {
+        let mut interpreter: Interpreter = Interpreter::default();
+        if vm {
+            interpreter.set_backend(crlox::Backend::Vm);
+        }
+        interpreter.set_gc_threshold(usize::MAX);
+        let source: &str = "for (var i = 0; i < 20; i = i + 1) { var list = \"x\".split(\",\"); try { throw list; } catch (e) { list.push(e); } }\n\
+                            var kept = \"y\".split(\",\"); try { throw kept; } catch (e) { kept.push(e); }";
+        interpreter.run(source).expect("runs");
+
+        // Each list and the error holding it
+        assert!(interpreter.collect_garbage() >= 40);
+        assert_eq!(interpreter.collect_garbage(), 0);
+        let value: Value = interpreter.run("kept.get(1).value.get(0);").expect("kept is still usable");
+        assert_eq!(Interpreter::stringify(value), "y");
+    }
+}
+
+// Collecting on every allocation doesn't change what a script does
+#[test]
+fn stress_keeps_live_values() {
+    let mut interpreter: Interpreter = Interpreter::default();
+    interpreter.set_gc_stress(true);
+    let source: &str = "var all = \"\".split(\",\");\n\
+                        for (var i = 0; i < 30; i = i + 1) { var item = \"a,b\".split(\",\"); item.push(all); all.push(item); }\n\
+                        all.len;";
+    let length: Value = interpreter.run(source).expect("runs");
+    assert_eq!(Interpreter::stringify(length), "31");
+}
@@ -0,0 +1,130 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use crlox::{Backend, Diagnostic, FromValue, Interpreter, IntoValue, RuntimeError, Value};
+
+/*
+Host functions
+
+Rust closures registered with register_fn, Lox functions called back through Interpreter::call,
+and the IntoValue/FromValue conversions between the two.
+*/
+
+fn quiet() -> Interpreter {
+    let mut interpreter: Interpreter = Interpreter::default();
+    interpreter.set_output(Vec::new());
+    interpreter.set_error_output(Vec::new());
+    interpreter
+}
+
+fn run(interpreter: &mut Interpreter, source: &str) -> String {
+    let value: Value = interpreter.run(source).unwrap_or_else(|a| panic!("{} failed: {}", source, a[0]));
+    Interpreter::stringify(value)
+}
+
+fn sum(_: &mut Interpreter, args: &[Value]) -> Result<Value, RuntimeError> {
+    let numbers: Vec<f64> = Vec::from_value(args[0].clone())?;
+    Ok(numbers.iter().sum::<f64>().into_value())
+}
+
+#[test]
+fn scripts_call_host_functions() {
+    for backend in [Backend::TreeWalker, Backend::Vm] {
+        let mut interpreter: Interpreter = quiet();
+        interpreter.set_backend(backend);
+        interpreter.register_fn("sum", 1, sum);
+        interpreter.set_global("numbers", vec![1.0, 2.0].into_value());
+        assert_eq!(run(&mut interpreter, "numbers.push(3); sum(numbers);"), "6");
+        assert_eq!(run(&mut interpreter, "sum;"), "<native fn sum>");
+    }
+}
+
+// A host function's errors are raised at the call, where a script can catch them
+#[test]
+fn host_errors_are_catchable() {
+    let mut interpreter: Interpreter = quiet();
+    interpreter.register_fn("sum", 1, sum);
+    assert_eq!(run(&mut interpreter, "var m; try { sum(1); } catch (e) { m = e.message; } m;"), "Expected a list but got 1.0.");
+    assert_eq!(run(&mut interpreter, "var m; try { sum(\"a,b\".split(\",\")); } catch (e) { m = e.message; } m;"), "Expected a number but got \"a\".");
+
+    let diagnostics: Vec<Diagnostic> = interpreter.run("\n\nsum(nil);").expect_err("fails");
+    assert_eq!(diagnostics[0].line, 3);
+    assert_eq!(diagnostics[0].message, "Expected a list but got nill.");
+
+    let diagnostics: Vec<Diagnostic> = interpreter.run("sum(1, 2);").expect_err("fails");
+    assert_eq!(diagnostics[0].message, "Expected 1 arguments but got 2.");
+}
+
+// Lox functions handed to the host can be called back, with their errors coming back too
+#[test]
+fn host_functions_call_back_into_lox() {
+    let mut interpreter: Interpreter = quiet();
+    interpreter.register_fn("each", 2, |interpreter, args| {
+        let items: Vec<Value> = Vec::from_value(args[0].clone())?;
+        for item in items {
+            interpreter.call(&args[1], vec![item])?;
+        }
+        Ok(().into_value())
+    });
+    run(&mut interpreter, "var seen = \"\"; fun see(x) { seen = seen + x; } each(\"a,b,c\".split(\",\"), see);");
+    assert_eq!(run(&mut interpreter, "seen;"), "abc");
+
+    let message: String = run(&mut interpreter, "fun negate(x) { return -x; } var m; try { each(\"a\".split(\",\"), negate); } catch (e) { m = e.message; } m;");
+    assert_eq!(message, "Str(\"a\") Must be a number...");
+}
+
+#[test]
+fn host_calls_lox_functions() {
+    let mut interpreter: Interpreter = quiet();
+    run(&mut interpreter, "fun add(a, b) { return a + b; } fun pair(a) { return (a + \",\" + a).split(\",\"); }");
+    let add: Value = interpreter.get_global("add").expect("is defined");
+    let result: Value = interpreter.call(&add, vec![1.0.into_value(), 2.0.into_value()]).expect("runs");
+    assert_eq!(f64::from_value(result).expect("is a number"), 3.0);
+
+    let pair: Value = interpreter.get_global("pair").expect("is defined");
+    let list: Vec<String> = Vec::from_value(interpreter.call(&pair, vec!["x".into_value()]).expect("runs")).expect("is a list");
+    assert_eq!(list, vec!["x", "x"]);
+
+    let error: RuntimeError = interpreter.call(&add, vec![1.0.into_value()]).expect_err("fails");
+    assert_eq!(error.message, "Expected 2 arguments but got 1.");
+    let error: RuntimeError = interpreter.call(&1.0.into_value(), Vec::new()).expect_err("fails");
+    assert_eq!(error.message, "Can only call functions and classes.");
+}
+
+#[test]
+fn values_convert_both_ways() {
+    let mut interpreter: Interpreter = quiet();
+    let mut scores: HashMap<String, f64> = HashMap::new();
+    scores.insert("ann".to_string(), 3.0);
+    interpreter.set_global("scores", scores.into_value());
+    interpreter.set_global("names", vec!["a", "b"].into_value());
+    interpreter.set_global("missing", None::<bool>.into_value());
+    assert_eq!(run(&mut interpreter, "scores.get(\"ann\") + names.len;"), "5");
+    assert_eq!(run(&mut interpreter, "missing;"), "nill");
+
+    let back: HashMap<String, Vec<String>> = HashMap::from_value(interpreter.run("scores.set(\"k\", \"x,y\".split(\",\")); scores.remove(\"ann\"); scores;").expect("runs")).expect("converts");
+    assert_eq!(back["k"], vec!["x", "y"]);
+    assert_eq!(Option::<bool>::from_value(Value::Nill).expect("converts"), None);
+    assert!(bool::from_value(interpreter.run("true;").expect("runs")).expect("converts"));
+
+    let error: RuntimeError = String::from_value(1.0.into_value()).expect_err("fails");
+    assert_eq!(error.message, "Expected a string but got 1.0.");
+    let error: RuntimeError = Vec::<f64>::from_value(interpreter.run("\"1,a\".split(\",\");").expect("runs")).expect_err("fails");
+    assert_eq!(error.message, "Expected a number but got \"1\".");
+}
+
+// Lists are copied across, so neither side sees the other's changes
+#[test]
+fn lists_are_copied() {
+    let mut interpreter: Interpreter = quiet();
+    let seen: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(Vec::new()));
+    let sink: Rc<RefCell<Vec<String>>> = seen.clone();
+    interpreter.register_fn("keep", 1, move |_, args| {
+        let mut items: Vec<String> = Vec::from_value(args[0].clone())?;
+        items.push("host".to_string());
+        *sink.borrow_mut() = items.clone();
+        Ok(items.into_value())
+    });
+    assert_eq!(run(&mut interpreter, "var l = \"a\".split(\",\"); var k = keep(l); l.len * 10 + k.len;"), "12");
+    assert_eq!(*seen.borrow(), vec!["a", "host"]);
+}
@@ -0,0 +1,91 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::rc::Rc;
+use crlox::{Diagnostic, Interpreter, Value};
+
+/*
+Files and stdio
+
+The natives scripts use to read and write files, standard input and standard error.
+*/
+
+// A directory of its own for each test, as a Lox string literal along with its path
+fn directory(name: &str) -> (PathBuf, String) {
+    let directory: PathBuf = std::env::temp_dir().join(format!("crlox-io-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&directory).expect("creates the directory");
+    let quoted: String = format!("{:?}", directory.to_string_lossy());
+    (directory, quoted)
+}
+
+fn run(interpreter: &mut Interpreter, source: &str) -> String {
+    let value: Value = interpreter.run(source).unwrap_or_else(|a| panic!("{} failed: {}", source, a[0]));
+    Interpreter::stringify(value)
+}
+
+#[test]
+fn files_are_written_and_read_back() {
+    let (directory, quoted) = directory("files");
+    let mut interpreter: Interpreter = Interpreter::default();
+    run(&mut interpreter, &format!("var dir = {}; var path = dir + \"/notes.txt\";", quoted));
+
+    assert_eq!(run(&mut interpreter, "fileExists(path);"), "false");
+    run(&mut interpreter, "writeFile(path, \"one\ntwo\");");
+    run(&mut interpreter, "appendFile(path, \"\nthree\r\n\");");
+    assert_eq!(fs::read_to_string(directory.join("notes.txt")).expect("was written"), "one\ntwo\nthree\r\n");
+    assert_eq!(run(&mut interpreter, "fileExists(path);"), "true");
+    assert_eq!(run(&mut interpreter, "readFile(path).len;"), "15");
+
+    // Lines come back without their endings
+    assert_eq!(run(&mut interpreter, "\"|\".join(readLines(path));"), "one|two|three");
+
+    // Numbers and other values are written as print would show them
+    run(&mut interpreter, "writeFile(dir + \"/number.txt\", 42);");
+    assert_eq!(fs::read_to_string(directory.join("number.txt")).expect("was written"), "42");
+    assert_eq!(run(&mut interpreter, "\",\".join(listDir(dir));"), "notes.txt,number.txt");
+    fs::remove_dir_all(&directory).ok();
+}
+
+// Failures are errors a script can catch, naming the file
+#[test]
+fn missing_files_raise_errors() {
+    let (directory, quoted) = directory("missing");
+    let mut interpreter: Interpreter = Interpreter::default();
+    run(&mut interpreter, &format!("var dir = {};", quoted));
+    for call in ["readFile(dir + \"/none\")", "readLines(dir + \"/none\")", "listDir(dir + \"/none\")", "writeFile(dir + \"/none/file\", 1)"] {
+        let message: String = run(&mut interpreter, &format!("var m; try {{ {}; }} catch (e) {{ m = e.message; }} m;", call));
+        assert!(message.starts_with("Could not "), "{}: {}", call, message);
+        assert!(message.contains("none"), "{}: {}", call, message);
+    }
+
+    let diagnostics: Vec<Diagnostic> = interpreter.run("readFile(1);").expect_err("fails");
+    assert_eq!(diagnostics[0].message, "readFile() expects a string but got 1.0.");
+    fs::remove_dir_all(&directory).ok();
+}
+
+#[test]
+fn eprint_writes_to_the_error_output() {
+    let written: Rc<RefCell<String>> = Rc::new(RefCell::new(String::new()));
+    let mut interpreter: Interpreter = Interpreter::default();
+    let sink: Rc<RefCell<String>> = written.clone();
+    interpreter.set_error_output_callback(move |a| sink.borrow_mut().push_str(a));
+    interpreter.set_output(Vec::new());
+    run(&mut interpreter, "eprint(\"no newline\"); eprint(1); print \"out\";");
+    assert_eq!(written.borrow().as_str(), "no newline1");
+}
+
+// readLine reads standard input a line at a time, then gives nil
+#[test]
+fn read_line_reads_stdin() {
+    let (directory, _) = directory("stdin");
+    let script: PathBuf = directory.join("echo.lox");
+    fs::write(&script, "var line = readLine(); while (line) { print \"[\" + line + \"]\"; line = readLine(); }").expect("writes the script");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_crlox")).arg(&script)
+        .stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().expect("crlox to run");
+    child.stdin.take().expect("stdin").write_all(b"first\r\nsecond\nlast").expect("writes");
+    let output: Output = child.wait_with_output().expect("crlox to finish");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "[first]\n[second]\n[last]\n");
+    fs::remove_dir_all(&directory).ok();
+}
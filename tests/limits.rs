@@ -0,0 +1,53 @@
+use crlox::{Backend, Diagnostic, DumpFormat, Interpreter, Limits};
+
+/*
+Limits
+
+These run on the test harness's own threads, which have a small stack, to check that scripts
+nested as deep as the default limits allow are stopped with an error rather than overflowing it.
+*/
+
+fn interpreters() -> Vec<Interpreter> {
+    let mut vm: Interpreter = Interpreter::default();
+    vm.set_backend(Backend::Vm);
+    vec![Interpreter::default(), vm]
+}
+
+fn messages(result: Result<crlox::Value, Vec<Diagnostic>>) -> Vec<String> {
+    result.expect_err("is stopped").iter().map(|a| a.message.clone()).collect()
+}
+
+// Nesting just inside the limit runs, anything deeper is refused before it runs
+#[test]
+fn deep_nesting() {
+    let depth: usize = Limits::default().max_nesting_depth;
+    let within: String = format!("print {}1{};", "(".repeat(depth / 2), ")".repeat(depth / 2));
+    let beyond: String = format!("print {}1{};", "(".repeat(depth * 2), ")".repeat(depth * 2));
+    for mut interpreter in interpreters() {
+        interpreter.set_output(Vec::new());
+        interpreter.run(&within).expect("runs");
+        assert_eq!(messages(interpreter.run(&beyond)), vec!["Too much nesting."]);
+    }
+
+    let blocks: String = format!("{}print 1;{}", "{".repeat(depth / 2), "}".repeat(depth / 2));
+    let interpreter: Interpreter = Interpreter::default();
+    interpreter.dump_ast(&blocks, DumpFormat::Text).expect("prints");
+    interpreter.dump_ast(&blocks, DumpFormat::Json).expect("prints");
+    interpreter.disassemble(&blocks).expect("compiles");
+    interpreter.format(&within).expect("formats");
+}
+
+// Recursion is stopped at the call depth limit
+#[test]
+fn deep_recursion() {
+    let source: &str = "fun f(n) { return f(n + 1); } f(0);";
+    for mut interpreter in interpreters() {
+        assert_eq!(messages(interpreter.run(source)), vec!["Stack overflow."]);
+    }
+
+    let depth: usize = Limits::default().max_call_depth - 1;
+    for mut interpreter in interpreters() {
+        let value: crlox::Value = interpreter.run(&format!("fun f(n) {{ if (n == 0) return 0; return 1 + f(n - 1); }} f({});", depth)).expect("runs");
+        assert_eq!(Interpreter::stringify(value), depth.to_string());
+    }
+}
@@ -0,0 +1,95 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use crlox::{Diagnostic, DiagnosticKind, Interpreter};
+
+/*
+Memory limit
+
+Counts every allocation the test binary makes, so that a script stopped for running out of memory
+can be checked to have stopped before building the value that would have taken it over the limit,
+not after.
+*/
+
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+// The peak is shared, so the tests take turns
+static SERIAL: Mutex<()> = Mutex::new(());
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let allocated: usize = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(allocated, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, pointer: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.dealloc(pointer, layout) }
+    }
+}
+
+#[global_allocator]
+static COUNTING: Counting = Counting;
+
+const MAX_MEMORY: usize = 1_000_000;
+
+// Run a script under the memory limit, giving back how it was stopped and the most it had
+// allocated at once beyond what was allocated before it started
+fn run_limited(source: &str) -> (Vec<Diagnostic>, usize) {
+    let mut interpreter: Interpreter = Interpreter::default();
+    interpreter.set_max_memory(Some(MAX_MEMORY));
+    interpreter.set_output(Vec::new());
+    let before: usize = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+    let diagnostics: Vec<Diagnostic> = match interpreter.run(source) {
+        Ok(_) => panic!("{} ran without running out of memory", source),
+        Err(a) => a
+    };
+    (diagnostics, PEAK.load(Ordering::Relaxed) - before)
+}
+
+fn assert_stopped_early(source: &str) {
+    let (diagnostics, peak) = run_limited(source);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].kind, DiagnosticKind::Aborted, "{}", diagnostics[0]);
+    assert_eq!(diagnostics[0].message, "Out of memory.");
+    assert!(peak < 16 * MAX_MEMORY, "{} peaked at {} bytes", source, peak);
+}
+
+// A 32 KB string, built within the limit
+const BIG: &str = "var s = \"x\"; for (var i = 0; i < 15; i = i + 1) { s = s + s; }\n";
+
+#[test]
+fn string_natives_check_before_building() {
+    let _serial = SERIAL.lock().unwrap_or_else(|a| a.into_inner());
+    assert_stopped_early(&format!("{}s.replace(\"x\", s);", BIG));
+    assert_stopped_early(&format!("{}(s + s).split(\"\");", BIG));
+    assert_stopped_early(&format!("{}var parts = \"a,b\".split(\",\"); for (var i = 0; i < 40; i = i + 1) {{ parts.push(s); }} \"\".join(parts);", BIG));
+}
+
+#[test]
+fn reading_files_checks_before_reading() {
+    let _serial = SERIAL.lock().unwrap_or_else(|a| a.into_inner());
+    let path = std::env::temp_dir().join(format!("crlox-memory-{}.txt", std::process::id()));
+    fs::write(&path, "line\n".repeat(2 * MAX_MEMORY / 5)).expect("writes");
+    let quoted: String = format!("{:?}", path.to_string_lossy());
+    assert_stopped_early(&format!("readFile({});", quoted));
+    assert_stopped_early(&format!("readLines({});", quoted));
+    fs::remove_file(&path).expect("removes");
+}
+
+// Within the limit, the same natives still work
+#[test]
+fn natives_run_within_the_limit() {
+    let _serial = SERIAL.lock().unwrap_or_else(|a| a.into_inner());
+    let mut interpreter: Interpreter = Interpreter::default();
+    interpreter.set_max_memory(Some(MAX_MEMORY));
+    let source: &str = "var s = \"a-b-c\"; s.replace(\"-\", \"+\").split(\"+\").len + \"-\".join(s.split(\"-\")).len;";
+    let value: crlox::Value = interpreter.run(source).expect("runs");
+    assert_eq!(Interpreter::stringify(value), "8");
+}
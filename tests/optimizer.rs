@@ -0,0 +1,58 @@
+use crlox::{Backend, Diagnostic, Interpreter};
+
+/*
+Optimizer
+
+What can be worked out before running is, and nothing else: folding never changes what a script
+prints or the errors it raises.
+*/
+
+fn listing(source: &str) -> String {
+    Interpreter::default().disassemble(source).expect("compiles")
+}
+
+#[test]
+fn folds_arithmetic() {
+    let code: String = listing("print 1 + 2 * 3 - 4 / 2;");
+    assert!(code.contains("Constant            0 5"), "{}", code);
+    assert!(!code.contains("Add") && !code.contains("Multiply") && !code.contains("Divide"), "{}", code);
+
+    let code: String = listing("print \"con\" + \"cat\";");
+    assert!(code.contains("\"concat\""), "{}", code);
+    assert!(!code.contains("Add"), "{}", code);
+}
+
+#[test]
+fn drops_branches_that_cant_run() {
+    let code: String = listing("if (false) print \"never\"; else print \"always\";");
+    assert!(!code.contains("never") && code.contains("always"), "{}", code);
+    assert!(!code.contains("Jump"), "{}", code);
+}
+
+#[test]
+fn leaves_variables_alone() {
+    let code: String = listing("var a = 1; print a + 2;");
+    assert!(code.contains("GetGlobal") && code.contains("Add"), "{}", code);
+}
+
+// Operations that raise an error are left to raise it when they run, at the same place
+#[test]
+fn keeps_errors() {
+    for source in ["print 1 / 0;", "print 1 + nil;", "print \"a\" == \"a\";", "print -\"a\";"] {
+        for backend in [Backend::TreeWalker, Backend::Vm] {
+            let mut interpreter: Interpreter = Interpreter::default();
+            interpreter.set_backend(backend);
+            let errors: Vec<Diagnostic> = interpreter.run(source).expect_err("raises an error");
+            assert_eq!(errors[0].line, 1, "{}", source);
+        }
+    }
+}
+
+// A folded script prints the same as one that can't be folded
+#[test]
+fn folding_keeps_results() {
+    let folded: String = Interpreter::stringify(Interpreter::default().run("(1 + 2) * 3 - 10 / 4;").expect("runs"));
+    let unfolded: String = Interpreter::stringify(Interpreter::default().run("var a = 1; var b = 2; var c = 3; (a + b) * c - 10 / 4;").expect("runs"));
+    assert_eq!(folded, "6.5");
+    assert_eq!(folded, unfolded);
+}
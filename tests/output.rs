@@ -0,0 +1,108 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use crlox::{Backend, Diagnostic, Interpreter};
+
+/*
+Output
+
+Pointing print, eprint and diagnostics somewhere other than stdout and stderr, either at a writer
+or at a callback.
+*/
+
+// A writer the test can still read from once it has been handed to an interpreter
+#[derive(Clone, Default)]
+struct Shared(Rc<RefCell<Vec<u8>>>);
+
+impl Shared {
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).to_string()
+    }
+}
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn captured(backend: Backend) -> (Interpreter, Shared, Shared) {
+    let output: Shared = Shared::default();
+    let error_output: Shared = Shared::default();
+    let mut interpreter: Interpreter = Interpreter::default();
+    interpreter.set_backend(backend);
+    interpreter.set_output(output.clone());
+    interpreter.set_error_output(error_output.clone());
+    (interpreter, output, error_output)
+}
+
+#[test]
+fn print_goes_to_the_writer() {
+    for backend in [Backend::TreeWalker, Backend::Vm] {
+        let (mut interpreter, output, error_output) = captured(backend);
+        interpreter.run("print 1; eprint(\"warning\"); print \"two\";").expect("runs");
+        assert_eq!(output.text(), "1\ntwo\n");
+        assert_eq!(error_output.text(), "warning");
+    }
+}
+
+// Whatever was printed before an error is kept, and the error itself is left for the host to report
+#[test]
+fn output_before_an_error_is_kept() {
+    for backend in [Backend::TreeWalker, Backend::Vm] {
+        let (mut interpreter, output, error_output) = captured(backend);
+        let diagnostics: Vec<Diagnostic> = interpreter.run("print \"before\";\nprint -nil;\nprint \"after\";").expect_err("fails");
+        assert_eq!(output.text(), "before\n");
+        assert_eq!(error_output.text(), "");
+
+        interpreter.report(&diagnostics);
+        assert!(error_output.text().starts_with("[line 2] Error"), "{}", error_output.text());
+        assert!(error_output.text().ends_with("  [line 2] in script\n"), "{}", error_output.text());
+    }
+}
+
+// exit() isn't an error, so there is nothing to report
+#[test]
+fn report_leaves_out_exit() {
+    let (mut interpreter, output, error_output) = captured(Backend::TreeWalker);
+    let diagnostics: Vec<Diagnostic> = interpreter.run("print \"bye\"; exit(2);").expect_err("stops");
+    interpreter.report(&diagnostics);
+    assert_eq!(output.text(), "bye\n");
+    assert_eq!(error_output.text(), "");
+}
+
+// Each print arrives in a single call, newline included
+#[test]
+fn callbacks_get_one_call_per_print() {
+    let calls: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(Vec::new()));
+    let errors: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter: Interpreter = Interpreter::default();
+    let sink: Rc<RefCell<Vec<String>>> = calls.clone();
+    interpreter.set_output_callback(move |a| sink.borrow_mut().push(a.to_string()));
+    let sink: Rc<RefCell<Vec<String>>> = errors.clone();
+    interpreter.set_error_output_callback(move |a| sink.borrow_mut().push(a.to_string()));
+
+    interpreter.run("for (var i = 0; i < 3; i = i + 1) print i;").expect("runs");
+    assert_eq!(*calls.borrow(), vec!["0\n", "1\n", "2\n"]);
+
+    let diagnostics: Vec<Diagnostic> = interpreter.run("print;").expect_err("fails");
+    interpreter.report(&diagnostics);
+    assert_eq!(*errors.borrow(), vec!["[line 1] Error at ';': Expect expression.\n"]);
+}
+
+// Swapping the output part way only moves what is printed from then on
+#[test]
+fn output_can_be_swapped() {
+    let (mut interpreter, first, _) = captured(Backend::TreeWalker);
+    interpreter.run("print 1;").expect("runs");
+    let second: Shared = Shared::default();
+    interpreter.set_output(second.clone());
+    interpreter.run("print 2;").expect("runs");
+    assert_eq!(first.text(), "1\n");
+    assert_eq!(second.text(), "2\n");
+}
@@ -0,0 +1,98 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use crlox::{Capabilities, Diagnostic, Interpreter, Limits, Value};
+
+/*
+Sandbox
+
+Natives whose capability is turned off stay defined, but calling one raises an error naming the
+capability instead of reaching outside of the interpreter.
+*/
+
+fn sandboxed(capabilities: Capabilities) -> Interpreter {
+    let mut interpreter: Interpreter = Interpreter::build_interpreter(Limits::default(), capabilities, &[]);
+    interpreter.set_output(Vec::new());
+    interpreter.set_error_output(Vec::new());
+    interpreter
+}
+
+fn error(interpreter: &mut Interpreter, source: &str) -> Diagnostic {
+    let mut diagnostics: Vec<Diagnostic> = interpreter.run(source).expect_err("is denied");
+    assert_eq!(diagnostics.len(), 1);
+    diagnostics.remove(0)
+}
+
+#[test]
+fn every_capability_is_denied() {
+    let mut interpreter: Interpreter = sandboxed(Capabilities::sandbox());
+    for (call, capability) in [
+        ("readFile(\"a\")", "file I/O"),
+        ("readLines(\"a\")", "file I/O"),
+        ("writeFile(\"a\", 1)", "file I/O"),
+        ("appendFile(\"a\", 1)", "file I/O"),
+        ("fileExists(\"a\")", "file I/O"),
+        ("listDir(\".\")", "file I/O"),
+        ("readLine()", "stdin"),
+        ("getEnv(\"PATH\")", "environment"),
+        ("exit(0)", "process exit"),
+        ("clock()", "clock")
+    ] {
+        let name: &str = call.split('(').next().expect("has a name");
+        let diagnostic: Diagnostic = error(&mut interpreter, &format!("\n{};", call));
+        assert_eq!(diagnostic.message, format!("{}() is not available, the {} capability is disabled.", name, capability));
+        assert_eq!(diagnostic.line, 2);
+        assert_eq!(diagnostic.exit_status(), None);
+    }
+}
+
+// A denied native still takes its usual arguments, and is an error a script can catch
+#[test]
+fn denied_natives_behave_like_natives() {
+    let mut interpreter: Interpreter = sandboxed(Capabilities::sandbox());
+    let value: Value = interpreter.run("var m; try { clock(); } catch (e) { m = e.message; } m;").expect("runs");
+    assert_eq!(Interpreter::stringify(value), "clock() is not available, the clock capability is disabled.");
+    assert_eq!(error(&mut interpreter, "clock(1);").message, "Expected 0 arguments but got 1.");
+    assert_eq!(Interpreter::stringify(interpreter.run("clock;").expect("runs")), "<native fn clock>");
+
+    // Natives without effects outside of the interpreter are all still there
+    let value: Value = interpreter.run("sqrt(16) + \"-\".join(\"a,b\".split(\",\")).len;").expect("runs");
+    assert_eq!(Interpreter::stringify(value), "7");
+}
+
+#[test]
+fn denied_writes_leave_no_file() {
+    let path: PathBuf = std::env::temp_dir().join(format!("crlox-sandbox-{}", std::process::id()));
+    let mut interpreter: Interpreter = sandboxed(Capabilities::sandbox());
+    error(&mut interpreter, &format!("writeFile({:?}, 1);", path.to_string_lossy()));
+    assert!(!path.exists());
+}
+
+// Each capability can be turned off on its own
+#[test]
+fn capabilities_are_separate() {
+    let capabilities: Capabilities = Capabilities {file_io: false, ..Capabilities::default()};
+    let mut interpreter: Interpreter = sandboxed(capabilities);
+    error(&mut interpreter, "fileExists(\"a\");");
+    interpreter.run("clock(); getEnv(\"PATH\");").expect("runs");
+    assert_eq!(error(&mut interpreter, "exit(4);").exit_status(), Some(4));
+
+    let capabilities: Capabilities = Capabilities {exit: false, ..Capabilities::default()};
+    let mut interpreter: Interpreter = sandboxed(capabilities);
+    assert_eq!(error(&mut interpreter, "exit(4);").exit_status(), None);
+    interpreter.run("fileExists(\"a\");").expect("runs");
+}
+
+#[test]
+fn command_line_sandbox() {
+    let path: PathBuf = std::env::temp_dir().join(format!("crlox-sandbox-cli-{}.lox", std::process::id()));
+    fs::write(&path, "print \"start\";\nprint getEnv(\"PATH\");\n").expect("writes the script");
+    let output: Output = Command::new(env!("CARGO_BIN_EXE_crlox")).arg("--sandbox").arg(&path).output().expect("crlox to run");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "start\n");
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("[line 2] Error at ')': getEnv() is not available, the environment capability is disabled."));
+
+    let output: Output = Command::new(env!("CARGO_BIN_EXE_crlox")).arg(&path).output().expect("crlox to run");
+    assert_eq!(output.status.code(), Some(0));
+    fs::remove_file(&path).ok();
+}
@@ -0,0 +1,77 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/*
+Script tests
+
+Runs every script in tests/scripts/ through the crlox binary on both backends, with and without
+--gc-stress, and checks what it wrote and how it exited against the comments in the script:
+
+  // expect: text    a line the script prints to stdout
+  // stderr: text    a line it writes to stderr, diagnostics included
+  // exit: n         the status it exits with, 0 if not given
+
+The lines have to match in full and in order, with nothing else written.
+*/
+
+const MODES: [&[&str]; 4] = [&[], &["--gc-stress"], &["--vm"], &["--vm", "--gc-stress"]];
+
+struct Expected {
+    stdout: Vec<String>,
+    stderr: Vec<String>,
+    exit: i32
+}
+
+fn expected(source: &str) -> Expected {
+    let mut expected: Expected = Expected {stdout: Vec::new(), stderr: Vec::new(), exit: 0};
+    for line in source.lines() {
+        let line: &str = line.trim_start();
+        if let Some(a) = line.strip_prefix("// expect: ") {
+            expected.stdout.push(a.to_string());
+        } else if let Some(a) = line.strip_prefix("// stderr: ") {
+            expected.stderr.push(a.to_string());
+        } else if let Some(a) = line.strip_prefix("// exit: ") {
+            expected.exit = a.trim().parse().expect("an exit status");
+        }
+    }
+    expected
+}
+
+fn lines(bytes: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(bytes).lines().map(|a| a.to_string()).collect()
+}
+
+// Run one script in every mode, giving back what went wrong
+fn check(script: &Path) -> Vec<String> {
+    let expected: Expected = expected(&fs::read_to_string(script).expect("a readable script"));
+    let mut failures: Vec<String> = Vec::new();
+    for mode in MODES {
+        let output: Output = Command::new(env!("CARGO_BIN_EXE_crlox")).args(mode).arg(script).output().expect("crlox to run");
+        let at: String = format!("{} {}", script.display(), mode.join(" "));
+        if lines(&output.stdout) != expected.stdout {
+            failures.push(format!("{}: stdout was {:?}, expected {:?}", at, lines(&output.stdout), expected.stdout));
+        }
+        if lines(&output.stderr) != expected.stderr {
+            failures.push(format!("{}: stderr was {:?}, expected {:?}", at, lines(&output.stderr), expected.stderr));
+        }
+        if output.status.code() != Some(expected.exit) {
+            failures.push(format!("{}: exited with {:?}, expected {}", at, output.status.code(), expected.exit));
+        }
+    }
+    failures
+}
+
+#[test]
+fn scripts() {
+    let directory: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("scripts");
+    let mut scripts: Vec<PathBuf> = fs::read_dir(directory).expect("tests/scripts")
+        .filter_map(|a| a.ok().map(|b| b.path()))
+        .filter(|a| a.extension().is_some_and(|b| b == "lox"))
+        .collect();
+    scripts.sort();
+    assert!(!scripts.is_empty());
+
+    let failures: Vec<String> = scripts.iter().flat_map(|a| check(a)).collect();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
@@ -0,0 +1,53 @@
+use crlox::{Capabilities, Diagnostic, Interpreter, Limits, Value};
+
+fn error(interpreter: &mut Interpreter, source: &str) -> Diagnostic {
+    let mut diagnostics: Vec<Diagnostic> = interpreter.run(source).expect_err("is stopped");
+    assert_eq!(diagnostics.len(), 1);
+    diagnostics.remove(0)
+}
+
+// exit() hands its status to the host, anything that isn't a status is an error instead
+#[test]
+fn exit_takes_a_status() {
+    let mut interpreter: Interpreter = Interpreter::default();
+    assert_eq!(error(&mut interpreter, "exit(0);").exit_status(), Some(0));
+    assert_eq!(error(&mut interpreter, "exit(255);").exit_status(), Some(255));
+
+    for (status, message) in [
+        ("4294967296", "exit() expects a status from 0 to 255 but got 4294967296."),
+        ("256", "exit() expects a status from 0 to 255 but got 256."),
+        ("-1", "exit() expects a status from 0 to 255 but got -1."),
+        ("1.5", "exit() expects a whole number but got 1.5."),
+        ("\"1\"", "exit() expects a number but got \"1\".")
+    ] {
+        let diagnostic: Diagnostic = error(&mut interpreter, &format!("exit({});", status));
+        assert_eq!(diagnostic.exit_status(), None);
+        assert_eq!(diagnostic.message, message);
+    }
+
+    // Unlike exit itself, a bad status can be caught
+    let value: Value = interpreter.run("var caught = false; try { exit(256); } catch (e) { caught = true; } caught;").expect("runs");
+    assert_eq!(Interpreter::stringify(value), "true");
+}
+
+// args holds what followed the script path
+#[test]
+fn args_are_a_list_of_strings() {
+    let arguments: Vec<String> = vec!["one".to_string(), "two words".to_string()];
+    let mut interpreter: Interpreter = Interpreter::build_interpreter(Limits::default(), Capabilities::default(), &arguments);
+    let value: Value = interpreter.run("args.len + \" \" + \",\".join(args);").expect("runs");
+    assert_eq!(Interpreter::stringify(value), "2 one,two words");
+
+    let value: Value = Interpreter::default().run("args.len;").expect("runs");
+    assert_eq!(Interpreter::stringify(value), "0");
+}
+
+#[test]
+fn get_env_reads_variables() {
+    std::env::set_var("CRLOX_TEST_VARIABLE", "set");
+    let mut interpreter: Interpreter = Interpreter::default();
+    let value: Value = interpreter.run("getEnv(\"CRLOX_TEST_VARIABLE\");").expect("runs");
+    assert_eq!(Interpreter::stringify(value), "set");
+    let value: Value = interpreter.run("getEnv(\"CRLOX_TEST_VARIABLE_NOT_SET\");").expect("runs");
+    assert_eq!(Interpreter::stringify(value), "nill");
+}
@@ -0,0 +1,72 @@
+use crlox::{Diagnostic, DumpFormat, Interpreter};
+
+/*
+Token dumps
+
+What `crlox tokens` prints: each token on a line of its own with the line and column it starts at,
+its type, its text and the value it stands for, or the same as JSON for tools.
+*/
+
+// The lines of a listing, each with its newline
+fn listing(lines: &[&str]) -> String {
+    lines.iter().map(|a| format!("{}\n", a)).collect()
+}
+
+fn tokens(source: &str, format: DumpFormat) -> String {
+    Interpreter::default().dump_tokens(source, format).unwrap_or_else(|a| panic!("{} failed: {}", source, a[0]))
+}
+
+#[test]
+fn tokens_are_listed_with_where_they_start() {
+    assert_eq!(tokens("print 1.5 + x;", DumpFormat::Text), listing(&[
+        "   1:1    Print         print",
+        "   1:7    Num           1.5              1.5",
+        "   1:11   Plus          +",
+        "   1:13   Id            x",
+        "   1:14   Semicolon     ;",
+        "   1:15   Eof"
+    ]));
+}
+
+// Strings keep to one line however many they span, and their values are quoted so spaces and
+// escapes can't be mistaken for the end of the line
+#[test]
+fn strings_are_quoted() {
+    assert_eq!(tokens("var s = \"a\tb\nc\";\n  x(\"two words\") ;", DumpFormat::Text), listing(&[
+        "   1:1    Var           var",
+        "   1:5    Id            s",
+        "   1:7    Equal         =",
+        "   1:9    String        \"a\\tb\\nc\"        \"a\\tb\\nc\"",
+        "   2:3    Semicolon     ;",
+        "   3:3    Id            x",
+        "   3:4    LParen        (",
+        "   3:5    String        \"two words\"      \"two words\"",
+        "   3:16   RParen        )",
+        "   3:18   Semicolon     ;",
+        "   3:19   Eof"
+    ]));
+    assert_eq!(tokens("\"\";", DumpFormat::Text).lines().next(), Some("   1:1    String        \"\"               \"\""));
+}
+
+// Comments and whitespace make no tokens, but still move the columns along
+#[test]
+fn comments_are_skipped() {
+    assert_eq!(tokens("// note\n\t print; // more", DumpFormat::Text), "   2:3    Print         print\n   2:8    Semicolon     ;\n   2:17   Eof\n");
+}
+
+#[test]
+fn json_gives_the_same_tokens() {
+    assert_eq!(tokens("print \"a b\";", DumpFormat::Json), "{\"version\": 2, \"tokens\": [\
+{\"type\": \"Print\", \"lexeme\": \"print\", \"literal\": null, \"line\": 1, \"column\": 1}, \
+{\"type\": \"String\", \"lexeme\": \"\\\"a b\\\"\", \"literal\": \"a b\", \"line\": 1, \"column\": 7}, \
+{\"type\": \"Semicolon\", \"lexeme\": \";\", \"literal\": null, \"line\": 1, \"column\": 12}, \
+{\"type\": \"Eof\", \"lexeme\": \"\", \"literal\": null, \"line\": 1, \"column\": 13}]}\n");
+}
+
+// Only scanning errors stop a listing, source that wouldn't parse is listed all the same
+#[test]
+fn scan_errors_are_reported() {
+    assert!(tokens("print print ;;", DumpFormat::Text).contains("1:7    Print"));
+    let diagnostics: Vec<Diagnostic> = Interpreter::default().dump_tokens("print \"open;", DumpFormat::Text).expect_err("fails");
+    assert_eq!(diagnostics[0].to_string(), "[line 1] Error: Undetermined String");
+}